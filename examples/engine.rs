@@ -0,0 +1,146 @@
+//! A minimal alpha-beta searcher showing how to integrate tablebase probing into an engine.
+//!
+//! Usage: `engine <tb-dir> <fen> [depth]`
+
+use cozy_chess::{Board, GameStatus, Piece};
+use cozy_syzygy::search::{adjudicate, filter_root_moves, should_probe, Bound, ProbeConfig};
+use cozy_syzygy::Tablebase;
+
+const MATE: i32 = 30000;
+const TB_WIN: i32 = 20000;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let (Some(dir), Some(fen)) = (args.next(), args.next()) else {
+        eprintln!("usage: engine <tb-dir> <fen> [depth]");
+        std::process::exit(2);
+    };
+    let depth = args.next().map_or(6, |d| d.parse().expect("invalid depth"));
+
+    let mut tb = Tablebase::new();
+    let summary = tb.add_directory(dir).expect("failed to load tablebase");
+    eprintln!("tablebase: {summary}");
+    let board: Board = fen.parse().expect("invalid fen");
+
+    let mut searcher = Searcher {
+        tb: &tb,
+        cfg: ProbeConfig::new(&tb),
+        nodes: 0,
+        tb_hits: 0,
+    };
+    let root_moves = match filter_root_moves(&tb, &board) {
+        Ok(filter) => {
+            searcher.tb_hits += filter.tb_hits;
+            filter.moves
+        }
+        // The position isn't covered, so every move is searched.
+        Err(_) => {
+            let mut moves = vec![];
+            board.generate_moves(|mvs| {
+                moves.extend(mvs);
+                false
+            });
+            moves
+        }
+    };
+
+    for depth in 1..=depth {
+        let mut best = None;
+        let mut alpha = -MATE;
+        for &mv in &root_moves {
+            let mut child = board.clone();
+            child.play_unchecked(mv);
+            let v = -searcher.search(&child, depth - 1, 1, -MATE, -alpha);
+            if best.is_none() || v > alpha {
+                alpha = v;
+                best = Some(mv);
+            }
+        }
+
+        match best {
+            Some(mv) => println!(
+                "depth {depth} score {alpha} nodes {} tbhits {} bestmove {mv}",
+                searcher.nodes, searcher.tb_hits
+            ),
+            None => {
+                println!("no legal moves");
+                break;
+            }
+        }
+    }
+}
+
+struct Searcher<'a> {
+    tb: &'a Tablebase,
+    cfg: ProbeConfig,
+    nodes: u64,
+    tb_hits: u64,
+}
+
+impl Searcher<'_> {
+    fn search(&mut self, board: &Board, depth: i32, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+
+        match board.status() {
+            GameStatus::Won => return -(MATE - ply),
+            GameStatus::Drawn => return 0,
+            GameStatus::Ongoing => {}
+        }
+        if board.halfmove_clock() >= 100 {
+            return 0;
+        }
+
+        let mut max_score = MATE;
+        let pieces = board.occupied().len();
+        if should_probe(depth, board.halfmove_clock(), pieces, &self.cfg) {
+            if let Ok((wdl, _)) = self.tb.probe_wdl(board) {
+                self.tb_hits += 1;
+                let (score, bound) = adjudicate(wdl, ply as u32, TB_WIN);
+                match bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    Bound::Lower => alpha = alpha.max(score),
+                    Bound::Upper => max_score = score,
+                }
+            }
+        }
+
+        if depth <= 0 {
+            return evaluate(board);
+        }
+
+        let mut moves = vec![];
+        board.generate_moves(|mvs| {
+            moves.extend(mvs);
+            false
+        });
+
+        for mv in moves {
+            let mut child = board.clone();
+            child.play_unchecked(mv);
+            let v = -self.search(&child, depth - 1, ply + 1, -beta, -alpha);
+            if v > alpha {
+                alpha = v;
+                if v >= beta {
+                    break;
+                }
+            }
+        }
+
+        alpha.min(max_score)
+    }
+}
+
+fn evaluate(board: &Board) -> i32 {
+    const VALUES: [i32; 6] = [100, 300, 300, 500, 900, 0];
+    let us = board.colors(board.side_to_move());
+    let them = board.colors(!board.side_to_move());
+    Piece::ALL
+        .into_iter()
+        .map(|p| {
+            let pieces = board.pieces(p);
+            VALUES[p as usize] * ((pieces & us).len() as i32 - (pieces & them).len() as i32)
+        })
+        .sum()
+}
@@ -0,0 +1,589 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
+
+use cozy_chess::{Board, Color, GameStatus};
+use cozy_syzygy::generate::random_positions;
+use cozy_syzygy::{
+    test_positions, ChecksumStatus, Checksums, ProbeError, Tablebase, TieBreak, ValueSource, Wdl,
+};
+
+const USAGE: &str = "\
+usage: cozy-syzygy <command> [args...]
+
+commands:
+  diff <reference> <dir>       compare the tablebase files in a directory against a
+                               reference directory, or against a checksum list in the
+                               format of md5sum, such as the official checksum.md5.
+                               Subdirectories are searched too.
+  probe [--json] <tb-dir>...   read one position per line from stdin and print its WDL
+                               value, and the value with the halfmove clock of the FEN
+                               when DTZ tables are loaded. With --json, print one JSON
+                               object per line, which also has the DTZ and best move,
+                               and the error if the position could not be probed.
+  adjudicate [--cursed-win] <tb-dir>...
+                               read one position per line from stdin and print the
+                               game result if the position is in the tablebase, taking
+                               its halfmove clock into account, or `none` otherwise.
+                               Wins and losses with a nonzero clock need their DTZ
+                               tables. With --cursed-win, wins that are drawn by the 50
+                               move rule are adjudicated as wins.
+  bench [--positions <n>] <tb-dir>...
+                               probe random positions of the loaded tables, 100000 by
+                               default, and print the throughput and latency percentiles.
+  line <fen> [--max-plies <n>] <tb-dir>...
+                               print the best line of play from a position, 100 plies
+                               at most by default, with the WDL value and DTZ of each
+                               move for the side playing it. DTZ values need the DTZ
+                               tables, and the line ends at mate or a draw by the rules.
+  selftest [--samples <n>] <tb-dir>...
+                               check the positions with known values whose tables are
+                               loaded, and cross-check random positions of every loaded
+                               table, 10000 by default, against their color-flipped
+                               mirrors.
+
+Positions are read as a FEN, an EPD, or a JSON object with a \"fen\" field, such as
+{\"fen\":\"8/8/8/8/8/2k5/8/KR6 w - - 0 1\",\"ply\":80}, so that game streams with one
+JSON object per move can be piped in.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("diff") => match &args[1..] {
+            [reference, dir] => diff(reference.as_ref(), dir.as_ref()),
+            _ => return usage(),
+        },
+        Some("probe") => match &args[1..] {
+            [flag, dirs @ ..] if flag == "--json" && !dirs.is_empty() => probe(dirs, true),
+            dirs if !dirs.is_empty() => probe(dirs, false),
+            _ => return usage(),
+        },
+        Some("adjudicate") => match &args[1..] {
+            [flag, dirs @ ..] if flag == "--cursed-win" && !dirs.is_empty() => {
+                adjudicate(dirs, true)
+            }
+            dirs if !dirs.is_empty() => adjudicate(dirs, false),
+            _ => return usage(),
+        },
+        Some("bench") => match &args[1..] {
+            [flag, n, dirs @ ..] if flag == "--positions" && !dirs.is_empty() => match n.parse() {
+                Ok(n) => bench(dirs, n),
+                Err(_) => return usage(),
+            },
+            dirs if !dirs.is_empty() => bench(dirs, 100_000),
+            _ => return usage(),
+        },
+        Some("line") => match &args[1..] {
+            [fen, flag, n, dirs @ ..] if flag == "--max-plies" && !dirs.is_empty() => {
+                match n.parse() {
+                    Ok(n) => line(fen, n, dirs),
+                    Err(_) => return usage(),
+                }
+            }
+            [fen, dirs @ ..] if !dirs.is_empty() => line(fen, 100, dirs),
+            _ => return usage(),
+        },
+        Some("selftest") => match &args[1..] {
+            [flag, n, dirs @ ..] if flag == "--samples" && !dirs.is_empty() => match n.parse() {
+                Ok(n) => selftest(dirs, n),
+                Err(_) => return usage(),
+            },
+            dirs if !dirs.is_empty() => selftest(dirs, 10_000),
+            _ => return usage(),
+        },
+        _ => return usage(),
+    };
+
+    match result {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn usage() -> ExitCode {
+    eprintln!("{USAGE}");
+    ExitCode::from(2)
+}
+
+/// Compares the tablebase files in `dir` against those in `reference`, which is either a directory
+/// or a checksum list.
+fn diff(reference: &Path, dir: &Path) -> Result<ExitCode, Box<dyn Error>> {
+    let actual = tablebase_files(dir)?;
+    let (expected, mut differences) = match reference.is_dir() {
+        true => diff_directory(reference, &actual)?,
+        false => diff_checksums(reference, &actual)?,
+    };
+
+    let mut extra = 0;
+    for name in actual.keys() {
+        if !expected.contains(name) {
+            println!("extra     {name}");
+            extra += 1;
+        }
+    }
+    differences += extra;
+
+    println!(
+        "{} files checked, {differences} differences",
+        expected.len() + extra
+    );
+
+    Ok(match differences {
+        0 => ExitCode::SUCCESS,
+        _ => ExitCode::FAILURE,
+    })
+}
+
+/// Compares the files in `actual` against those in the `reference` directory, returning the names
+/// of the reference files and the number of differences.
+///
+/// Files are matched by name. Files with equal sizes are compared byte-for-byte, since a content
+/// mismatch is exactly what a checksum comparison would detect.
+fn diff_directory(
+    reference: &Path,
+    actual: &BTreeMap<String, PathBuf>,
+) -> Result<(BTreeSet<String>, usize), Box<dyn Error>> {
+    let expected = tablebase_files(reference)?;
+    let mut differences = 0;
+    for (name, expected_path) in &expected {
+        let Some(actual_path) = actual.get(name) else {
+            println!("missing   {name}");
+            differences += 1;
+            continue;
+        };
+
+        let expected_len = expected_path.metadata()?.len();
+        let actual_len = actual_path.metadata()?.len();
+        if expected_len != actual_len {
+            println!("size      {name} ({actual_len} bytes, expected {expected_len})");
+            differences += 1;
+        } else if !same_contents(expected_path, actual_path)? {
+            println!("mismatch  {name}");
+            differences += 1;
+        }
+    }
+    Ok((expected.into_keys().collect(), differences))
+}
+
+/// Compares the files in `actual` against the checksum list at `list`, returning the names of the
+/// listed tablebase files and the number of differences.
+///
+/// Each file is read into memory to be hashed, one at a time.
+fn diff_checksums(
+    list: &Path,
+    actual: &BTreeMap<String, PathBuf>,
+) -> Result<(BTreeSet<String>, usize), Box<dyn Error>> {
+    let checksums = Checksums::parse(&std::fs::read_to_string(list)?)
+        .ok_or_else(|| format!("{} is not a checksum list", list.display()))?;
+    let expected: BTreeSet<String> = checksums
+        .file_names()
+        .filter(|name| is_tablebase_file(name.as_ref()))
+        .map(str::to_owned)
+        .collect();
+
+    let mut differences = 0;
+    for name in &expected {
+        let Some(actual_path) = actual.get(name) else {
+            println!("missing   {name}");
+            differences += 1;
+            continue;
+        };
+        if checksums.check(name, &std::fs::read(actual_path)?) != ChecksumStatus::Match {
+            println!("checksum  {name}");
+            differences += 1;
+        }
+    }
+    Ok((expected, differences))
+}
+
+/// Probes the positions read from stdin, one per line.
+fn probe(dirs: &[String], json: bool) -> Result<ExitCode, Box<dyn Error>> {
+    let tb = load(dirs)?;
+
+    let mut stdout = std::io::stdout().lock();
+    for line in std::io::stdin().lock().lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let Some(board) = parse_position(line) else {
+            match json {
+                true => writeln!(
+                    stdout,
+                    r#"{{"fen":{},"error":"invalid position"}}"#,
+                    json_string(line)
+                )?,
+                false => writeln!(stdout, "{line}: invalid position")?,
+            }
+            continue;
+        };
+
+        let table = tb.classify(&board).material;
+        let result = tb.probe_wdl_with_source(&board);
+        // This needs the DTZ tables, so it is only reported when they are loaded.
+        let clock_wdl = tb.probe_wdl_with_halfmove_clock(&board).ok();
+        match (json, result) {
+            (true, Ok((wdl, capture, source))) => {
+                // These need the DTZ tables, so they are null when the tables are missing.
+                let dtz = tb.probe_dtz(&board).ok();
+                let best_move = tb.best_move(&board, TieBreak::MoveOrder).ok().flatten();
+                writeln!(
+                    stdout,
+                    r#"{{"fen":"{board}","wdl":"{}","clock_wdl":{},"dtz":{},"best_move":{},"capture":{capture},"source":"{}","table":"{table}"}}"#,
+                    wdl_name(wdl),
+                    clock_wdl.map_or("null".to_owned(), |wdl| format!(r#""{}""#, wdl_name(wdl))),
+                    dtz.map_or("null".to_owned(), |(dtz, _)| dtz.0.to_string()),
+                    best_move.map_or("null".to_owned(), |m| format!(r#""{}""#, m.mv)),
+                    source_name(source)
+                )?
+            }
+            (true, Err(e)) => writeln!(
+                stdout,
+                r#"{{"fen":"{board}","wdl":null,"clock_wdl":null,"dtz":null,"best_move":null,"capture":null,"source":null,"table":"{table}","error":{}}}"#,
+                json_string(&e.to_string())
+            )?,
+            (false, Ok((wdl, capture, _))) => {
+                write!(stdout, "{board}: {}", wdl_name(wdl))?;
+                if capture {
+                    write!(stdout, " with a capture")?;
+                }
+                match clock_wdl {
+                    Some(clock_wdl) if clock_wdl != wdl => {
+                        writeln!(stdout, ", {} with the halfmove clock", wdl_name(clock_wdl))?
+                    }
+                    _ => writeln!(stdout)?,
+                }
+            }
+            (false, Err(e)) => writeln!(stdout, "{board}: {e}")?,
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// `s` as a JSON string, with quotes.
+fn json_string(s: &str) -> String {
+    let mut json = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn wdl_name(wdl: Wdl) -> &'static str {
+    match wdl {
+        Wdl::Loss => "loss",
+        Wdl::BlessedLoss => "blessed-loss",
+        Wdl::Draw => "draw",
+        Wdl::CursedWin => "cursed-win",
+        Wdl::Win => "win",
+    }
+}
+
+fn source_name(source: ValueSource) -> &'static str {
+    match source {
+        ValueSource::Table => "table",
+        ValueSource::CaptureSearch => "capture-search",
+        ValueSource::BuiltIn => "built-in",
+        ValueSource::Fallback => "fallback",
+    }
+}
+
+fn load(dirs: &[String]) -> Result<Tablebase, Box<dyn Error>> {
+    let mut tb = Tablebase::new();
+    for dir in dirs {
+        tb.add_directory(dir)?;
+    }
+    Ok(tb)
+}
+
+/// Prints the best line of play from `fen`, one move per line, for at most `max_plies` plies.
+///
+/// Moves are chosen with [`Tablebase::best_move`], so winning lines make progress towards
+/// resetting the 50 move counter when the DTZ tables are loaded.
+fn line(fen: &str, max_plies: u32, dirs: &[String]) -> Result<ExitCode, Box<dyn Error>> {
+    let tb = load(dirs)?;
+    let mut board = parse_position(fen).ok_or("invalid position")?;
+
+    let mut stdout = std::io::stdout().lock();
+    for ply in 1..=max_plies {
+        if board.status() != GameStatus::Ongoing {
+            break;
+        }
+        let Some(best) = tb.best_move(&board, TieBreak::MoveOrder)? else {
+            break;
+        };
+        write!(stdout, "{ply:>3}. {} {}", best.mv, wdl_name(best.wdl))?;
+        match best.dtz {
+            Some(dtz) => writeln!(stdout, " dtz {}", dtz.0)?,
+            None => writeln!(stdout)?,
+        }
+        board.play_unchecked(best.mv);
+    }
+
+    match board.status() {
+        GameStatus::Won => writeln!(stdout, "{board}: checkmate")?,
+        GameStatus::Drawn => writeln!(stdout, "{board}: draw")?,
+        GameStatus::Ongoing => writeln!(stdout, "{board}")?,
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Adjudicates the positions read from stdin, one per line.
+///
+/// The output is flushed after every line so that this can be driven interactively by a match
+/// runner.
+fn adjudicate(dirs: &[String], cursed_win_is_win: bool) -> Result<ExitCode, Box<dyn Error>> {
+    let tb = load(dirs)?;
+
+    let mut stdout = std::io::stdout().lock();
+    for line in std::io::stdin().lock().lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let result = match parse_position(line) {
+            Some(board) => match tb.probe_wdl_with_halfmove_clock(&board) {
+                Ok(wdl) => {
+                    let wdl = match cursed_win_is_win {
+                        true => wdl.ignoring_50_move_rule(),
+                        false => wdl,
+                    };
+                    game_result(wdl, board.side_to_move())
+                }
+                Err(_) => "none",
+            },
+            None => "invalid",
+        };
+        writeln!(stdout, "{result}")?;
+        stdout.flush()?;
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Probes random positions of the loaded tables, timing each probe.
+///
+/// The positions are generated before timing starts, and the materials are interleaved so that
+/// consecutive probes read different tables, as in a search.
+fn bench(dirs: &[String], positions: usize) -> Result<ExitCode, Box<dyn Error>> {
+    let tb = load(dirs)?;
+    let boards = sample_positions(&tb, positions)?;
+    if boards.is_empty() {
+        return Err("no positions to probe".into());
+    }
+
+    let mut latencies = Vec::with_capacity(boards.len());
+    let mut errors = 0;
+    let start = Instant::now();
+    for board in &boards {
+        let probe_start = Instant::now();
+        errors += tb.probe_wdl(board).is_err() as usize;
+        latencies.push(probe_start.elapsed());
+    }
+    let elapsed = start.elapsed();
+
+    latencies.sort();
+    let percentile = |p: usize| latencies[(latencies.len() - 1) * p / 100];
+    println!(
+        "{} probes in {elapsed:.2?}, {:.0} probes/s, {errors} errors",
+        boards.len(),
+        boards.len() as f64 / elapsed.as_secs_f64()
+    );
+    println!(
+        "latency: p50 {:.2?}, p90 {:.2?}, p99 {:.2?}, max {:.2?}",
+        percentile(50),
+        percentile(90),
+        percentile(99),
+        percentile(100)
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Checks the positions with known values, and cross-checks random positions.
+///
+/// Positions which need tables that are not loaded are skipped, so this can check partial
+/// downloads.
+fn selftest(dirs: &[String], samples: usize) -> Result<ExitCode, Box<dyn Error>> {
+    let tb = load(dirs)?;
+    let skipped =
+        |e: &ProbeError| matches!(e, ProbeError::MissingTable(_) | ProbeError::TooManyPieces);
+
+    let (mut passed, mut failed, mut skipped_known) = (0, 0, 0);
+    for pos in test_positions() {
+        let board: Board = pos.fen.parse()?;
+        match tb.probe_wdl(&board) {
+            Ok(result) if result == (pos.wdl, pos.capture) => passed += 1,
+            Ok((wdl, capture)) => {
+                println!(
+                    "wrong     {}: {}{}, expected {}{}",
+                    pos.fen,
+                    wdl_name(wdl),
+                    if capture { " with a capture" } else { "" },
+                    wdl_name(pos.wdl),
+                    if pos.capture { " with a capture" } else { "" }
+                );
+                failed += 1;
+            }
+            Err(e) if skipped(&e) => skipped_known += 1,
+            Err(e) => {
+                println!("error     {}: {e}", pos.fen);
+                failed += 1;
+            }
+        }
+    }
+    println!("{passed} known positions passed, {failed} failed, {skipped_known} skipped");
+
+    let (mut agreed, mut disagreed, mut skipped_sampled) = (0, 0, 0);
+    for board in sample_positions(&tb, samples)? {
+        match tb.probe_wdl_cross_checked(&board) {
+            Ok(_) => agreed += 1,
+            Err(e) if skipped(&e) => skipped_sampled += 1,
+            Err(e) => {
+                println!("error     {board}: {e}");
+                disagreed += 1;
+            }
+        }
+    }
+    println!("{agreed} random positions agreed, {disagreed} failed, {skipped_sampled} skipped");
+
+    Ok(match failed + disagreed {
+        0 => ExitCode::SUCCESS,
+        _ => ExitCode::FAILURE,
+    })
+}
+
+/// Random positions of the materials of the loaded tables, interleaved. The same tables always
+/// give the same positions.
+fn sample_positions(tb: &Tablebase, count: usize) -> Result<Vec<Board>, Box<dyn Error>> {
+    let mut materials: Vec<_> = tb.loaded_materials().map(|m| m.to_string()).collect();
+    if materials.is_empty() {
+        return Err("no tables were loaded".into());
+    }
+    materials.sort();
+    let mut generators = materials
+        .iter()
+        .enumerate()
+        .map(|(seed, m)| random_positions(m, seed as u64))
+        .collect::<Result<Vec<_>, _>>()?;
+    let n = generators.len();
+    Ok((0..count)
+        .filter_map(|i| generators[i % n].next())
+        .collect())
+}
+
+/// Parses a FEN, an EPD, or a JSON object with a FEN in its `fen` field. EPD operations and other
+/// JSON fields are ignored.
+fn parse_position(s: &str) -> Option<Board> {
+    if s.starts_with('{') {
+        return parse_position(&json_fen(s)?);
+    }
+    let fields: Vec<_> = s.split_whitespace().collect();
+    let is_number = |i: usize| fields.get(i).is_some_and(|f| f.parse::<u16>().is_ok());
+    match fields.len() {
+        n if n < 4 => None,
+        n if n >= 6 && is_number(4) && is_number(5) => fields[..6].join(" ").parse().ok(),
+        _ => format!("{} 0 1", fields[..4].join(" ")).parse().ok(),
+    }
+}
+
+/// The `fen` field of a JSON object. This is not a full JSON parser, but FENs only need the
+/// escapes of quotes, backslashes and slashes.
+fn json_fen(s: &str) -> Option<String> {
+    let rest = &s[s.find(r#""fen""#)? + r#""fen""#.len()..];
+    let rest = rest.trim_start().strip_prefix(':')?.trim_start();
+    let mut chars = rest.strip_prefix('"')?.chars();
+    let mut fen = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(fen),
+            '\\' => fen.push(chars.next()?),
+            c => fen.push(c),
+        }
+    }
+}
+
+fn game_result(wdl: Wdl, side_to_move: Color) -> &'static str {
+    match (wdl, side_to_move) {
+        (Wdl::Win, Color::White) | (Wdl::Loss, Color::Black) => "1-0",
+        (Wdl::Win, Color::Black) | (Wdl::Loss, Color::White) => "0-1",
+        _ => "1/2-1/2",
+    }
+}
+
+/// The tablebase files in `dir` and its subdirectories, by name. If a name is found more than once,
+/// the first file found is used, as when loading.
+fn tablebase_files(dir: &Path) -> std::io::Result<BTreeMap<String, PathBuf>> {
+    let mut files = BTreeMap::new();
+    let mut dirs = vec![dir.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        for f in std::fs::read_dir(dir)? {
+            let f = f?;
+            // Symbolic links are not followed, so this can't loop.
+            let file_type = f.file_type()?;
+            if file_type.is_dir() {
+                dirs.push(f.path());
+                continue;
+            }
+            let path = f.path();
+            if !file_type.is_file() || !is_tablebase_file(&path) {
+                continue;
+            }
+            if let Some(name) = path.file_name().and_then(|s| s.to_str()) {
+                files.entry(name.to_owned()).or_insert(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn is_tablebase_file(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|s| s.to_str()),
+        Some("rtbw" | "rtbz")
+    )
+}
+
+fn same_contents(a: &Path, b: &Path) -> std::io::Result<bool> {
+    let mut a = BufReader::new(File::open(a)?);
+    let mut b = BufReader::new(File::open(b)?);
+    let mut buf_a = vec![0; 1 << 16];
+    let mut buf_b = vec![0; 1 << 16];
+    loop {
+        let n = read_full(&mut a, &mut buf_a)?;
+        if n != read_full(&mut b, &mut buf_b)? || buf_a[..n] != buf_b[..n] {
+            return Ok(false);
+        }
+        if n == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+fn read_full(r: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match r.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+const SHARDS: usize = 16;
+const NONE: usize = usize::MAX;
+
+/// A cache of decoded compressed blocks, shared by every table of a tablebase.
+///
+/// A block is stored as the symbols it decodes to, each with the index of its first value, so a
+/// lookup in a cached block only needs to expand one symbol instead of Huffman decoding the block
+/// up to the index. Blocks are keyed by the address of their compressed data, which does not
+/// change while the table is loaded.
+///
+/// The cache is split into shards with separate locks so that concurrent probes rarely contend.
+/// Each shard evicts its least recently used block once it is full.
+pub(crate) struct BlockCache {
+    shards: Box<[Mutex<Shard>]>,
+}
+
+/// The symbols of a decoded block, as `(index of the first value, symbol)` pairs.
+pub(crate) type Symbols = Box<[(u32, u16)]>;
+
+impl BlockCache {
+    /// Create a cache holding up to `capacity` blocks, rounded up to a multiple of the number of
+    /// shards.
+    pub fn new(capacity: usize) -> Self {
+        let per_shard = capacity.div_ceil(SHARDS).max(1);
+        BlockCache {
+            shards: (0..SHARDS)
+                .map(|_| Mutex::new(Shard::new(per_shard)))
+                .collect(),
+        }
+    }
+
+    /// Find the symbol containing value `index` of a block, and the index of the value within
+    /// the symbol. If the block isn't cached, it is decoded with `decode` and inserted.
+    pub fn find(
+        &self,
+        key: usize,
+        index: u32,
+        decode: impl FnOnce() -> Option<Symbols>,
+    ) -> Option<(u16, u32)> {
+        // Fibonacci hashing, so that the addresses of neighbouring blocks spread over the shards.
+        let shard = (key as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> (64 - SHARDS.ilog2());
+        let mut shard = self.shards[shard as usize]
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let slot = match shard.touch(key) {
+            Some(slot) => {
+                shard.hits += 1;
+                slot
+            }
+            None => {
+                shard.misses += 1;
+                shard.insert(key, decode()?)
+            }
+        };
+        let symbols = &shard.slots[slot].symbols;
+        let i = symbols.partition_point(|&(start, _)| start <= index);
+        let (start, sym) = *symbols.get(i.checked_sub(1)?)?;
+        Some((sym, index - start))
+    }
+
+    /// Remove every block, keeping the hit and miss counts.
+    pub fn clear(&mut self) {
+        for shard in self.shards.iter_mut() {
+            let shard = shard.get_mut().unwrap_or_else(|e| e.into_inner());
+            shard.map.clear();
+            shard.slots.clear();
+            shard.head = NONE;
+            shard.tail = NONE;
+        }
+    }
+
+    /// The number of lookups which found their block in the cache, and the number which didn't.
+    pub fn hits_and_misses(&self) -> (u64, u64) {
+        self.shards.iter().fold((0, 0), |(hits, misses), shard| {
+            let shard = shard.lock().unwrap_or_else(|e| e.into_inner());
+            (hits + shard.hits, misses + shard.misses)
+        })
+    }
+}
+
+/// A least recently used cache, as a hash map into a doubly linked list of slots.
+struct Shard {
+    capacity: usize,
+    map: HashMap<usize, usize>,
+    slots: Vec<Slot>,
+    /// The most recently used slot.
+    head: usize,
+    /// The least recently used slot.
+    tail: usize,
+    hits: u64,
+    misses: u64,
+}
+
+struct Slot {
+    key: usize,
+    symbols: Symbols,
+    prev: usize,
+    next: usize,
+}
+
+impl Shard {
+    fn new(capacity: usize) -> Self {
+        Shard {
+            capacity,
+            map: HashMap::new(),
+            slots: vec![],
+            head: NONE,
+            tail: NONE,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Find the slot of a block, marking it as the most recently used.
+    fn touch(&mut self, key: usize) -> Option<usize> {
+        let slot = *self.map.get(&key)?;
+        self.unlink(slot);
+        self.push_front(slot);
+        Some(slot)
+    }
+
+    /// Insert a block which isn't in the cache, returning its slot.
+    fn insert(&mut self, key: usize, symbols: Symbols) -> usize {
+        let slot = if self.slots.len() < self.capacity {
+            self.slots.push(Slot {
+                key,
+                symbols,
+                prev: NONE,
+                next: NONE,
+            });
+            self.slots.len() - 1
+        } else {
+            let slot = self.tail;
+            self.unlink(slot);
+            self.map.remove(&self.slots[slot].key);
+            self.slots[slot].key = key;
+            self.slots[slot].symbols = symbols;
+            slot
+        };
+        self.map.insert(key, slot);
+        self.push_front(slot);
+        slot
+    }
+
+    fn unlink(&mut self, slot: usize) {
+        let Slot { prev, next, .. } = self.slots[slot];
+        match prev {
+            NONE => self.head = next,
+            prev => self.slots[prev].next = next,
+        }
+        match next {
+            NONE => self.tail = prev,
+            next => self.slots[next].prev = prev,
+        }
+    }
+
+    fn push_front(&mut self, slot: usize) {
+        self.slots[slot].prev = NONE;
+        self.slots[slot].next = self.head;
+        match self.head {
+            NONE => self.tail = slot,
+            head => self.slots[head].prev = slot,
+        }
+        self.head = slot;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shards_evict_least_recently_used() {
+        let mut shard = Shard::new(2);
+        shard.insert(1, Box::new([(0, 1)]));
+        shard.insert(2, Box::new([(0, 2)]));
+        assert!(shard.touch(1).is_some());
+        shard.insert(3, Box::new([(0, 3)]));
+        assert!(shard.touch(2).is_none());
+        let symbol = |shard: &mut Shard, key| shard.touch(key).map(|s| shard.slots[s].symbols[0].1);
+        assert_eq!(symbol(&mut shard, 1), Some(1));
+        assert_eq!(symbol(&mut shard, 3), Some(3));
+    }
+
+    #[test]
+    fn find_locates_symbols() {
+        let cache = BlockCache::new(1);
+        let decode = || Some(vec![(0, 7), (3, 8), (4, 9)].into());
+        assert_eq!(cache.find(64, 0, decode), Some((7, 0)));
+        assert_eq!(cache.find(64, 2, || None), Some((7, 2)));
+        assert_eq!(cache.find(64, 3, || None), Some((8, 0)));
+        assert_eq!(cache.find(64, 6, || None), Some((9, 2)));
+        assert_eq!(cache.find(128, 0, || None), None);
+        assert_eq!(cache.hits_and_misses(), (3, 2));
+    }
+}
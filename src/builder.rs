@@ -0,0 +1,232 @@
+use std::path::PathBuf;
+
+#[cfg(all(unix, feature = "mmap"))]
+use crate::mmap::MmapOptions;
+use crate::table::Kind;
+use crate::tablebase::fs::{kind_of_file, material_of_file, table_files};
+use crate::{LoadSummary, SyzygyError, Tablebase, MAX_PIECES};
+
+/// Configures which tables a [`Tablebase`] loads and how.
+///
+/// ```no_run
+/// # use cozy_syzygy::Tablebase;
+/// let (tb, summary) = Tablebase::builder()
+///     .directory("/path/to/syzygy")
+///     .max_pieces(5)
+///     .load_dtz(false)
+///     .skip_bad_files(true)
+///     .build()?;
+/// for (path, error) in &summary.failed {
+///     eprintln!("skipped {}: {error}", path.display());
+/// }
+/// # Ok::<(), cozy_syzygy::SyzygyError>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct TablebaseBuilder {
+    directories: Vec<PathBuf>,
+    max_pieces: u32,
+    load_dtz: bool,
+    skip_bad_files: bool,
+    read_into_memory: bool,
+    block_cache: usize,
+    max_table_bytes: usize,
+    require_complete_coverage: bool,
+    #[cfg(all(unix, feature = "mmap"))]
+    mmap_options: MmapOptions,
+}
+
+impl Default for TablebaseBuilder {
+    fn default() -> Self {
+        TablebaseBuilder::new()
+    }
+}
+
+impl TablebaseBuilder {
+    pub fn new() -> Self {
+        TablebaseBuilder {
+            directories: vec![],
+            max_pieces: MAX_PIECES as u32,
+            load_dtz: true,
+            skip_bad_files: false,
+            read_into_memory: false,
+            block_cache: 0,
+            max_table_bytes: usize::MAX,
+            require_complete_coverage: false,
+            #[cfg(all(unix, feature = "mmap"))]
+            mmap_options: MmapOptions::default(),
+        }
+    }
+
+    /// Load the Syzygy tablebase files in the specified directory and its subdirectories. This can
+    /// be called more than once; the directories are loaded in order, and earlier tables take
+    /// precedence.
+    pub fn directory(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.directories.push(dir.into());
+        self
+    }
+
+    /// Skip tables with more than `pieces` pieces, including kings. By default every table is
+    /// loaded.
+    pub fn max_pieces(mut self, pieces: u32) -> Self {
+        self.max_pieces = pieces;
+        self
+    }
+
+    /// Set whether to load DTZ tables. Without them, probes which need DTZ values fail with
+    /// [`ProbeError::MissingTable`][crate::ProbeError::MissingTable]. They are loaded by default.
+    pub fn load_dtz(mut self, load: bool) -> Self {
+        self.load_dtz = load;
+        self
+    }
+
+    /// Set whether files which fail to load are skipped and listed in the summary returned by
+    /// [`TablebaseBuilder::build`], rather than failing the build. They are not skipped by
+    /// default.
+    pub fn skip_bad_files(mut self, skip: bool) -> Self {
+        self.skip_bad_files = skip;
+        self
+    }
+
+    /// Set whether files are read into memory rather than memory-mapped. This avoids page faults
+    /// while probing at the cost of reading every file up front. Files are memory-mapped by
+    /// default.
+    pub fn read_into_memory(mut self, read: bool) -> Self {
+        self.read_into_memory = read;
+        self
+    }
+
+    /// See [`Tablebase::set_block_cache`].
+    pub fn block_cache(mut self, blocks: usize) -> Self {
+        self.block_cache = blocks;
+        self
+    }
+
+    /// See [`Tablebase::set_max_table_bytes`]. Files which don't fit fail to load, so they are
+    /// only skipped if [`TablebaseBuilder::skip_bad_files`] is set.
+    pub fn max_table_bytes(mut self, bytes: usize) -> Self {
+        self.max_table_bytes = bytes;
+        self
+    }
+
+    /// See [`Tablebase::set_require_complete_coverage`].
+    pub fn require_complete_coverage(mut self, require: bool) -> Self {
+        self.require_complete_coverage = require;
+        self
+    }
+
+    /// See [`Tablebase::set_mmap_options`]. The options apply to every directory.
+    #[cfg(all(unix, feature = "mmap"))]
+    pub fn mmap_options(mut self, options: MmapOptions) -> Self {
+        self.mmap_options = options;
+        self
+    }
+
+    /// Load the tablebase.
+    ///
+    /// An error is returned if a directory can't be read, or if a file fails to load and
+    /// [`TablebaseBuilder::skip_bad_files`] is not set. Files skipped because of their piece count
+    /// or kind are not included in the summary.
+    pub fn build(self) -> Result<(Tablebase, LoadSummary), SyzygyError> {
+        let mut tb = Tablebase::new();
+        tb.set_block_cache(self.block_cache);
+        tb.set_max_table_bytes(self.max_table_bytes);
+        tb.set_require_complete_coverage(self.require_complete_coverage);
+        #[cfg(all(unix, feature = "mmap"))]
+        tb.set_mmap_options(self.mmap_options);
+
+        let mut summary = LoadSummary::default();
+        for dir in &self.directories {
+            for path in table_files(dir)? {
+                if !self.load_dtz && kind_of_file(&path) == Kind::Dtz {
+                    continue;
+                }
+                let result = material_of_file(&path).and_then(|material| {
+                    if material.count() as u32 > self.max_pieces {
+                        return Ok(());
+                    }
+                    tb.load_listed_file(&path, self.read_into_memory, &mut summary)
+                });
+                match result {
+                    Ok(()) => {}
+                    Err(e) if self.skip_bad_files => summary.failed.push((path, e)),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok((tb, summary))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cozy_chess::{Color, Square};
+
+    use super::*;
+    use crate::tablebase::tests::{krvk_placement, winning_krvk};
+    use crate::Wdl;
+
+    #[test]
+    fn builder_filters_and_skips_files() {
+        let dir = std::env::temp_dir().join(format!("cozy-syzygy-builder-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let data = winning_krvk();
+        std::fs::write(dir.join("KRvK.rtbw"), &data).unwrap();
+        std::fs::write(dir.join("KQvK.rtbw"), [0; 64]).unwrap();
+        std::fs::write(dir.join("KQvK.rtbz"), [0; 64]).unwrap();
+        std::fs::write(dir.join("KRRvK.rtbw"), [0; 64]).unwrap();
+
+        let builder = Tablebase::builder()
+            .directory(&dir)
+            .max_pieces(3)
+            .load_dtz(false);
+        assert!(builder.clone().build().is_err());
+
+        let (tb, summary) = builder
+            .skip_bad_files(true)
+            .read_into_memory(true)
+            .build()
+            .unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(summary.loaded, 1);
+        assert_eq!(summary.failed.len(), 1);
+        assert_eq!(summary.failed[0].0.file_name().unwrap(), "KQvK.rtbw");
+        assert_eq!(tb.max_pieces(), 3);
+        assert_eq!(tb.table_info().count(), 1);
+        assert_eq!(tb.memory_usage()["KRvK"].owned, data.len());
+
+        let position = krvk_placement(Square::A1, Square::B7, Square::E8, Color::White);
+        assert_eq!(tb.probe_wdl_quiet(&position), Ok(Wdl::CursedWin));
+    }
+
+    #[test]
+    fn summaries_report_duplicates_and_names() {
+        let dir = std::env::temp_dir().join(format!("cozy-syzygy-summary-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("a")).unwrap();
+        std::fs::create_dir_all(dir.join("b")).unwrap();
+        let data = winning_krvk();
+        std::fs::write(dir.join("a/KvKR.rtbw"), &data).unwrap();
+        std::fs::write(dir.join("b/KRvK.rtbw"), &data).unwrap();
+
+        let (tb, summary) = Tablebase::builder()
+            .directory(dir.join("a"))
+            .directory(dir.join("b"))
+            .build()
+            .unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(summary.loaded, 1);
+        assert_eq!(summary.loaded_by_pieces[3], 1);
+        assert_eq!(summary.bytes, data.len() as u64);
+        assert_eq!(summary.non_canonical.len(), 1);
+        assert_eq!(summary.duplicates.len(), 1);
+        assert_eq!(summary.duplicates[0].file_name().unwrap(), "KRvK.rtbw");
+        assert_eq!(
+            summary.to_string(),
+            "loaded 1 tables (3-piece: 1), 0.0 MiB, 1 duplicates skipped, 1 non-canonical names"
+        );
+        assert_eq!(tb.table_info().next().unwrap().material, "KRvK");
+
+        // The table is loaded from its non-canonical name.
+        let position = krvk_placement(Square::E1, Square::A7, Square::E8, Color::White);
+        assert_eq!(tb.probe_wdl_quiet(&position), Ok(Wdl::Win));
+    }
+}
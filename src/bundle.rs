@@ -0,0 +1,175 @@
+//! A container for several tables in one blob, so that tables can be embedded in a binary with a
+//! single `include_bytes!`.
+//!
+//! A bundle starts with the magic `CSYZBNDL` and the number of tables as a `u32`. Each table
+//! follows as the length of its material as a `u8`, the material, e.g. `KRvK`, a `u8` which is 1
+//! if the table is compressed with zstd and 0 otherwise, the stored and uncompressed lengths as
+//! `u64`s, and the stored data. All integers are little-endian.
+
+use crate::stream::DataStream;
+use crate::SyzygyError;
+
+const MAGIC: [u8; 8] = *b"CSYZBNDL";
+
+/// Writes bundles for [`Tablebase::load_bundle_static`][crate::Tablebase::load_bundle_static].
+#[derive(Clone, Debug, Default)]
+pub struct BundleWriter {
+    tables: Vec<Entry<Vec<u8>>>,
+}
+
+impl BundleWriter {
+    pub fn new() -> Self {
+        BundleWriter::default()
+    }
+
+    /// Add a table for the material, e.g. `KRvK`, stored as is. Whether it is a WDL or DTZ table
+    /// is determined from its contents when the bundle is loaded.
+    pub fn add(&mut self, material: &str, data: &[u8]) {
+        self.tables.push(Entry {
+            material: material.to_owned(),
+            compressed: false,
+            size: data.len(),
+            data: data.to_vec(),
+        });
+    }
+
+    /// Add a table compressed with zstd at the specified level. Compressed tables are
+    /// decompressed into memory when the bundle is loaded.
+    #[cfg(feature = "zstd")]
+    pub fn add_compressed(
+        &mut self,
+        material: &str,
+        data: &[u8],
+        level: i32,
+    ) -> Result<(), SyzygyError> {
+        self.tables.push(Entry {
+            material: material.to_owned(),
+            compressed: true,
+            size: data.len(),
+            data: zstd::bulk::compress(data, level)?,
+        });
+        Ok(())
+    }
+
+    /// Write the bundle.
+    pub fn finish(&self) -> Vec<u8> {
+        let mut bundle = MAGIC.to_vec();
+        bundle.extend_from_slice(&(self.tables.len() as u32).to_le_bytes());
+        for table in &self.tables {
+            bundle.push(table.material.len() as u8);
+            bundle.extend_from_slice(table.material.as_bytes());
+            bundle.push(table.compressed as u8);
+            bundle.extend_from_slice(&(table.data.len() as u64).to_le_bytes());
+            bundle.extend_from_slice(&(table.size as u64).to_le_bytes());
+            bundle.extend_from_slice(&table.data);
+        }
+        bundle
+    }
+}
+
+/// A table in a bundle.
+#[derive(Clone, Debug)]
+pub(crate) struct Entry<T> {
+    pub material: String,
+    pub compressed: bool,
+    /// The size of the table after decompression.
+    pub size: usize,
+    pub data: T,
+}
+
+/// Read the tables in a bundle. Fails with [`SyzygyError::NotSyzygy`] if the data is not a
+/// bundle, or [`SyzygyError::Corrupt`] if it is truncated.
+pub(crate) fn entries(bundle: &[u8]) -> Result<Vec<Entry<&[u8]>>, SyzygyError> {
+    let mut data = DataStream::new(bundle);
+    if data.read::<8>().ok() != Some(MAGIC) {
+        return Err(SyzygyError::NotSyzygy);
+    }
+    let count = data.read_u32()?;
+    let mut tables = vec![];
+    for _ in 0..count {
+        let len = data.read_u8()? as usize;
+        let material = std::str::from_utf8(data.read_array(len)?)
+            .map_err(|_| SyzygyError::UnknownMaterial)?
+            .to_owned();
+        let compressed = match data.read_u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(SyzygyError::Corrupt),
+        };
+        let stored = usize::try_from(data.read_u64()?).map_err(|_| SyzygyError::Corrupt)?;
+        let size = usize::try_from(data.read_u64()?).map_err(|_| SyzygyError::Corrupt)?;
+        tables.push(Entry {
+            material,
+            compressed,
+            size,
+            data: data.read_array(stored)?,
+        });
+    }
+    Ok(tables)
+}
+
+/// Decompress a table from a bundle.
+#[cfg(feature = "zstd")]
+pub(crate) fn decompress(entry: &Entry<&[u8]>) -> Result<Vec<u8>, SyzygyError> {
+    Ok(zstd::bulk::decompress(entry.data, entry.size)?)
+}
+
+/// Compressed tables can't be loaded without the `zstd` feature.
+#[cfg(not(feature = "zstd"))]
+pub(crate) fn decompress(_: &Entry<&[u8]>) -> Result<Vec<u8>, SyzygyError> {
+    Err(SyzygyError::UnsupportedCompression)
+}
+
+#[cfg(test)]
+mod tests {
+    use cozy_chess::{Color, Square};
+
+    use super::*;
+    use crate::tablebase::tests::{krvk_placement, winning_krvk};
+    use crate::{Tablebase, Wdl};
+
+    /// Checks that the bundled table reads the values of [`winning_krvk`].
+    fn assert_winning(tb: &Tablebase) {
+        let position = krvk_placement(Square::E1, Square::A7, Square::E8, Color::White);
+        assert_eq!(tb.probe_wdl_quiet(&position), Ok(Wdl::Win));
+        let position = krvk_placement(Square::A1, Square::B7, Square::E8, Color::White);
+        assert_eq!(tb.probe_wdl_quiet(&position), Ok(Wdl::CursedWin));
+    }
+
+    #[test]
+    fn bundles_are_loaded() {
+        let data = winning_krvk();
+        let mut writer = BundleWriter::new();
+        writer.add("KRvK", &data);
+        let bundle = writer.finish().leak();
+
+        let mut tb = Tablebase::new();
+        tb.load_bundle_static(bundle).unwrap();
+        assert_eq!(tb.table_info().next().unwrap().material, "KRvK");
+        assert_winning(&tb);
+
+        assert!(matches!(
+            Tablebase::new().load_bundle_static(&bundle[..bundle.len() - 1]),
+            Err(SyzygyError::Corrupt)
+        ));
+        assert!(matches!(
+            Tablebase::new().load_bundle_static(&data.leak()[..]),
+            Err(SyzygyError::NotSyzygy)
+        ));
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn compressed_bundles_are_loaded() {
+        let data = winning_krvk();
+        let mut writer = BundleWriter::new();
+        writer.add_compressed("KRvK", &data, 19).unwrap();
+        let bundle = writer.finish();
+        assert!(bundle.len() < data.len());
+
+        let mut tb = Tablebase::new();
+        tb.load_bundle_static(bundle.leak()).unwrap();
+        assert_eq!(tb.table_info().next().unwrap().size, data.len());
+        assert_winning(&tb);
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use cozy_chess::Board;
+
+use crate::{DataStream, ProbeError, ProbeRecord, SyzygyError, Tablebase, Wdl};
+
+/// A persistent cache of probe results.
+///
+/// The cache is an append-only file of [`ProbeRecord`]s keyed by
+/// [`Board::hash`][cozy_chess::Board::hash]. The whole file is indexed in memory when it is
+/// opened, so this is intended for pipelines which repeatedly process overlapping sets of
+/// positions rather than for use inside of a search.
+pub struct ProbeCache {
+    index: HashMap<u64, ProbeRecord>,
+    file: BufWriter<File>,
+}
+
+impl ProbeCache {
+    /// Open the cache file at the specified path, creating it if it does not exist.
+    ///
+    /// A trailing partial record (e.g. from an interrupted write) is ignored and will be
+    /// overwritten by the next insertion. Invalid records are skipped.
+    pub fn open(path: impl AsRef<Path>) -> Result<ProbeCache, SyzygyError> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let mut data = vec![];
+        file.read_to_end(&mut data)?;
+
+        let mut index = HashMap::new();
+        let mut stream = DataStream::new(&data);
+        while stream.remaining() >= ProbeRecord::SIZE {
+            if let Some(record) = ProbeRecord::decode(stream.read_array(ProbeRecord::SIZE)?) {
+                index.insert(record.hash, record);
+            }
+        }
+
+        file.set_len(stream.position() as u64)?;
+        file.seek(SeekFrom::End(0))?;
+
+        Ok(ProbeCache {
+            index,
+            file: BufWriter::new(file),
+        })
+    }
+
+    /// Returns the number of cached results.
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    pub fn get(&self, hash: u64) -> Option<&ProbeRecord> {
+        self.index.get(&hash)
+    }
+
+    /// Append a record to the cache, replacing any previous record with the same hash.
+    pub fn insert(&mut self, record: ProbeRecord) -> std::io::Result<()> {
+        self.file.write_all(&record.encode())?;
+        self.index.insert(record.hash, record);
+        Ok(())
+    }
+
+    /// Write any buffered records to the file.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+
+    /// Find the WDL value of the specified position, consulting the cache before probing the
+    /// tablebase and storing the result afterwards.
+    ///
+    /// See [`Tablebase::probe_wdl`] for details.
+    pub fn probe_wdl(
+        &mut self,
+        tb: &Tablebase,
+        position: &Board,
+    ) -> std::io::Result<Result<(Wdl, bool), ProbeError>> {
+        if let Some(record) = self.index.get(&position.hash()) {
+            return Ok(Ok((record.wdl, record.capture)));
+        }
+
+        let result = match tb.probe_wdl(position) {
+            Ok(result) => result,
+            Err(e) => return Ok(Err(e)),
+        };
+        self.insert(ProbeRecord::new(position, result))?;
+        Ok(Ok(result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(hash: u64, wdl: Wdl) -> ProbeRecord {
+        ProbeRecord {
+            hash,
+            wdl,
+            capture: false,
+            dtz: None,
+        }
+    }
+
+    #[test]
+    fn records_are_found_after_reopening() {
+        let dir = std::env::temp_dir().join(format!("cozy-syzygy-cache-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("probes.bin");
+
+        let mut cache = ProbeCache::open(&path).unwrap();
+        assert!(cache.is_empty());
+        cache.insert(record(1, Wdl::Win)).unwrap();
+        cache.insert(record(2, Wdl::Draw)).unwrap();
+        cache.insert(record(1, Wdl::CursedWin)).unwrap();
+        cache.flush().unwrap();
+        drop(cache);
+
+        // The later record for a hash replaces the earlier one.
+        let cache = ProbeCache::open(&path).unwrap();
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(1), Some(&record(1, Wdl::CursedWin)));
+        assert_eq!(cache.get(2), Some(&record(2, Wdl::Draw)));
+        assert_eq!(cache.get(3), None);
+        drop(cache);
+
+        // A partial record from an interrupted write is dropped, and the next record is
+        // written in its place.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&record(3, Wdl::Loss).encode()[..5]).unwrap();
+        drop(file);
+        let mut cache = ProbeCache::open(&path).unwrap();
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(3), None);
+        cache.insert(record(4, Wdl::BlessedLoss)).unwrap();
+        cache.flush().unwrap();
+        drop(cache);
+
+        let cache = ProbeCache::open(&path).unwrap();
+        let len = std::fs::metadata(&path).unwrap().len();
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(len, 4 * ProbeRecord::SIZE as u64);
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.get(1), Some(&record(1, Wdl::CursedWin)));
+        assert_eq!(cache.get(4), Some(&record(4, Wdl::BlessedLoss)));
+    }
+}
@@ -0,0 +1,189 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use cozy_chess::Board;
+
+use crate::{Dtz, ProbeError, Tablebase, Wdl};
+
+/// Set when the entry holds the result of [`Tablebase::probe_wdl`].
+const HAS_WDL: u64 = 1 << 0;
+/// Set when the entry holds the result of [`Tablebase::probe_dtz`].
+const HAS_DTZ: u64 = 1 << 1;
+const CAPTURE: u64 = 1 << 2;
+const WDL_SHIFT: u32 = 3;
+const DTZ_SHIFT: u32 = 32;
+
+/// A [`Tablebase`] with a fixed-size cache of probe results, for searches which probe the same
+/// positions over and over.
+///
+/// Results are keyed by [`Board::hash`], which does not include the halfmove clock, so positions
+/// which only differ in it share a result, as they do when probing. Each position has one entry,
+/// which is replaced by whatever result is stored there next, so the cache never fills up with
+/// positions the search has left behind. Failed probes are not cached.
+///
+/// The cache is lock-free, so any number of search threads can probe through it at once. Each
+/// entry is stored as two words, the result and the result XORed with the hash, so an entry torn
+/// by two threads writing it at once does not match either hash and is a miss.
+pub struct CachedTablebase {
+    tb: Tablebase,
+    entries: Box<[Entry]>,
+}
+
+#[derive(Default)]
+struct Entry {
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+impl CachedTablebase {
+    /// Cache the probes of `tb` in `entries` entries, rounded up to a power of two. Each entry
+    /// takes 16 bytes.
+    pub fn new(tb: Tablebase, entries: usize) -> Self {
+        CachedTablebase {
+            tb,
+            entries: (0..entries.max(1).next_power_of_two())
+                .map(|_| Entry::default())
+                .collect(),
+        }
+    }
+
+    /// The cached tablebase, for probes which are not cached.
+    pub fn tablebase(&self) -> &Tablebase {
+        &self.tb
+    }
+
+    /// Returns the cached tablebase, discarding the cache.
+    pub fn into_inner(self) -> Tablebase {
+        self.tb
+    }
+
+    /// The number of entries in the cache.
+    pub fn entries(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Remove every result from the cache.
+    pub fn clear(&self) {
+        for entry in self.entries.iter() {
+            entry.check.store(0, Ordering::Relaxed);
+            entry.data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// See [`Tablebase::probe_wdl`].
+    pub fn probe_wdl(&self, position: &Board) -> Result<(Wdl, bool), ProbeError> {
+        let hash = position.hash();
+        let cached = self.lookup(hash);
+        if let Some(data) = cached.filter(|d| d & HAS_WDL != 0) {
+            return Ok((unpack_wdl(data), data & CAPTURE != 0));
+        }
+
+        let (wdl, capture) = self.tb.probe_wdl(position)?;
+        // Keep a DTZ result for the same position, which has the same WDL value.
+        let dtz = cached.map_or(0, |d| d & (HAS_DTZ | u64::MAX << DTZ_SHIFT));
+        let capture_bit = match capture {
+            true => CAPTURE,
+            false => 0,
+        };
+        self.store(hash, dtz | HAS_WDL | capture_bit | pack_wdl(wdl));
+        Ok((wdl, capture))
+    }
+
+    /// See [`Tablebase::probe_dtz`].
+    pub fn probe_dtz(&self, position: &Board) -> Result<(Dtz, Wdl), ProbeError> {
+        let hash = position.hash();
+        let cached = self.lookup(hash);
+        if let Some(data) = cached.filter(|d| d & HAS_DTZ != 0) {
+            return Ok((Dtz((data >> DTZ_SHIFT) as u32 as i32), unpack_wdl(data)));
+        }
+
+        let (dtz, wdl) = self.tb.probe_dtz(position)?;
+        let wdl_result = cached.map_or(0, |d| d & (HAS_WDL | CAPTURE));
+        let dtz_bits = (dtz.0 as u32 as u64) << DTZ_SHIFT;
+        self.store(hash, wdl_result | HAS_DTZ | dtz_bits | pack_wdl(wdl));
+        Ok((dtz, wdl))
+    }
+
+    fn entry(&self, hash: u64) -> &Entry {
+        &self.entries[hash as usize & (self.entries.len() - 1)]
+    }
+
+    /// The cached result of the position with `hash`, if there is one.
+    fn lookup(&self, hash: u64) -> Option<u64> {
+        let entry = self.entry(hash);
+        let data = entry.data.load(Ordering::Relaxed);
+        let check = entry.check.load(Ordering::Relaxed);
+        // Empty entries have no results, so they are misses even for a hash of zero.
+        (check ^ data == hash && data & (HAS_WDL | HAS_DTZ) != 0).then_some(data)
+    }
+
+    fn store(&self, hash: u64, data: u64) {
+        let entry = self.entry(hash);
+        entry.check.store(hash ^ data, Ordering::Relaxed);
+        entry.data.store(data, Ordering::Relaxed);
+    }
+}
+
+fn pack_wdl(wdl: Wdl) -> u64 {
+    (wdl as u64) << WDL_SHIFT
+}
+
+fn unpack_wdl(data: u64) -> Wdl {
+    match (data >> WDL_SHIFT) & 0x7 {
+        0 => Wdl::Loss,
+        1 => Wdl::BlessedLoss,
+        2 => Wdl::Draw,
+        3 => Wdl::CursedWin,
+        _ => Wdl::Win,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn wdl() -> impl Strategy<Value = Wdl> {
+        prop_oneof![
+            Just(Wdl::Loss),
+            Just(Wdl::BlessedLoss),
+            Just(Wdl::Draw),
+            Just(Wdl::CursedWin),
+            Just(Wdl::Win),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn results_are_found_by_their_hash(
+            hash in any::<u64>(),
+            other in any::<u64>(),
+            wdl in wdl(),
+            dtz in any::<i32>(),
+        ) {
+            let cache = CachedTablebase::new(Tablebase::new(), 4);
+            prop_assert_eq!(cache.lookup(hash), None);
+
+            let data = HAS_DTZ | ((dtz as u32 as u64) << DTZ_SHIFT) | pack_wdl(wdl);
+            cache.store(hash, data);
+            prop_assert_eq!(cache.lookup(hash), Some(data));
+            prop_assert_eq!(unpack_wdl(data), wdl);
+            prop_assume!(other != hash);
+            prop_assert_eq!(cache.lookup(other), None);
+
+            cache.clear();
+            prop_assert_eq!(cache.lookup(hash), None);
+        }
+    }
+
+    #[test]
+    fn torn_entries_are_misses() {
+        let cache = CachedTablebase::new(Tablebase::new(), 1);
+        assert_eq!(cache.lookup(0), None);
+        cache.store(8, HAS_WDL | pack_wdl(Wdl::Win));
+        cache.entries[0]
+            .data
+            .store(HAS_WDL | pack_wdl(Wdl::Loss), Ordering::Relaxed);
+        assert_eq!(cache.lookup(8), None);
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// MD5 checksums of tablebase files, by file name, e.g. `KRvK.rtbw`.
+///
+/// The official Syzygy downloads publish these lists as `checksum.md5` files, which can be read
+/// with [`Checksums::parse`]. See [`Tablebase::verify_checksums`][crate::Tablebase::verify_checksums].
+#[derive(Clone, Debug, Default)]
+pub struct Checksums {
+    files: HashMap<String, [u8; 16]>,
+}
+
+impl Checksums {
+    pub fn new() -> Self {
+        Checksums::default()
+    }
+
+    /// Parse a checksum list in the format written by `md5sum`, with one `<hex digest>  <file>`
+    /// line per file. Directories in the file names are ignored. Returns `None` if a line is not
+    /// in this format.
+    pub fn parse(list: &str) -> Option<Self> {
+        let mut checksums = Checksums::new();
+        for line in list.lines().filter(|l| !l.trim().is_empty()) {
+            let (digest, file) = line.trim().split_once(char::is_whitespace)?;
+            // md5sum marks files read in binary mode with a `*`.
+            let file = file.trim_start().trim_start_matches('*');
+            let name = file.rsplit(['/', '\\']).next()?;
+            checksums.insert(name, parse_digest(digest)?);
+        }
+        Some(checksums)
+    }
+
+    /// Add the checksum of the named file, replacing any previous checksum.
+    pub fn insert(&mut self, file_name: &str, md5: [u8; 16]) {
+        self.files.insert(file_name.to_owned(), md5);
+    }
+
+    /// The checksum of the named file, if it is listed.
+    pub fn get(&self, file_name: &str) -> Option<[u8; 16]> {
+        self.files.get(file_name).copied()
+    }
+
+    /// The names of the listed files.
+    pub fn file_names(&self) -> impl Iterator<Item = &str> {
+        self.files.keys().map(String::as_str)
+    }
+
+    /// Check the contents of the named file against its checksum.
+    pub fn check(&self, file_name: &str, data: &[u8]) -> ChecksumStatus {
+        match self.get(file_name) {
+            None => ChecksumStatus::Unlisted,
+            Some(md5) if md5 == self::md5(data) => ChecksumStatus::Match,
+            Some(_) => ChecksumStatus::Mismatch,
+        }
+    }
+}
+
+/// Whether a file matched its listed checksum.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChecksumStatus {
+    Match,
+    /// The file does not match its checksum, so it is corrupt or from a different release.
+    Mismatch,
+    /// The file is not in the checksum list.
+    Unlisted,
+}
+
+/// The result of checking a loaded table, returned by
+/// [`Tablebase::verify_checksums`][crate::Tablebase::verify_checksums].
+#[derive(Clone, Debug)]
+pub struct ChecksumResult {
+    /// The material of the table, e.g. `KRvK`.
+    pub material: String,
+    /// Whether this is a DTZ table rather than a WDL table.
+    pub dtz: bool,
+    /// The path the table was loaded from, if it was loaded from the file system.
+    pub path: Option<PathBuf>,
+    pub status: ChecksumStatus,
+}
+
+/// The name of the official file for a table, which is what checksum lists use.
+pub(crate) fn file_name(material: &str, dtz: bool) -> String {
+    match dtz {
+        true => format!("{material}.rtbz"),
+        false => format!("{material}.rtbw"),
+    }
+}
+
+fn parse_digest(hex: &str) -> Option<[u8; 16]> {
+    if hex.len() != 32 || !hex.is_ascii() {
+        return None;
+    }
+    let mut digest = [0; 16];
+    for (i, byte) in digest.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok()?;
+    }
+    Some(digest)
+}
+
+/// The MD5 digest of `data`, following RFC 1321.
+pub(crate) fn md5(data: &[u8]) -> [u8; 16] {
+    const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+    let constants: [u32; 64] =
+        std::array::from_fn(|i| ((i as f64 + 1.0).sin().abs() * 4294967296.0) as u32);
+
+    let mut state: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+    let mut process = |block: &[u8]| {
+        let words: [u32; 16] = std::array::from_fn(|i| {
+            u32::from_le_bytes(block[4 * i..4 * i + 4].try_into().unwrap())
+        });
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let f = f
+                .wrapping_add(a)
+                .wrapping_add(constants[i])
+                .wrapping_add(words[g]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(SHIFTS[i / 16 * 4 + i % 4]));
+        }
+        for (s, v) in state.iter_mut().zip([a, b, c, d]) {
+            *s = s.wrapping_add(v);
+        }
+    };
+
+    let mut blocks = data.chunks_exact(64);
+    for block in &mut blocks {
+        process(block);
+    }
+    // The padding is a one bit, zeros up to 8 bytes before a block boundary, and the length in
+    // bits.
+    let rest = blocks.remainder();
+    let mut tail = [0; 128];
+    tail[..rest.len()].copy_from_slice(rest);
+    tail[rest.len()] = 0x80;
+    let len = match rest.len() < 56 {
+        true => 64,
+        false => 128,
+    };
+    tail[len - 8..len].copy_from_slice(&(data.len() as u64 * 8).to_le_bytes());
+    for block in tail[..len].chunks_exact(64) {
+        process(block);
+    }
+
+    let mut digest = [0; 16];
+    for (bytes, s) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&s.to_le_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn md5_matches_reference_digests() {
+        let cases: [(&[u8], &str); 4] = [
+            (b"", "d41d8cd98f00b204e9800998ecf8427e"),
+            (b"abc", "900150983cd24fb0d6963f7d28e17f72"),
+            (
+                b"The quick brown fox jumps over the lazy dog",
+                "9e107d9d372bb6826bd81d3542a419d6",
+            ),
+            (
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+                "57edf4a22be3c955ac49da2e2107b67a",
+            ),
+        ];
+        for (data, hex) in cases {
+            assert_eq!(md5(data), parse_digest(hex).unwrap());
+        }
+    }
+
+    #[test]
+    fn checksum_lists_are_parsed() {
+        let list = "d41d8cd98f00b204e9800998ecf8427e  KRvK.rtbw\n\
+                    900150983cd24fb0d6963f7d28e17f72 *3-4-5/KQvK.rtbz\n";
+        let checksums = Checksums::parse(list).unwrap();
+        assert_eq!(checksums.check("KRvK.rtbw", b""), ChecksumStatus::Match);
+        assert_eq!(
+            checksums.check("KQvK.rtbz", b"ab"),
+            ChecksumStatus::Mismatch
+        );
+        assert_eq!(checksums.check("KQvK.rtbw", b""), ChecksumStatus::Unlisted);
+        assert!(Checksums::parse("not a checksum  KRvK.rtbw").is_none());
+    }
+}
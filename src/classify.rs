@@ -0,0 +1,131 @@
+use cozy_chess::{BitBoard, Board, Color, File, Piece, Rank, Square};
+
+/// A description of the endgame in a position, for display and logging.
+///
+/// See [`Tablebase::classify`][crate::Tablebase::classify].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EndgameClass {
+    /// The canonical material name, e.g. `KRPvKR`. The stronger side is listed first.
+    pub material: String,
+    /// Whether a WDL table for this material is loaded.
+    pub wdl: bool,
+    /// Whether there are no pawns on the board.
+    pub pawnless: bool,
+    /// Whether each side has exactly one bishop, and those bishops are on opposite colors.
+    pub opposite_bishops: bool,
+    /// Whether the material commonly allows the weaker side to build a fortress. This is a
+    /// heuristic, and says nothing about the actual result of the position.
+    pub fortress_prone: bool,
+}
+
+pub(crate) fn opposite_bishops(position: &Board) -> bool {
+    let white = position.colored_pieces(Color::White, Piece::Bishop);
+    let black = position.colored_pieces(Color::Black, Piece::Bishop);
+    white.len() == 1
+        && black.len() == 1
+        && white.is_disjoint(BitBoard::DARK_SQUARES) != black.is_disjoint(BitBoard::DARK_SQUARES)
+}
+
+pub(crate) fn fortress_prone(position: &Board) -> bool {
+    // Opposite colored bishops are notoriously drawish when pawns are still on the board.
+    if opposite_bishops(position) && !position.pieces(Piece::Pawn).is_empty() {
+        return true;
+    }
+
+    Color::ALL.into_iter().any(|c| wrong_rook_pawn(position, c))
+}
+
+/// Whether `color` has only a bishop and rook pawns on a single edge file, where the bishop does
+/// not control the promotion square. The defending king can then hold the corner.
+fn wrong_rook_pawn(position: &Board, color: Color) -> bool {
+    let pieces = position.colors(color) & !position.pieces(Piece::King);
+    let bishops = position.colored_pieces(color, Piece::Bishop);
+    let pawns = position.colored_pieces(color, Piece::Pawn);
+    if bishops.len() != 1 || pawns.is_empty() || pieces != bishops | pawns {
+        return false;
+    }
+    if position.colors(!color) != position.king(!color).bitboard() {
+        return false;
+    }
+
+    let file = if pawns.is_subset(File::A.bitboard()) {
+        File::A
+    } else if pawns.is_subset(File::H.bitboard()) {
+        File::H
+    } else {
+        return false;
+    };
+    let promotion = Square::new(file, Rank::Eighth.relative_to(color));
+    promotion.bitboard().is_disjoint(BitBoard::DARK_SQUARES)
+        != bishops.is_disjoint(BitBoard::DARK_SQUARES)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tablebase::tests::winning_tablebase;
+
+    fn board(fen: &str) -> Board {
+        fen.parse().unwrap()
+    }
+
+    #[test]
+    fn opposite_bishops_are_found() {
+        // Opposite colors, the same color, and two bishops against one.
+        assert!(opposite_bishops(&board(
+            "2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1"
+        )));
+        assert!(!opposite_bishops(&board(
+            "4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1"
+        )));
+        assert!(!opposite_bishops(&board(
+            "2b1k3/8/8/8/8/8/8/2BBK3 w - - 0 1"
+        )));
+    }
+
+    #[test]
+    fn fortresses_are_flagged() {
+        let fortress = |fen| fortress_prone(&board(fen));
+
+        // Opposite colored bishops only draw easily with pawns on the board.
+        assert!(fortress("2b1k3/8/8/8/8/8/P7/2B1K3 w - - 0 1"));
+        assert!(!fortress("2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1"));
+
+        // The dark squared bishop doesn't control a8 or h1.
+        assert!(fortress("4k3/8/8/8/8/8/P7/2B1K3 w - - 0 1"));
+        assert!(fortress("4k3/8/8/8/8/P7/P7/2B1K3 w - - 0 1"));
+        assert!(fortress("4kb2/7p/8/8/8/8/8/4K3 w - - 0 1"));
+
+        // The right bishop, pawns off the rook files or on both of them, and extra material.
+        assert!(!fortress("4k3/8/8/8/8/8/P7/4KB2 w - - 0 1"));
+        assert!(!fortress("4k3/8/8/8/8/8/1P6/2B1K3 w - - 0 1"));
+        assert!(!fortress("4k3/8/8/8/8/8/P6P/2B1K3 w - - 0 1"));
+        assert!(!fortress("4k3/8/8/8/8/8/P7/1NB1K3 w - - 0 1"));
+        assert!(!fortress("4k3/7p/8/8/8/8/P7/2B1K3 w - - 0 1"));
+    }
+
+    #[test]
+    fn endgames_are_described() {
+        let tb = winning_tablebase();
+
+        let class = tb.classify(&board("4k3/8/8/8/8/8/8/r3K3 w - - 0 1"));
+        assert_eq!(class.material, "KRvK");
+        assert!(class.wdl);
+        assert!(class.pawnless);
+
+        let class = tb.classify(&board("4k3/8/8/8/8/8/P7/2B1K3 w - - 0 1"));
+        assert_eq!(
+            class,
+            EndgameClass {
+                material: "KBPvK".to_string(),
+                wdl: false,
+                pawnless: false,
+                opposite_bishops: false,
+                fortress_prone: true,
+            }
+        );
+
+        // Bare kings are always known.
+        assert!(tb.classify(&board("4k3/8/8/8/8/8/8/4K3 w - - 0 1")).wdl);
+    }
+}
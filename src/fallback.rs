@@ -0,0 +1,27 @@
+use cozy_chess::Board;
+
+use crate::{Dtz, Wdl};
+
+/// Another source of tablebase values, asked for positions whose tables are not loaded. See
+/// [`Tablebase::set_fallback`][crate::Tablebase::set_fallback].
+///
+/// This is meant for clients of online tablebases, such as the Lichess tablebase API, so that an
+/// engine with only the smaller tables on disk can still probe larger endgames. The crate does not
+/// include such a client, since it would need an HTTP dependency; implementing this trait with
+/// the HTTP client of your choice is enough.
+///
+/// The fallback may be asked about positions after captures as well as the probed position, and
+/// is called on the probing thread, so slow fallbacks should cache their answers.
+pub trait Fallback: Send + Sync {
+    /// The WDL value of the position for the side to move, ignoring the halfmove clock, as
+    /// returned by [`Tablebase::probe_wdl`][crate::Tablebase::probe_wdl]. `None` means the value
+    /// is not known, and the probe fails with the missing table.
+    fn wdl(&self, position: &Board) -> Option<Wdl>;
+
+    /// The DTZ value of the position, as returned by
+    /// [`Tablebase::probe_dtz`][crate::Tablebase::probe_dtz]. The position is never a draw.
+    /// `None` means the value is not known, and the probe fails with the missing table.
+    fn dtz(&self, _position: &Board) -> Option<Dtz> {
+        None
+    }
+}
@@ -0,0 +1,201 @@
+//! Random positions for a material, for tests, benchmarks and sampling.
+
+use cozy_chess::{Board, BoardBuilder, Color, Piece, Square};
+
+use crate::{Material, SyzygyError, MAX_PIECES};
+
+/// How many placements are tried before giving up. Most placements are legal, so this is only a
+/// guard against looping forever.
+const MAX_ATTEMPTS: u32 = 10_000;
+
+/// Generate a random legal position with the material, e.g. `KRPvKN`, with white to move.
+///
+/// The pieces are placed uniformly at random, with pawns kept off the first and last ranks, and
+/// placements where the side not to move is in check are rejected. The position has no castling
+/// rights and no en passant square. The same seed always gives the same position.
+///
+/// An error is returned if the material can't be parsed or has more than eight pieces.
+pub fn random_position(material: &str, seed: u64) -> Result<Board, SyzygyError> {
+    random_positions(material, seed)?
+        .next()
+        .ok_or(SyzygyError::UnknownMaterial)
+}
+
+/// Generate an endless sequence of random legal positions with the material. The side to move
+/// alternates, starting with white. See [`random_position`].
+pub fn random_positions(
+    material: &str,
+    seed: u64,
+) -> Result<impl Iterator<Item = Board>, SyzygyError> {
+    let material: Material = material.parse()?;
+    if material.count() as usize > MAX_PIECES {
+        return Err(SyzygyError::TooManyPieces);
+    }
+    let mut rng = SplitMix64(seed);
+    let mut side_to_move = Color::White;
+    Ok(std::iter::from_fn(move || {
+        let board = (0..MAX_ATTEMPTS).find_map(|_| place(material, side_to_move, &mut rng))?;
+        side_to_move = !side_to_move;
+        Some(board)
+    }))
+}
+
+/// Try one random placement of the material, returning `None` if it is not legal.
+fn place(material: Material, side_to_move: Color, rng: &mut SplitMix64) -> Option<Board> {
+    let mut squares = [None; 64];
+    let mut kings = [0; 2];
+    for c in Color::ALL {
+        kings[c as usize] = rng.empty_square(&squares, false)?;
+        squares[kings[c as usize]] = Some((Piece::King, c));
+    }
+    let distance = |a: usize, b: usize| (a % 8).abs_diff(b % 8).max((a / 8).abs_diff(b / 8));
+    if distance(kings[0], kings[1]) < 2 {
+        return None;
+    }
+    for c in Color::ALL {
+        for p in Piece::ALL {
+            if p == Piece::King {
+                continue;
+            }
+            for _ in 0..material[(c, p)] {
+                let sq = rng.empty_square(&squares, p == Piece::Pawn)?;
+                squares[sq] = Some((p, c));
+            }
+        }
+    }
+
+    // Building the board rejects positions where the side not to move is in check.
+    let mut builder = BoardBuilder::empty();
+    for (sq, piece) in squares.into_iter().enumerate() {
+        *builder.square_mut(Square::index(sq)) = piece;
+    }
+    builder.side_to_move = side_to_move;
+    builder.build().ok()
+}
+
+/// SplitMix64, a small and fast generator which is good enough for picking positions and moves.
+pub(crate) struct SplitMix64(pub u64);
+
+impl SplitMix64 {
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let z = (self.0 ^ (self.0 >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        let z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A random number below `n`, which must be nonzero.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// A random empty square, kept off the first and last ranks for pawns.
+    fn empty_square(
+        &mut self,
+        squares: &[Option<(Piece, Color)>; 64],
+        pawn: bool,
+    ) -> Option<usize> {
+        let range = match pawn {
+            true => 8..56,
+            false => 0..64,
+        };
+        let empty = squares[range.clone()]
+            .iter()
+            .filter(|s| s.is_none())
+            .count();
+        if empty == 0 {
+            return None;
+        }
+        let n = self.below(empty);
+        range.filter(|&sq| squares[sq].is_none()).nth(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cozy_chess::{
+        get_bishop_moves, get_king_moves, get_knight_moves, get_pawn_attacks, get_rook_moves,
+    };
+
+    use super::*;
+    use crate::ProbePosition;
+
+    /// Whether any piece of the other color attacks the king of `color`.
+    fn in_check(board: &Board, color: Color) -> bool {
+        let king = board.king(color);
+        let occupied = board.occupied();
+        let theirs = |p| board.colored_pieces(!color, p);
+        let diagonal = theirs(Piece::Bishop) | theirs(Piece::Queen);
+        let orthogonal = theirs(Piece::Rook) | theirs(Piece::Queen);
+        !(get_pawn_attacks(king, color) & theirs(Piece::Pawn)).is_empty()
+            || !(get_knight_moves(king) & theirs(Piece::Knight)).is_empty()
+            || !(get_bishop_moves(king, occupied) & diagonal).is_empty()
+            || !(get_rook_moves(king, occupied) & orthogonal).is_empty()
+            || !(get_king_moves(king) & theirs(Piece::King)).is_empty()
+    }
+
+    #[test]
+    fn positions_are_legal_with_the_material() {
+        for name in ["KRvK", "KPvKP", "KQBvKN", "KRPPvKRP"] {
+            let material: Material = name.parse().unwrap();
+            for (i, board) in random_positions(name, 3).unwrap().take(500).enumerate() {
+                let expected = [Color::White, Color::Black][i % 2];
+                assert_eq!(board.side_to_move(), expected, "{name}");
+                assert_eq!(Material::of(&board), material, "{board}");
+                assert!(!in_check(&board, !board.side_to_move()), "{board}");
+                assert!(board.to_string().parse::<Board>().is_ok(), "{board}");
+                let pawns = board.pieces(Piece::Pawn);
+                assert!(pawns.into_iter().all(|sq| (8..56).contains(&(sq as usize))));
+                assert!(board.en_passant().is_none() && !board.has_castle_rights());
+            }
+        }
+    }
+
+    #[test]
+    fn positions_depend_only_on_the_seed() {
+        let hashes = |seed| {
+            random_positions("KPvKP", seed)
+                .unwrap()
+                .take(20)
+                .map(|board| board.hash())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(hashes(4), hashes(4));
+        assert_ne!(hashes(4), hashes(5));
+    }
+
+    #[test]
+    fn materials_are_checked() {
+        assert!(matches!(
+            random_position("KXvK", 0),
+            Err(SyzygyError::UnknownMaterial)
+        ));
+        assert!(matches!(
+            random_position("KQQQQvKRRR", 0),
+            Err(SyzygyError::TooManyPieces)
+        ));
+    }
+
+    #[test]
+    fn random_numbers_are_in_range() {
+        let mut rng = SplitMix64(1);
+        let mut seen = [false; 6];
+        for _ in 0..1000 {
+            seen[rng.below(6)] = true;
+        }
+        assert_eq!(seen, [true; 6]);
+    }
+
+    #[test]
+    fn pawns_stay_off_the_back_ranks() {
+        let mut rng = SplitMix64(2);
+        let mut squares = [None; 64];
+        for _ in 0..48 {
+            let sq = rng.empty_square(&squares, true).unwrap();
+            assert!((8..56).contains(&sq));
+            squares[sq] = Some((Piece::Pawn, Color::White));
+        }
+        assert_eq!(rng.empty_square(&squares, true), None);
+        assert!(rng.empty_square(&squares, false).is_some());
+    }
+}
@@ -0,0 +1,136 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::{SyzygyError, MAX_PIECES};
+
+/// Information about a loaded tablebase file.
+///
+/// See [`Tablebase::table_info`][crate::Tablebase::table_info].
+#[derive(Clone, Debug)]
+pub struct TableInfo {
+    /// The material of the table, e.g. `KRvK`.
+    pub material: String,
+    /// The path the table was loaded from, if it was loaded from the file system.
+    pub path: Option<PathBuf>,
+    /// Whether this is a DTZ table rather than a WDL table.
+    pub dtz: bool,
+    /// The size of the table data in bytes.
+    pub size: usize,
+    /// How long it took to open and parse the table. A long load time usually means that the file
+    /// is on slow storage (e.g. a network mount), which will also make probing slow.
+    pub load_time: Duration,
+}
+
+/// Information about the loaded tables of one endgame, returned by
+/// [`Tablebase::endgame_info`][crate::Tablebase::endgame_info].
+#[derive(Clone, Copy, Debug)]
+pub struct EndgameInfo<'a> {
+    /// The material of the endgame, e.g. `KRvK`.
+    pub material: &'a str,
+    /// The WDL table, if it is loaded.
+    pub wdl: Option<&'a TableInfo>,
+    /// The DTZ table, if it is loaded.
+    pub dtz: Option<&'a TableInfo>,
+}
+
+impl EndgameInfo<'_> {
+    /// The total size of the loaded tables of the endgame in bytes.
+    pub fn size(&self) -> usize {
+        self.wdl.iter().chain(&self.dtz).map(|t| t.size).sum()
+    }
+}
+
+/// The memory used by the tables of an endgame, returned by
+/// [`Tablebase::memory_usage`][crate::Tablebase::memory_usage].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// The bytes of table data read into memory owned by the tablebase.
+    pub owned: usize,
+    /// The bytes of memory-mapped files. Only the pages which have been read take up memory, and
+    /// the operating system can drop them again under memory pressure.
+    pub mapped: usize,
+    /// The bytes of static or caller-managed data, loaded with
+    /// [`Tablebase::load_bytes_static`][crate::Tablebase::load_bytes_static] or
+    /// [`Tablebase::load_source`][crate::Tablebase::load_source].
+    pub external: usize,
+}
+
+/// The result of loading a directory with [`Tablebase::add_directory`][crate::Tablebase::add_directory],
+/// [`Tablebase::add_directory_lenient`][crate::Tablebase::add_directory_lenient] or
+/// [`TablebaseBuilder::build`][crate::TablebaseBuilder::build].
+///
+/// The [`Display`][std::fmt::Display] implementation writes a one line summary for logs.
+#[derive(Debug, Default)]
+pub struct LoadSummary {
+    /// The number of files that were loaded successfully.
+    pub loaded: usize,
+    /// The number of files loaded, by the number of pieces in their material.
+    pub loaded_by_pieces: [usize; MAX_PIECES + 1],
+    /// The total size of the files loaded, in bytes.
+    pub bytes: u64,
+    /// Files which were skipped because a table of the same kind and material was already
+    /// loaded, e.g. from another directory.
+    pub duplicates: Vec<PathBuf>,
+    /// Files whose names are not the standard name for their material, e.g. `KvKR.rtbw` or
+    /// `KRQvK.rtbw`. These are loaded as the standard material, `KRvK` and `KQRvK`.
+    pub non_canonical: Vec<PathBuf>,
+    /// The files that failed to load, and why.
+    pub failed: Vec<(PathBuf, SyzygyError)>,
+}
+
+impl std::fmt::Display for LoadSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "loaded {} tables", self.loaded)?;
+        let mut counts = self
+            .loaded_by_pieces
+            .iter()
+            .enumerate()
+            .filter(|&(_, &n)| n > 0);
+        if let Some((pieces, n)) = counts.next() {
+            write!(f, " ({pieces}-piece: {n}")?;
+            for (pieces, n) in counts {
+                write!(f, ", {pieces}-piece: {n}")?;
+            }
+            write!(f, ")")?;
+        }
+        write!(f, ", {:.1} MiB", self.bytes as f64 / (1 << 20) as f64)?;
+        if !self.duplicates.is_empty() {
+            write!(f, ", {} duplicates skipped", self.duplicates.len())?;
+        }
+        if !self.non_canonical.is_empty() {
+            write!(f, ", {} non-canonical names", self.non_canonical.len())?;
+        }
+        if !self.failed.is_empty() {
+            write!(f, ", {} failed", self.failed.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// How far loading has got, passed to the progress callback of
+/// [`SharedTablebase::add_directory_in_background`][crate::SharedTablebase::add_directory_in_background]
+/// after each file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoadProgress {
+    /// The number of files tried so far, including files which failed to load.
+    pub files_done: usize,
+    pub files_total: usize,
+    /// The total size of the files loaded so far, in bytes.
+    pub bytes: u64,
+}
+
+/// Tables which are not loaded, by material, e.g. `KRvK`, returned by
+/// [`Tablebase::missing_tables`][crate::Tablebase::missing_tables] and
+/// [`Tablebase::missing_tables_for`][crate::Tablebase::missing_tables_for].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MissingTables {
+    pub wdl: Vec<String>,
+    pub dtz: Vec<String>,
+}
+
+impl MissingTables {
+    /// Whether every table is loaded.
+    pub fn is_empty(&self) -> bool {
+        self.wdl.is_empty() && self.dtz.is_empty()
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::{Material, ProbeError};
+
+/// Counters of the table reads done by a tablebase, returned by
+/// [`Tablebase::stats`][crate::Tablebase::stats].
+///
+/// A single probe may read several tables, since the captures of the position are searched, so
+/// these count table reads rather than probes.
+#[derive(Clone, Debug, Default)]
+pub struct ProbeStats {
+    /// The reads of each WDL table, by material, e.g. `KRvK`.
+    pub wdl: HashMap<String, TableStats>,
+    /// The reads of each DTZ table, by material.
+    pub dtz: HashMap<String, TableStats>,
+    /// The number of lookups that found their block in the block cache.
+    pub block_cache_hits: u64,
+    /// The number of lookups that had to decode their block and add it to the block cache.
+    pub block_cache_misses: u64,
+    /// The files added with [`Tablebase::add_directory_lazy`][crate::Tablebase::add_directory_lazy]
+    /// which failed to load when they were first probed, and why. Probes of their material fail
+    /// without trying to load them again.
+    pub quarantined: Vec<(PathBuf, String)>,
+}
+
+impl ProbeStats {
+    /// The reads of both tables of an endgame, by material, e.g. `KRvK`.
+    pub fn endgame(&self, material: &str) -> TableStats {
+        let mut stats = TableStats::default();
+        for table in [self.wdl.get(material), self.dtz.get(material)]
+            .into_iter()
+            .flatten()
+        {
+            stats.reads += table.reads;
+            stats.missing += table.missing;
+            stats.failed += table.failed;
+            stats.time += table.time;
+        }
+        stats
+    }
+}
+
+/// Counters of the reads of a single table. See [`ProbeStats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TableStats {
+    /// The number of reads of the table.
+    pub reads: u64,
+    /// The number of reads which failed because the table is not loaded. A material with many of
+    /// these is a table worth downloading.
+    pub missing: u64,
+    /// The number of reads which failed because the table is corrupt or could not be loaded.
+    pub failed: u64,
+    /// The total time spent in reads of the table. Since tables are memory-mapped, this is
+    /// dominated by page faults when the table is on slow storage or not yet in the page cache.
+    pub time: Duration,
+}
+
+/// A table read, passed to the hook set with
+/// [`Tablebase::set_read_hook`][crate::Tablebase::set_read_hook].
+#[derive(Clone, Copy, Debug)]
+pub struct TableRead<'a> {
+    /// The material of the table, e.g. `KRvK`.
+    pub material: &'a str,
+    /// Whether this is a read of a DTZ table rather than a WDL table.
+    pub dtz: bool,
+    /// How long the read took.
+    pub time: Duration,
+    /// Why the read failed, if it did.
+    pub error: Option<&'a ProbeError>,
+}
+
+/// The hook set with [`Tablebase::set_read_hook`][crate::Tablebase::set_read_hook].
+pub type ReadHook = Box<dyn Fn(&TableRead) + Send + Sync>;
+
+/// The counters behind [`ProbeStats`], keyed by material and whether the table is a DTZ table.
+#[derive(Default)]
+pub(crate) struct Counters {
+    tables: Mutex<HashMap<(Material, bool), TableStats>>,
+}
+
+impl Counters {
+    pub fn record(
+        &self,
+        material: Material,
+        dtz: bool,
+        time: Duration,
+        error: Option<&ProbeError>,
+    ) {
+        let mut tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+        let stats = tables.entry((material, dtz)).or_default();
+        stats.reads += 1;
+        stats.time += time;
+        match error {
+            Some(ProbeError::MissingTable(_)) => stats.missing += 1,
+            Some(_) => stats.failed += 1,
+            None => {}
+        }
+    }
+
+    /// Add the counters to `stats`.
+    pub fn collect(&self, stats: &mut ProbeStats) {
+        let tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+        for (&(material, dtz), &counters) in tables.iter() {
+            let map = match dtz {
+                true => &mut stats.dtz,
+                false => &mut stats.wdl,
+            };
+            map.insert(material.to_string(), counters);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_separate_missing_and_failed_reads() {
+        let counters = Counters::default();
+        let material: Material = "KRvK".parse().unwrap();
+        let missing = ProbeError::MissingTable("KRvK".to_string());
+        let corrupt = ProbeError::CorruptTable("KRvK".to_string());
+        counters.record(material, false, Duration::from_micros(1), None);
+        counters.record(material, false, Duration::from_micros(2), Some(&missing));
+        counters.record(material, false, Duration::from_micros(3), Some(&corrupt));
+        counters.record(material, true, Duration::ZERO, None);
+
+        let mut stats = ProbeStats::default();
+        counters.collect(&mut stats);
+        let expected = TableStats {
+            reads: 3,
+            missing: 1,
+            failed: 1,
+            time: Duration::from_micros(6),
+        };
+        assert_eq!(stats.wdl["KRvK"], expected);
+        assert_eq!(stats.dtz["KRvK"].reads, 1);
+        assert_eq!(stats.endgame("KRvK").reads, 4);
+        assert_eq!(stats.endgame("KQvK"), TableStats::default());
+    }
+}
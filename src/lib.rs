@@ -1,16 +1,85 @@
 //! Syzygy tablebase probing library for `cozy-chess`.
 
-use cozy_chess::{Color, Piece};
+use cozy_chess::{Color, Move, Piece};
+#[cfg(feature = "mmap")]
+use memmap::Mmap;
 
+mod block_cache;
+#[cfg(feature = "fs")]
+mod builder;
+mod bundle;
+#[cfg(feature = "fs")]
+mod cache;
+mod cached;
+#[cfg(feature = "checksum")]
+mod checksum;
+mod classify;
 mod constants;
+mod fallback;
+pub mod generate;
+mod info;
+mod instrument;
+#[cfg(all(unix, feature = "mmap"))]
+mod mmap;
+mod packed;
 mod pairs;
+mod position;
+#[cfg(feature = "raw")]
+pub mod raw;
+mod record;
+pub mod regression;
+mod root;
+pub mod sample;
+pub mod search;
+mod shared;
+mod source;
+mod stream;
 mod table;
 mod tablebase;
+mod test_positions;
+pub mod transforms;
+mod verify;
 
 const MAX_PIECES: usize = 8;
 
-use memmap::Mmap;
+/// The indexing constants and functions used by the Syzygy format, for use by companion projects
+/// such as table generators and converters.
+///
+/// These are exposed as-is and are not covered by semver guarantees.
+#[cfg(feature = "internals")]
+pub mod internals {
+    pub use crate::constants::*;
+    pub use crate::table::{pawnful_norm_and_factors, pawnless_norm_and_factors, subfactor};
+
+    /// The maximum number of pieces, including kings, supported by this crate.
+    pub const MAX_PIECES: usize = crate::MAX_PIECES;
+}
+
+use stream::DataStream;
+
+#[cfg(feature = "fs")]
+pub use builder::TablebaseBuilder;
+pub use bundle::BundleWriter;
+#[cfg(feature = "fs")]
+pub use cache::ProbeCache;
+pub use cached::CachedTablebase;
+#[cfg(feature = "checksum")]
+pub use checksum::{ChecksumResult, ChecksumStatus, Checksums};
+pub use classify::EndgameClass;
+pub use fallback::Fallback;
+pub use info::{EndgameInfo, LoadProgress, LoadSummary, MemoryUsage, MissingTables, TableInfo};
+pub use instrument::{ProbeStats, ReadHook, TableRead, TableStats};
+#[cfg(all(unix, feature = "mmap"))]
+pub use mmap::{Advice, MmapOptions};
+pub use packed::PackedPosition;
+pub use position::{is_color_flipped, ProbePosition};
+pub use record::ProbeRecord;
+pub use root::{RootMove, TieBreak};
+pub use shared::SharedTablebase;
+pub use source::TableSource;
 pub use tablebase::Tablebase;
+pub use test_positions::{test_positions, TestCategory, TestPosition};
+pub use verify::Verifier;
 
 /// 5-valued game outcome
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -27,6 +96,17 @@ pub enum Wdl {
     Win,
 }
 
+impl Wdl {
+    /// The outcome of the game if the 50 move rule did not apply.
+    pub fn ignoring_50_move_rule(self) -> Wdl {
+        match self {
+            Wdl::BlessedLoss => Wdl::Loss,
+            Wdl::CursedWin => Wdl::Win,
+            wdl => wdl,
+        }
+    }
+}
+
 impl std::ops::Neg for Wdl {
     type Output = Wdl;
 
@@ -41,10 +121,110 @@ impl std::ops::Neg for Wdl {
     }
 }
 
+/// A WDL value which takes the halfmove clock into account, returned by
+/// [`Tablebase::probe_wdl_with_rule50`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rule50Wdl {
+    /// The value is exact.
+    Exact(Wdl),
+    /// The position is a win or a cursed win, but its DTZ table is not loaded, so it is not known
+    /// whether the counter can be reset before the 50 move rule draws the game.
+    MaybeWin,
+    /// The position is a loss or a blessed loss. See [`Rule50Wdl::MaybeWin`].
+    MaybeLoss,
+}
+
+impl Rule50Wdl {
+    /// The value if it is exact.
+    pub fn exact(self) -> Option<Wdl> {
+        match self {
+            Rule50Wdl::Exact(wdl) => Some(wdl),
+            _ => None,
+        }
+    }
+}
+
+/// A WDL value of a position which may have castling rights, returned by
+/// [`Tablebase::probe_wdl_ignoring_castling`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CastlingWdl {
+    /// The position has no castling rights, so the value is exact.
+    Exact(Wdl),
+    /// Only the side to move can castle, so the value is at least this.
+    AtLeast(Wdl),
+    /// Only the other side can castle, so the value is at most this.
+    AtMost(Wdl),
+}
+
+/// Where the WDL value reported by a probe came from.
+///
+/// See [`Tablebase::probe_wdl_with_source`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ValueSource {
+    /// The value was read from the table of the position.
+    Table,
+    /// The value was found by searching the captures of the position, either because a capture is
+    /// better than the value stored in the table, or because the position is stalemate apart from
+    /// en passant captures.
+    CaptureSearch,
+    /// The value is known without reading a table, as for positions with only the kings.
+    BuiltIn,
+    /// The table of the position is not loaded, and the value came from the fallback set with
+    /// [`Tablebase::set_fallback`].
+    Fallback,
+}
+
+/// The WDL value of a position and the capture which achieves it, returned by
+/// [`Tablebase::probe_wdl_result`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProbeResult {
+    pub wdl: Wdl,
+    /// The best move if it is a capture or en passant capture, which has to be found by searching
+    /// since the tables do not store moves. `None` if a move which is not a capture is at least as
+    /// good; the table value is then achieved by some quiet move.
+    pub best_capture: Option<Move>,
+}
+
+/// Distance to zeroing: the number of plies until the 50 move counter is reset by a capture or
+/// pawn move, assuming optimal play.
+///
+/// The value is positive if the side to move is winning and negative if it is losing. Cursed wins
+/// and blessed losses are offset by 100, so they have a magnitude greater than 100. Since some
+/// tables store distances in moves rather than plies, the value may be one ply further from zero
+/// than the true distance.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Dtz(pub i32);
+
 #[derive(Debug)]
 pub enum SyzygyError {
     NotSyzygy,
     UnknownMaterial,
+    /// The data is too small to contain a Syzygy header. This usually means that a download was
+    /// interrupted. The path is included if the data came from a file.
+    FileTooSmall(Option<std::path::PathBuf>),
+    /// The data is truncated or contains invalid values.
+    Corrupt,
+    /// The header of the table for the named material is truncated or contains invalid values.
+    /// The offset is how far into the file the header was read before the problem was found, or
+    /// the start of the first bad block for tables loaded with
+    /// [`Tablebase::load_bytes_checked`].
+    Corrupted {
+        material: String,
+        offset: usize,
+    },
+    /// The pieces listed in the file do not match the material it was loaded as. This usually
+    /// means that the file was renamed.
+    MaterialMismatch,
+    /// The material has more pieces than this crate supports.
+    TooManyPieces,
+    /// Loading the table would take the total size of the loaded tables over the limit set with
+    /// [`Tablebase::set_max_table_bytes`].
+    TableBytesExceeded,
+    /// The file does not match its checksum, or is not in the checksum list it was checked
+    /// against.
+    ChecksumMismatch,
+    /// A bundle contains compressed tables, which need the `zstd` feature.
+    UnsupportedCompression,
     Io(std::io::Error),
 }
 
@@ -63,6 +243,35 @@ impl std::fmt::Display for SyzygyError {
             SyzygyError::UnknownMaterial => {
                 write!(f, "the material could not be determined")
             }
+            SyzygyError::FileTooSmall(Some(path)) => write!(
+                f,
+                "{} is too small to be a Syzygy tablebase file",
+                path.display()
+            ),
+            SyzygyError::FileTooSmall(None) => {
+                write!(f, "the data is too small to be a Syzygy tablebase file")
+            }
+            SyzygyError::Corrupt => write!(f, "the data is corrupt"),
+            SyzygyError::Corrupted { material, offset } => {
+                write!(f, "the {material} table is corrupt at offset {offset}")
+            }
+            SyzygyError::MaterialMismatch => {
+                write!(f, "the pieces in the file do not match its material")
+            }
+            SyzygyError::TooManyPieces => write!(
+                f,
+                "tables with more than {} pieces are not supported",
+                MAX_PIECES
+            ),
+            SyzygyError::TableBytesExceeded => {
+                write!(f, "loading the table would exceed the limit on table bytes")
+            }
+            SyzygyError::ChecksumMismatch => {
+                write!(f, "the file does not match its checksum")
+            }
+            SyzygyError::UnsupportedCompression => {
+                write!(f, "compressed tables need the zstd feature")
+            }
             SyzygyError::Io(e) => write!(f, "{}", e),
         }
     }
@@ -77,6 +286,46 @@ impl std::error::Error for SyzygyError {
     }
 }
 
+/// The reason a position could not be probed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProbeError {
+    /// The position has castling rights, which tablebases do not include.
+    CastlingRights,
+    /// The position has more pieces than the largest loaded table.
+    TooManyPieces,
+    /// The position has more pieces than the tablebase completely covers. See
+    /// [`Tablebase::set_require_complete_coverage`].
+    IncompleteCoverage,
+    /// The table for the named material, e.g. `KQvK`, is not loaded. This may be the table for
+    /// the position after a capture rather than for the probed position itself.
+    MissingTable(String),
+    /// The table for the named material turned out to be corrupt.
+    CorruptTable(String),
+    /// The position could not be converted to a legal [`Board`][cozy_chess::Board].
+    InvalidPosition,
+    /// The position and its color-flipped mirror gave different results. See
+    /// [`Tablebase::probe_wdl_cross_checked`].
+    ColorFlipMismatch,
+}
+
+impl std::fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProbeError::CastlingRights => write!(f, "the position has castling rights"),
+            ProbeError::TooManyPieces => write!(f, "the position has too many pieces"),
+            ProbeError::IncompleteCoverage => write!(f, "the position is not completely covered"),
+            ProbeError::MissingTable(material) => write!(f, "the {material} table is not loaded"),
+            ProbeError::CorruptTable(material) => write!(f, "the {material} table is corrupt"),
+            ProbeError::InvalidPosition => write!(f, "the position is not valid"),
+            ProbeError::ColorFlipMismatch => {
+                write!(f, "the position and its color-flipped mirror disagree")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProbeError {}
+
 const CANONICAL_PIECE_ORDER: [Piece; 5] = [
     Piece::Queen,
     Piece::Rook,
@@ -85,15 +334,45 @@ const CANONICAL_PIECE_ORDER: [Piece; 5] = [
     Piece::Pawn,
 ];
 
+/// The pieces of each side of an endgame, other than the kings, e.g. `KRPvKR`.
+///
+/// Materials are parsed from and displayed as the names of Syzygy files, and the number of each
+/// piece can be read and changed by indexing with a color and piece. The default material is
+/// KvK.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
-struct Material([[u8; 5]; 2]);
+pub struct Material([[u8; 5]; 2]);
 
 impl Material {
-    fn is_symmetric(&self) -> bool {
+    /// The material of a position.
+    pub fn of(position: &impl ProbePosition) -> Self {
+        let mut material = Material::default();
+        for c in Color::ALL {
+            for p in Piece::ALL {
+                if p == Piece::King {
+                    continue;
+                }
+                material[(c, p)] = (position.pieces(p) & position.colors(c)).len() as u8;
+            }
+        }
+        material
+    }
+
+    /// The material with the colors swapped if needed to give the name its table is stored
+    /// under. For example, the canonical material of `KvKR` is `KRvK`.
+    pub fn canonical(self) -> Self {
+        match self.is_canonical() {
+            true => self,
+            false => self.flip(),
+        }
+    }
+
+    /// Whether both sides have the same pieces, e.g. `KRvKR`.
+    pub fn is_symmetric(&self) -> bool {
         self.0[0] == self.0[1]
     }
 
-    fn is_canonical(&self) -> bool {
+    /// Whether this is the name its table is stored under. The stronger side comes first.
+    pub fn is_canonical(&self) -> bool {
         let white: u8 = self.0[0].iter().sum();
         let black = self.0[1].iter().sum();
         match white.cmp(&black) {
@@ -111,11 +390,19 @@ impl Material {
         true // symmetric
     }
 
-    fn flip(self) -> Self {
+    /// Whether positions with this material are looked up with the colors swapped. See
+    /// [`is_color_flipped`].
+    fn color_flip(&self, side_to_move: Color) -> bool {
+        !self.is_canonical() || self.is_symmetric() && side_to_move == Color::Black
+    }
+
+    /// The material with the colors swapped.
+    pub fn flip(self) -> Self {
         Material([self.0[1], self.0[0]])
     }
 
-    fn count(&self) -> u8 {
+    /// The number of pieces, including the kings.
+    pub fn count(&self) -> u8 {
         self.0.iter().flatten().sum::<u8>() + 2 // 2 kings
     }
 }
@@ -243,50 +530,12 @@ impl ColoredPiece {
     }
 }
 
-struct DataStream<'a> {
-    read_so_far: usize,
-    data: &'a [u8],
-}
-
-impl<'a> DataStream<'a> {
-    fn new(data: &'a [u8]) -> Self {
-        DataStream {
-            read_so_far: 0,
-            data,
-        }
-    }
-
-    fn align_to(&mut self, bytes: usize) {
-        let over = self.read_so_far % bytes;
-        if over > 0 {
-            self.read_array(bytes - over);
-        }
-    }
-
-    fn read_u8(&mut self) -> u8 {
-        self.read_array(1)[0]
-    }
-
-    fn read_u16(&mut self) -> u16 {
-        u16::from_le_bytes(self.read_array(2).try_into().unwrap())
-    }
-
-    fn read_u32(&mut self) -> u32 {
-        u32::from_le_bytes(self.read_array(4).try_into().unwrap())
-    }
-
-    fn read_array(&mut self, size: usize) -> &'a [u8] {
-        let (a, r) = self.data.split_at(size);
-        self.data = r;
-        self.read_so_far += size;
-        a
-    }
-}
-
 enum Data {
     StaticBytes(&'static [u8]),
     OwnedBytes(Box<[u8]>),
+    #[cfg(feature = "mmap")]
     File(Mmap),
+    Source(Box<dyn TableSource>),
 }
 
 impl AsRef<[u8]> for Data {
@@ -294,7 +543,9 @@ impl AsRef<[u8]> for Data {
         match self {
             Data::StaticBytes(b) => b,
             Data::OwnedBytes(b) => b,
+            #[cfg(feature = "mmap")]
             Data::File(f) => f,
+            Data::Source(s) => s.bytes(),
         }
     }
 }
@@ -0,0 +1,88 @@
+/// How a memory-mapped table will be read, given to the kernel with `madvise`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Advice {
+    /// The default read-ahead.
+    Normal,
+    /// Pages are read in no particular order, so read-ahead is disabled. This suits probing,
+    /// which reads one block at a time from anywhere in the table.
+    Random,
+    /// Pages are read in order, so read-ahead is more aggressive.
+    Sequential,
+    /// The whole table will be read soon, so it is read in the background.
+    WillNeed,
+}
+
+/// How table files are memory-mapped. See
+/// [`Tablebase::set_mmap_options`][crate::Tablebase::set_mmap_options].
+///
+/// The options apply to the tables loaded while they are set, so different tables can be mapped
+/// differently, e.g. to lock only the small tables into memory:
+///
+/// ```no_run
+/// # use cozy_syzygy::{Advice, MmapOptions, Tablebase};
+/// let mut tb = Tablebase::new();
+/// let random = MmapOptions {
+///     advice: Some(Advice::Random),
+///     ..MmapOptions::default()
+/// };
+/// tb.set_mmap_options(MmapOptions {
+///     lock: true,
+///     populate: true,
+///     ..random
+/// });
+/// tb.add_directory("/path/to/syzygy/3-4-5")?;
+/// tb.set_mmap_options(random);
+/// tb.add_directory("/path/to/syzygy/6-wdl")?;
+/// # Ok::<(), cozy_syzygy::SyzygyError>(())
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MmapOptions {
+    /// The advice given for the whole file, or `None` to leave the kernel's default.
+    pub advice: Option<Advice>,
+    /// Lock the file into memory with `mlock`, so that probes never wait for the disk. The amount
+    /// of memory a process may lock is limited by `RLIMIT_MEMLOCK`, and files which can't be
+    /// locked fail to load.
+    pub lock: bool,
+    /// Read the whole file when it is loaded, rather than when its pages are first probed.
+    pub populate: bool,
+}
+
+impl MmapOptions {
+    /// Apply the options to a newly mapped file.
+    pub(crate) fn apply(&self, map: &[u8]) -> std::io::Result<()> {
+        if let Some(advice) = self.advice {
+            advise(map, advice)?;
+        }
+        if self.lock {
+            // Locking also reads the file, so populating it is not needed.
+            return check(unsafe { libc::mlock(map.as_ptr().cast(), map.len()) });
+        }
+        if self.populate {
+            let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(1) as usize;
+            for i in (0..map.len()).step_by(page_size) {
+                // The read must not be optimized out, since it is only done to fault the page in.
+                unsafe { std::ptr::read_volatile(&map[i]) };
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Give `advice` for a mapped file.
+pub(crate) fn advise(map: &[u8], advice: Advice) -> std::io::Result<()> {
+    let advice = match advice {
+        Advice::Normal => libc::MADV_NORMAL,
+        Advice::Random => libc::MADV_RANDOM,
+        Advice::Sequential => libc::MADV_SEQUENTIAL,
+        Advice::WillNeed => libc::MADV_WILLNEED,
+    };
+    // Mappings start on a page boundary, as `madvise` requires.
+    check(unsafe { libc::madvise(map.as_ptr() as *mut _, map.len(), advice) })
+}
+
+fn check(result: libc::c_int) -> std::io::Result<()> {
+    match result {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error()),
+    }
+}
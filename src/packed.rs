@@ -0,0 +1,228 @@
+//! A compact 192-bit position encoding, described at [`PackedPosition`].
+
+use cozy_chess::{BitBoard, Board, Color, Piece, Rank, Square};
+
+use crate::ProbePosition;
+
+/// A position decoded from the 192-bit packed encoding.
+///
+/// The encoding is 24 bytes:
+/// - Bytes 0 to 7 are the occupied squares, as a little-endian bitboard.
+/// - Bytes 8 to 23 contain a 4-bit code for each occupied square in increasing order of square,
+///   with the low nibble of each byte first. Unused nibbles are zero.
+///
+/// The codes are:
+/// - 0 to 5: a white pawn, knight, bishop, rook, queen, or king.
+/// - 6 to 11: a black pawn, knight, bishop, rook, queen, or king.
+/// - 12: a pawn which can be captured en passant. It is white if it is on the fourth rank, and
+///   black if it is on the fifth rank.
+/// - 13: a rook with castling rights. It is white if it is on the first rank, and black if it is on
+///   the eighth rank.
+/// - 14: the black king, when it is black to move.
+///
+/// This implements [`ProbePosition`], so it can be probed without constructing a [`Board`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PackedPosition {
+    pieces: [BitBoard; Piece::NUM],
+    colors: [BitBoard; Color::NUM],
+    side_to_move: Color,
+    en_passant: Option<Square>,
+    castling_rooks: BitBoard,
+}
+
+impl PackedPosition {
+    /// Decode a packed position. Returns `None` if the encoding is not valid.
+    pub fn from_bytes(bytes: &[u8; 24]) -> Option<PackedPosition> {
+        let occupied = BitBoard(u64::from_le_bytes(bytes[..8].try_into().ok()?));
+        if occupied.len() > 32 {
+            return None;
+        }
+
+        let mut pos = PackedPosition {
+            pieces: [BitBoard::EMPTY; Piece::NUM],
+            colors: [BitBoard::EMPTY; Color::NUM],
+            side_to_move: Color::White,
+            en_passant: None,
+            castling_rooks: BitBoard::EMPTY,
+        };
+        let mut ep_pawn = None;
+
+        for (i, sq) in occupied.into_iter().enumerate() {
+            let code = (bytes[8 + i / 2] >> (i % 2 * 4)) & 0xF;
+            let (piece, color) = match code {
+                0..=11 => (
+                    Piece::index(code as usize % 6),
+                    Color::index(code as usize / 6),
+                ),
+                12 if ep_pawn.is_none() => {
+                    let color = match sq.rank() {
+                        Rank::Fourth => Color::White,
+                        Rank::Fifth => Color::Black,
+                        _ => return None,
+                    };
+                    ep_pawn = Some((sq, color));
+                    (Piece::Pawn, color)
+                }
+                13 => {
+                    pos.castling_rooks |= sq.bitboard();
+                    match sq.rank() {
+                        Rank::First => (Piece::Rook, Color::White),
+                        Rank::Eighth => (Piece::Rook, Color::Black),
+                        _ => return None,
+                    }
+                }
+                14 => {
+                    pos.side_to_move = Color::Black;
+                    (Piece::King, Color::Black)
+                }
+                _ => return None,
+            };
+            pos.pieces[piece as usize] |= sq.bitboard();
+            pos.colors[color as usize] |= sq.bitboard();
+        }
+
+        if let Some((sq, color)) = ep_pawn {
+            // The pawn must have just moved, so the other side is to move.
+            if color == pos.side_to_move {
+                return None;
+            }
+            let rank = Rank::Third.relative_to(color);
+            pos.en_passant = Some(Square::new(sq.file(), rank));
+        }
+
+        Some(pos)
+    }
+
+    /// Encode a position.
+    pub fn encode(board: &Board) -> [u8; 24] {
+        let mut bytes = [0; 24];
+        let occupied = board.occupied();
+        bytes[..8].copy_from_slice(&occupied.0.to_le_bytes());
+
+        let ep_pawn = board.en_passant().map(|f| {
+            let rank = Rank::Fifth.relative_to(board.side_to_move());
+            Square::new(f, rank)
+        });
+        let mut castling_rooks = BitBoard::EMPTY;
+        for c in Color::ALL {
+            let rights = board.castle_rights(c);
+            for file in [rights.short, rights.long].into_iter().flatten() {
+                castling_rooks |= Square::new(file, Rank::First.relative_to(c)).bitboard();
+            }
+        }
+
+        for (i, sq) in occupied.into_iter().enumerate() {
+            let (Some(piece), Some(color)) = (board.piece_on(sq), board.color_on(sq)) else {
+                continue;
+            };
+            let code = if Some(sq) == ep_pawn {
+                12
+            } else if castling_rooks.has(sq) {
+                13
+            } else if piece == Piece::King
+                && color == Color::Black
+                && board.side_to_move() == Color::Black
+            {
+                14
+            } else {
+                color as u8 * 6 + piece as u8
+            };
+            bytes[8 + i / 2] |= code << (i % 2 * 4);
+        }
+
+        bytes
+    }
+}
+
+impl ProbePosition for PackedPosition {
+    fn pieces(&self, piece: Piece) -> BitBoard {
+        self.pieces[piece as usize]
+    }
+
+    fn colors(&self, color: Color) -> BitBoard {
+        self.colors[color as usize]
+    }
+
+    fn side_to_move(&self) -> Color {
+        self.side_to_move
+    }
+
+    fn en_passant(&self) -> Option<Square> {
+        self.en_passant
+    }
+
+    fn has_castle_rights(&self) -> bool {
+        !self.castling_rooks.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pack(squares: &[(Square, u8)]) -> [u8; 24] {
+        let mut bytes = [0; 24];
+        let mut occupied = BitBoard::EMPTY;
+        for &(sq, _) in squares {
+            occupied |= sq.bitboard();
+        }
+        bytes[..8].copy_from_slice(&occupied.0.to_le_bytes());
+        let mut sorted = squares.to_vec();
+        sorted.sort_by_key(|&(sq, _)| sq as usize);
+        for (i, (_, code)) in sorted.into_iter().enumerate() {
+            bytes[8 + i / 2] |= code << (i % 2 * 4);
+        }
+        bytes
+    }
+
+    #[test]
+    fn decodes_pieces_and_side_to_move() {
+        let pos = PackedPosition::from_bytes(&pack(&[
+            (Square::E1, 5),
+            (Square::D2, 3),
+            (Square::E8, 14),
+        ]))
+        .unwrap();
+        assert_eq!(pos.side_to_move(), Color::Black);
+        assert_eq!(
+            pos.pieces(Piece::King),
+            Square::E1.bitboard() | Square::E8.bitboard()
+        );
+        assert_eq!(
+            pos.pieces(Piece::Rook) & pos.colors(Color::White),
+            Square::D2.bitboard()
+        );
+        assert_eq!(pos.colors(Color::Black), Square::E8.bitboard());
+        assert_eq!(pos.en_passant(), None);
+        assert!(!pos.has_castle_rights());
+    }
+
+    #[test]
+    fn decodes_en_passant_and_castling() {
+        let pos = PackedPosition::from_bytes(&pack(&[
+            (Square::E1, 5),
+            (Square::H1, 13),
+            (Square::D4, 12),
+            (Square::E8, 14),
+        ]))
+        .unwrap();
+        assert_eq!(pos.en_passant(), Some(Square::D3));
+        assert_eq!(
+            pos.pieces(Piece::Pawn) & pos.colors(Color::White),
+            Square::D4.bitboard()
+        );
+        assert!(pos.has_castle_rights());
+
+        // A white pawn can't have just moved when it is white to move.
+        let bytes = pack(&[(Square::E1, 5), (Square::D4, 12), (Square::E8, 11)]);
+        assert_eq!(PackedPosition::from_bytes(&bytes), None);
+    }
+
+    #[test]
+    fn rejects_invalid_codes() {
+        let bytes = pack(&[(Square::E1, 5), (Square::E8, 11), (Square::A1, 15)]);
+        assert_eq!(PackedPosition::from_bytes(&bytes), None);
+        let bytes = pack(&[(Square::E1, 5), (Square::E8, 11), (Square::A3, 13)]);
+        assert_eq!(PackedPosition::from_bytes(&bytes), None);
+    }
+}
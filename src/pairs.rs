@@ -1,17 +1,25 @@
-use crate::DataStream;
+use std::ops::Range;
 
-pub struct PairsData<'data> {
+use crate::block_cache::{BlockCache, Symbols};
+use crate::{DataStream, SyzygyError};
+
+/// The parameters of a compressed table. The tables themselves are stored as ranges into the
+/// file data, which is provided at lookup time.
+pub struct PairsData {
+    flags: u8,
+    /// The number of values in the table.
+    values: usize,
     index_bits: usize,
     min_len: usize,
     block_size: usize,
-    offsets: &'data [u8],
-    sympat: &'data [u8],
+    offsets: Range<usize>,
+    sympat: Range<usize>,
     symlen: Vec<u8>,
     base: Vec<u64>,
     // Filled in elsewhere
-    pub index_table: &'data [u8],
-    pub size_table: &'data [u8],
-    pub data: &'data [u8],
+    pub index_table: Range<usize>,
+    pub size_table: Range<usize>,
+    pub data: Range<usize>,
 }
 
 #[derive(Default, Debug, Copy, Clone)]
@@ -21,13 +29,19 @@ pub struct Sizes {
     pub data_table_size: usize,
 }
 
-impl<'data> PairsData<'data> {
-    pub(crate) fn create(data: &mut DataStream<'data>, tb_size: usize, wdl: bool) -> (Self, Sizes) {
-        let flags = data.read_u8();
+impl PairsData {
+    pub(crate) fn create(
+        data: &mut DataStream,
+        tb_size: usize,
+        wdl: bool,
+    ) -> Result<(Self, Sizes), SyzygyError> {
+        let flags = data.read_u8()?;
         if flags & 0x80 != 0 {
-            let min_len = data.read_u8() as usize;
-            return (
+            let min_len = data.read_u8()? as usize;
+            return Ok((
                 PairsData {
+                    flags,
+                    values: tb_size,
                     index_bits: 0,
                     min_len: match wdl {
                         true => min_len,
@@ -36,48 +50,54 @@ impl<'data> PairsData<'data> {
                     block_size: 0,
                     symlen: vec![],
                     base: vec![],
-                    offsets: &[],
-                    index_table: &[],
-                    size_table: &[],
-                    data: &[],
-                    sympat: &[],
+                    offsets: 0..0,
+                    index_table: 0..0,
+                    size_table: 0..0,
+                    data: 0..0,
+                    sympat: 0..0,
                 },
                 Sizes {
                     index_table_size: 0,
                     size_table_size: 0,
                     data_table_size: 0,
                 },
-            );
+            ));
         }
 
-        let block_size = data.read_u8() as usize;
-        let index_bits = data.read_u8() as usize;
-        let extra_blocks = data.read_u8() as usize;
-        let real_num_blocks = data.read_u32() as usize;
+        let block_size = data.read_u8()? as usize;
+        let index_bits = data.read_u8()? as usize;
+        let extra_blocks = data.read_u8()? as usize;
+        let real_num_blocks = data.read_u32()? as usize;
         let num_blocks = real_num_blocks + extra_blocks;
-        let max_len = data.read_u8() as usize;
-        let min_len = data.read_u8() as usize;
+        let max_len = data.read_u8()? as usize;
+        let min_len = data.read_u8()? as usize;
+        // An index_bits of 0 would be mistaken for a constant table by lookup.
+        if block_size >= 32 || index_bits == 0 || index_bits >= 32 {
+            return Err(SyzygyError::Corrupt);
+        }
+        if min_len == 0 || max_len < min_len || max_len > 64 {
+            return Err(SyzygyError::Corrupt);
+        }
         let h = max_len - min_len + 1;
-        let offsets = data.read_array(2 * h);
-        let num_syms = data.read_u16() as usize;
-        let sympat = data.read_array(3 * num_syms);
-        data.align_to(2);
+        let offsets_start = data.position();
+        let offsets = data.read_array(2 * h)?;
+        let num_syms = data.read_u16()? as usize;
+        let sympat_start = data.position();
+        let sympat = data.read_array(3 * num_syms)?;
+        data.align_to(2)?;
 
         let num_indices = (tb_size + (1 << index_bits) - 1) >> index_bits;
 
-        let mut tmp = vec![false; num_syms];
-        let mut symlen = vec![0; num_syms];
-        for i in 0..num_syms {
-            if !tmp[i] {
-                calculate_symlen(&mut symlen, sympat, i, &mut tmp);
-            }
-        }
+        let symlen = calculate_symlen(sympat, num_syms)?;
 
         let mut base = vec![0; h];
         for i in (0..h - 1).rev() {
-            let off_i = u16::from_le_bytes(offsets[2 * i..2 * i + 2].try_into().unwrap());
-            let off_ip1 = u16::from_le_bytes(offsets[2 * i + 2..2 * i + 4].try_into().unwrap());
-            base[i] = (base[i + 1] + off_i as u64 - off_ip1 as u64) / 2;
+            let off_i = u16::from_le_bytes([offsets[2 * i], offsets[2 * i + 1]]);
+            let off_ip1 = u16::from_le_bytes([offsets[2 * i + 2], offsets[2 * i + 3]]);
+            base[i] = (base[i + 1] + off_i as u64)
+                .checked_sub(off_ip1 as u64)
+                .ok_or(SyzygyError::Corrupt)?
+                / 2;
         }
         for i in 0..h {
             base[i] <<= 64 - (min_len + i);
@@ -86,101 +106,113 @@ impl<'data> PairsData<'data> {
         // offsets is shifted back by min_len here in the C, but that's obviously terrible in Rust,
         // so we'll just have to remember to subtract min_len before we access it later.
 
-        (
+        let data_table_size = real_num_blocks
+            .checked_mul(1 << block_size)
+            .ok_or(SyzygyError::Corrupt)?;
+
+        Ok((
             PairsData {
+                flags,
+                values: tb_size,
                 index_bits,
                 min_len,
                 block_size,
-                offsets,
-                sympat,
+                offsets: offsets_start..offsets_start + offsets.len(),
+                sympat: sympat_start..sympat_start + sympat.len(),
                 symlen,
                 base,
                 // these need to be filled in later by the caller
-                index_table: &[],
-                size_table: &[],
-                data: &[],
+                index_table: 0..0,
+                size_table: 0..0,
+                data: 0..0,
             },
             Sizes {
                 index_table_size: 6 * num_indices,
                 size_table_size: 2 * num_blocks,
-                data_table_size: (1 << block_size) * real_num_blocks,
+                data_table_size,
             },
-        )
+        ))
     }
 
-    pub fn lookup(&self, index: u64) -> u8 {
+    /// Decode the value at the specified index. Returns `None` if the data is corrupt.
+    pub fn lookup(&self, data: &[u8], cache: Option<&BlockCache>, index: u64) -> Option<u16> {
+        if index >= self.values as u64 {
+            return None;
+        }
         if self.index_bits == 0 {
-            return self.min_len as u8;
+            return Some(self.min_len as u16);
         }
 
-        let main_index = (index >> self.index_bits) as usize;
+        let index_table = data.get(self.index_table.clone())?;
+        let size_table = data.get(self.size_table.clone())?;
+        let offsets = data.get(self.offsets.clone())?;
+        let sympat = data.get(self.sympat.clone())?;
+        let data = data.get(self.data.clone())?;
+
+        let main_index = usize::try_from(index >> self.index_bits).ok()?;
         let index_bits_mask = (1 << self.index_bits) - 1;
         let mut lit_index = (index & index_bits_mask) as i64 - (1 << self.index_bits - 1);
 
-        let mut block = u32::from_le_bytes(
-            self.index_table[6 * main_index..6 * main_index + 4]
-                .try_into()
-                .unwrap(),
-        ) as usize;
-
-        lit_index += u16::from_le_bytes(
-            self.index_table[6 * main_index + 4..6 * main_index + 6]
-                .try_into()
-                .unwrap(),
-        ) as i64;
+        let entry = index_table.get(6 * main_index..6 * main_index + 6)?;
+        let mut block = u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]) as usize;
+        lit_index += u16::from_le_bytes([entry[4], entry[5]]) as i64;
 
-        let size_table =
-            |i| u16::from_le_bytes(self.size_table[2 * i..2 * i + 2].try_into().unwrap());
+        let size = |i: usize| {
+            let s = size_table.get(2 * i..2 * i + 2)?;
+            Some(u16::from_le_bytes([s[0], s[1]]) as i64)
+        };
 
         if lit_index < 0 {
             while lit_index < 0 {
-                block -= 1;
-                lit_index += size_table(block) as i64 + 1;
+                block = block.checked_sub(1)?;
+                lit_index += size(block)? + 1;
             }
         } else {
-            while lit_index > size_table(block) as i64 {
-                lit_index -= size_table(block) as i64 + 1;
+            while lit_index > size(block)? {
+                lit_index -= size(block)? + 1;
                 block += 1;
             }
         }
 
-        let mut ptr = &self.data[block << self.block_size..];
-
-        let offset = |l: usize| {
-            u16::from_le_bytes(
-                self.offsets[2 * (l - self.min_len)..2 * (l - self.min_len + 1)]
-                    .try_into()
-                    .unwrap(),
-            )
-        };
-        let base = |l: usize| self.base[l - self.min_len];
+        let mut ptr = data.get(block << self.block_size..)?;
 
-        let mut code = u64::from_be_bytes(ptr[0..8].try_into().unwrap());
-        ptr = &ptr[8..];
-        let mut bitcount = 0;
-        let mut sym = loop {
-            let mut l = self.min_len;
-            while base(l) > code {
-                l += 1;
+        let mut sym = match cache {
+            Some(cache) => {
+                let (sym, index) = cache.find(ptr.as_ptr() as usize, lit_index as u32, || {
+                    self.decode_block(size_table, offsets, data, block)
+                })?;
+                lit_index = index as i64;
+                sym as usize
             }
-            let sym = offset(l) as usize + (code - base(l) >> 64 - l) as usize;
-            if lit_index < self.symlen[sym] as i64 + 1 {
-                break sym;
-            }
-            lit_index -= self.symlen[sym] as i64 + 1;
-            code <<= l;
-            bitcount += l;
-            if bitcount >= 32 {
-                bitcount -= 32;
-                if !ptr.is_empty() {
-                    code |= (u32::from_be_bytes(ptr[0..4].try_into().unwrap()) as u64) << bitcount;
-                    ptr = &ptr[4..];
+            None => {
+                let mut code = u64::from_be_bytes(ptr.get(0..8)?.try_into().ok()?);
+                ptr = &ptr[8..];
+                let mut bitcount = 0;
+                loop {
+                    let (sym, l) = self.decode_symbol(offsets, code)?;
+                    let symlen = *self.symlen.get(sym)? as i64;
+                    if lit_index < symlen + 1 {
+                        break sym;
+                    }
+                    lit_index -= symlen + 1;
+                    code = code.checked_shl(l as u32).unwrap_or(0);
+                    bitcount += l;
+                    if bitcount >= 32 {
+                        bitcount -= 32;
+                        if !ptr.is_empty() {
+                            let next = u32::from_be_bytes(ptr.get(0..4)?.try_into().ok()?) as u64;
+                            // Codes longer than 32 bits leave bitcount above 32 in corrupt
+                            // tables, so this can shift out the whole word.
+                            code |= next.checked_shl(bitcount as u32).unwrap_or(0);
+                            ptr = &ptr[4..];
+                        }
+                    }
                 }
             }
         };
 
         while self.symlen[sym] != 0 {
-            let w = read_u24(self.sympat[3 * sym..3 * sym + 3].try_into().unwrap()) as usize;
+            let w = read_u24(sympat.get(3 * sym..3 * sym + 3)?.try_into().ok()?) as usize;
             let s1 = w & 0xFFF;
             if lit_index < self.symlen[s1] as i64 + 1 {
                 sym = s1;
@@ -190,28 +222,415 @@ impl<'data> PairsData<'data> {
             }
         }
 
-        return self.sympat[3 * sym];
+        let w = read_u24(sympat.get(3 * sym..3 * sym + 3)?.try_into().ok()?);
+        Some((w & 0xFFF) as u16)
+    }
+
+    /// The flags byte of the table. DTZ tables store which side to move they contain, and how
+    /// their values are mapped, here.
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// The number of values in the table, which are looked up with indices below this.
+    #[cfg(feature = "raw")]
+    pub fn values(&self) -> usize {
+        self.values
+    }
+
+    /// The number of compressed blocks, which can be checked with [`PairsData::check_block`].
+    pub fn blocks(&self) -> usize {
+        self.data.len() >> self.block_size
+    }
+
+    /// Decode every code in a block, checking that they expand to the number of values recorded
+    /// in the size table without running past the end of the block. Returns `None` if they don't.
+    pub fn check_block(&self, data: &[u8], block: usize) -> Option<()> {
+        let size_table = data.get(self.size_table.clone())?;
+        let offsets = data.get(self.offsets.clone())?;
+        let data = data.get(self.data.clone())?;
+        self.decode_block(size_table, offsets, data, block)
+            .map(|_| ())
     }
-}
 
-fn calculate_symlen(symlen: &mut [u8], sympat: &[u8], s: usize, tmp: &mut [bool]) {
-    let w = read_u24(sympat[3 * s..3 * s + 3].try_into().unwrap()) as usize;
-    let s2 = w >> 12;
-    if s2 == 0xFFF {
-        symlen[s] = 0;
-    } else {
-        let s1 = w & 0xFFF;
-        if !tmp[s1] {
-            calculate_symlen(symlen, sympat, s1, tmp);
+    /// The offset in the file data of a compressed block.
+    pub fn block_offset(&self, block: usize) -> usize {
+        self.data.start + (block << self.block_size)
+    }
+
+    /// Decode every code in a block into its symbols, each with the index of its first value. See
+    /// [`PairsData::check_block`] for when this returns `None`.
+    fn decode_block(
+        &self,
+        size_table: &[u8],
+        offsets: &[u8],
+        data: &[u8],
+        block: usize,
+    ) -> Option<Symbols> {
+        let size = size_table.get(2 * block..2 * block + 2)?;
+        let values = u16::from_le_bytes([size[0], size[1]]) as usize + 1;
+        let mut remaining = values;
+        let mut symbols = vec![];
+
+        let mut ptr = data.get(block << self.block_size..)?;
+        let mut code = u64::from_be_bytes(ptr.get(0..8)?.try_into().ok()?);
+        ptr = &ptr[8..];
+        let mut bitcount = 0;
+        let mut used = 0;
+        while remaining > 0 {
+            let (sym, l) = self.decode_symbol(offsets, code)?;
+            symbols.push(((values - remaining) as u32, sym as u16));
+            remaining = remaining.checked_sub(*self.symlen.get(sym)? as usize + 1)?;
+            used += l;
+            if used > 8 << self.block_size {
+                return None;
+            }
+            code = code.checked_shl(l as u32).unwrap_or(0);
+            bitcount += l;
+            if bitcount >= 32 {
+                bitcount -= 32;
+                if let Some(next) = ptr.get(0..4) {
+                    let next = u32::from_be_bytes(next.try_into().ok()?) as u64;
+                    code |= next.checked_shl(bitcount as u32).unwrap_or(0);
+                    ptr = &ptr[4..];
+                }
+            }
         }
-        if !tmp[s2] {
-            calculate_symlen(symlen, sympat, s2, tmp);
+
+        Some(symbols.into())
+    }
+
+    /// Read a byte from each page of the index table, the size table, and the first block, so
+    /// that they are loaded into memory. The bytes are combined and returned so that the reads
+    /// can't be optimized out.
+    pub fn touch(&self, data: &[u8]) -> u8 {
+        const PAGE_SIZE: usize = 4096;
+        let first_block_end = self.data.start.saturating_add(1 << self.block_size);
+        let first_block = self.data.start..first_block_end.min(self.data.end);
+        [
+            self.index_table.clone(),
+            self.size_table.clone(),
+            first_block,
+        ]
+        .into_iter()
+        .flat_map(|range| range.step_by(PAGE_SIZE))
+        .filter_map(|i| data.get(i))
+        .fold(0, |acc, &b| acc ^ b)
+    }
+
+    /// Decode the symbol whose code is at the start of `code`, returning it along with the length
+    /// of its code.
+    fn decode_symbol(&self, offsets: &[u8], code: u64) -> Option<(usize, usize)> {
+        let base = |l: usize| self.base.get(l - self.min_len).copied();
+        let mut l = self.min_len;
+        while base(l)? > code {
+            l += 1;
         }
-        symlen[s] = symlen[s1] + symlen[s2] + 1;
+        let o = offsets.get(2 * (l - self.min_len)..2 * (l - self.min_len + 1))?;
+        let sym = u16::from_le_bytes([o[0], o[1]]) as usize + (code - base(l)? >> 64 - l) as usize;
+        Some((sym, l))
     }
-    tmp[s] = true;
+}
+
+/// Computes the number of values minus one that each symbol expands to.
+///
+/// Pairs are expanded with an explicit stack rather than recursion, so that crafted tables with
+/// deeply nested or cyclic pairs are rejected instead of overflowing the stack. Every symbol
+/// referenced by a pair is checked to exist, so the expansion in [`PairsData::lookup`] only needs
+/// to check the symbol from the code.
+fn calculate_symlen(sympat: &[u8], num_syms: usize) -> Result<Vec<u8>, SyzygyError> {
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    enum State {
+        New,
+        /// The symbol's pairs are being expanded, so it is on the stack.
+        Expanding,
+        Done,
+    }
+
+    let pair = |s: usize| {
+        let w = read_u24([sympat[3 * s], sympat[3 * s + 1], sympat[3 * s + 2]]) as usize;
+        (w & 0xFFF, w >> 12)
+    };
+
+    let mut symlen = vec![0u8; num_syms];
+    let mut state = vec![State::New; num_syms];
+    let mut stack = vec![];
+    for first in 0..num_syms {
+        stack.push(first);
+        while let Some(&s) = stack.last() {
+            let (s1, s2) = pair(s);
+            if state[s] == State::Done || s2 == 0xFFF {
+                state[s] = State::Done;
+                stack.pop();
+                continue;
+            }
+            if s1 >= num_syms || s2 >= num_syms {
+                return Err(SyzygyError::Corrupt);
+            }
+
+            if state[s] == State::New {
+                state[s] = State::Expanding;
+                for next in [s2, s1] {
+                    match state[next] {
+                        State::New => stack.push(next),
+                        // Every expanding symbol is below this one on the stack.
+                        State::Expanding => return Err(SyzygyError::Corrupt),
+                        State::Done => {}
+                    }
+                }
+                continue;
+            }
+
+            symlen[s] = symlen[s1]
+                .checked_add(symlen[s2])
+                .and_then(|l| l.checked_add(1))
+                .ok_or(SyzygyError::Corrupt)?;
+            state[s] = State::Done;
+            stack.pop();
+        }
+    }
+    Ok(symlen)
 }
 
 fn read_u24(data: [u8; 3]) -> u32 {
     u32::from_le_bytes([data[0], data[1], data[2], 0])
 }
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// A table of 32 values, stored in three blocks of 16 bytes with an index entry every 8
+    /// values.
+    ///
+    /// The symbols are:
+    /// - `0`: `000`, the value 0
+    /// - `1`: `001`, the pair (4, 4), which expands to 2, 4, 2, 4
+    /// - `2`: `01`, the value 2
+    /// - `3`: `10`, the value 4
+    /// - `4`: `11`, the pair (2, 3), which expands to 2, 4
+    #[rustfmt::skip]
+    const TABLE: &[u8] = &[
+        // flags, block size, index bits, extra blocks
+        0x00, 4, 3, 0,
+        // real number of blocks
+        3, 0, 0, 0,
+        // max and min code length
+        3, 2,
+        // offsets for code lengths 2 and 3
+        2, 0, 0, 0,
+        // number of symbols
+        5, 0,
+        // symbol patterns
+        0x00, 0xF0, 0xFF,
+        0x04, 0x40, 0x00,
+        0x02, 0xF0, 0xFF,
+        0x04, 0xF0, 0xFF,
+        0x02, 0x30, 0x00,
+        // padding
+        0,
+        // index table: the block and offset of values 4, 12, 20 and 28
+        0, 0, 0, 0, 4, 0,
+        2, 0, 0, 0, 2, 0,
+        2, 0, 0, 0, 10, 0,
+        2, 0, 0, 0, 18, 0,
+        // size table: the number of values in each block, minus one
+        5, 0, 3, 0, 21, 0,
+        // block 0: 001 10 01
+        0x32, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        // block 1: 000 11 01
+        0x1A, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        // block 2: 21 times 000, then 01 straddling the first refill
+        0, 0, 0, 0, 0, 0, 0, 0, 0x80, 0, 0, 0, 0, 0, 0, 0,
+    ];
+
+    const VALUES: [u16; 32] = [
+        2, 4, 2, 4, 4, 2, // block 0
+        0, 2, 4, 2, // block 1
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, // block 2
+    ];
+
+    #[test]
+    fn lookup_decodes_table() {
+        let mut stream = DataStream::new(TABLE);
+        let (mut pairs_data, sizes) = PairsData::create(&mut stream, VALUES.len(), true).unwrap();
+        assert_eq!(sizes.index_table_size, 24);
+        assert_eq!(sizes.size_table_size, 6);
+        assert_eq!(sizes.data_table_size, 48);
+        pairs_data.index_table = stream.read_range(sizes.index_table_size).unwrap();
+        pairs_data.size_table = stream.read_range(sizes.size_table_size).unwrap();
+        pairs_data.data = stream.read_range(sizes.data_table_size).unwrap();
+        assert_eq!(stream.position(), TABLE.len());
+
+        // The second pass with the cache reads the blocks decoded by the first.
+        let cache = BlockCache::new(1);
+        for cache in [None, Some(&cache), Some(&cache)] {
+            for (index, &value) in VALUES.iter().enumerate() {
+                assert_eq!(
+                    pairs_data.lookup(TABLE, cache, index as u64),
+                    Some(value),
+                    "value {index}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn check_block_accepts_valid_blocks() {
+        let mut stream = DataStream::new(TABLE);
+        let (mut pairs_data, sizes) = PairsData::create(&mut stream, VALUES.len(), true).unwrap();
+        pairs_data.index_table = stream.read_range(sizes.index_table_size).unwrap();
+        pairs_data.size_table = stream.read_range(sizes.size_table_size).unwrap();
+        pairs_data.data = stream.read_range(sizes.data_table_size).unwrap();
+
+        assert_eq!(pairs_data.blocks(), 3);
+        for block in 0..3 {
+            assert_eq!(
+                pairs_data.check_block(TABLE, block),
+                Some(()),
+                "block {block}"
+            );
+        }
+        assert_eq!(pairs_data.check_block(TABLE, 3), None);
+
+        // Block 0 starts with a pair expanding to 4 values, so it can't have only 3.
+        let mut corrupt = TABLE.to_vec();
+        corrupt[pairs_data.size_table.start] = 2;
+        assert_eq!(pairs_data.check_block(&corrupt, 0), None);
+
+        // Block 2 can't fit 256 values.
+        let mut corrupt = TABLE.to_vec();
+        corrupt[pairs_data.size_table.start + 4] = 255;
+        assert_eq!(pairs_data.check_block(&corrupt, 2), None);
+    }
+
+    /// Symbol patterns for the specified pairs of symbols.
+    fn sympat(pairs: &[(usize, usize)]) -> Vec<u8> {
+        let pairs = pairs.iter().map(|&(s1, s2)| (s1 | s2 << 12) as u32);
+        pairs.flat_map(|w| w.to_le_bytes()[..3].to_vec()).collect()
+    }
+
+    #[test]
+    fn symlen_rejects_cyclic_pairs() {
+        let leaf = (0, 0xFFF);
+
+        let valid = sympat(&[leaf, (0, 3), leaf, (2, 0)]);
+        assert_eq!(calculate_symlen(&valid, 4).unwrap(), [0, 2, 0, 1]);
+        assert!(calculate_symlen(&sympat(&[(0, 0)]), 1).is_err());
+        assert!(calculate_symlen(&sympat(&[leaf, (2, 0), (0, 1)]), 3).is_err());
+        assert!(calculate_symlen(&sympat(&[leaf, (0, 2)]), 2).is_err());
+
+        // Every symbol pairs the next one with the first, which pairs itself into a cycle.
+        let mut cycle: Vec<_> = (1..4095).map(|s| (s, 0)).collect();
+        cycle.push(leaf);
+        assert!(calculate_symlen(&sympat(&cycle), cycle.len()).is_err());
+    }
+
+    #[test]
+    fn symlen_limits_the_depth_of_pairs() {
+        // Every symbol pairs the next one with the last, which is a leaf, so the first symbol is
+        // expanded through the whole chain and stands for `len` values.
+        let chain = |len: usize| {
+            let mut chain: Vec<_> = (1..len).map(|s| (s, len - 1)).collect();
+            chain.push((0, 0xFFF));
+            sympat(&chain)
+        };
+
+        assert_eq!(calculate_symlen(&chain(256), 256).unwrap()[0], 255);
+        // The length no longer fits in a byte.
+        assert!(calculate_symlen(&chain(257), 257).is_err());
+        // Expanding doesn't recurse, so even the longest chain fails without overflowing the stack.
+        assert!(calculate_symlen(&chain(4095), 4095).is_err());
+    }
+
+    #[test]
+    fn long_codes_pass_checks_and_are_looked_up() {
+        // 4 values in two blocks, each holding two 64 bit codes for the only symbol, the value 7.
+        #[rustfmt::skip]
+        let mut table = vec![
+            0x00, 4, 1, 0, 2, 0, 0, 0, 64, 64, 0, 0, 1, 0, 0x07, 0xF0, 0xFF, 0,
+            // index table: values 1 and 3 are the second value of each block
+            0, 0, 0, 0, 1, 0, 1, 0, 0, 0, 1, 0,
+            // size table
+            1, 0, 1, 0,
+        ];
+        table.resize(table.len() + 32, 0);
+        let mut stream = DataStream::new(&table);
+        let (mut pairs_data, sizes) = PairsData::create(&mut stream, 4, true).unwrap();
+        pairs_data.index_table = stream.read_range(sizes.index_table_size).unwrap();
+        pairs_data.size_table = stream.read_range(sizes.size_table_size).unwrap();
+        pairs_data.data = stream.read_range(sizes.data_table_size).unwrap();
+
+        // These are the checks of checked loading, which must agree with lookup.
+        for block in 0..2 {
+            assert_eq!(pairs_data.check_block(&table, block), Some(()));
+        }
+        let cache = BlockCache::new(1);
+        for cache in [None, Some(&cache)] {
+            for index in 0..4 {
+                assert_eq!(pairs_data.lookup(&table, cache, index), Some(7));
+            }
+        }
+    }
+
+    /// Bytes of a corrupt table, mostly zero so that short codes and small symbols are common.
+    fn corrupt_bytes(len: usize) -> impl Strategy<Value = Vec<u8>> {
+        prop::collection::vec(prop_oneof![3 => Just(0u8), 1 => any::<u8>()], len)
+    }
+
+    proptest! {
+        #[test]
+        fn long_codes_do_not_panic(
+            min_len in prop_oneof![Just(64u8), 1..=64u8],
+            offsets in corrupt_bytes(128),
+            entries in prop::collection::vec((0..4u32, 0..64u16), 8),
+            block_sizes in prop::collection::vec(0..64u16, 4),
+            blocks in corrupt_bytes(64),
+            index in 0..64u64,
+        ) {
+            // 64 values in four blocks, with codes of up to 64 bits and one symbol.
+            let mut table = vec![0x00, 4, 3, 0, 4, 0, 0, 0, 64, min_len];
+            table.extend_from_slice(&offsets[..2 * (65 - min_len as usize)]);
+            table.extend_from_slice(&[1, 0, 0x00, 0xF0, 0xFF]);
+            table.resize(table.len().next_multiple_of(2), 0);
+            let mut stream = DataStream::new(&table);
+            let Ok((mut pairs_data, sizes)) = PairsData::create(&mut stream, 64, true) else {
+                return Ok(());
+            };
+            let start = stream.position();
+            pairs_data.index_table = start..start + sizes.index_table_size;
+            pairs_data.size_table =
+                pairs_data.index_table.end..pairs_data.index_table.end + sizes.size_table_size;
+            pairs_data.data =
+                pairs_data.size_table.end..pairs_data.size_table.end + sizes.data_table_size;
+            for (block, offset) in entries {
+                table.extend_from_slice(&block.to_le_bytes());
+                table.extend_from_slice(&offset.to_le_bytes());
+            }
+            table.extend(block_sizes.iter().flat_map(|s| s.to_le_bytes()));
+            table.extend_from_slice(&blocks);
+
+            // Without a cache, the codes are decoded by lookup itself.
+            pairs_data.lookup(&table, None, index);
+            pairs_data.lookup(&table, Some(&BlockCache::new(1)), index);
+        }
+    }
+
+    #[test]
+    fn lookup_constant_table() {
+        let data = [0x80, 3];
+        let (pairs_data, sizes) =
+            PairsData::create(&mut DataStream::new(&data), 1000, true).unwrap();
+        assert_eq!(sizes.index_table_size, 0);
+        assert_eq!(sizes.size_table_size, 0);
+        assert_eq!(sizes.data_table_size, 0);
+        for index in [0, 1, 999] {
+            assert_eq!(pairs_data.lookup(&data, None, index), Some(3));
+        }
+
+        let (pairs_data, _) = PairsData::create(&mut DataStream::new(&data), 1000, false).unwrap();
+        assert_eq!(pairs_data.lookup(&data, None, 0), Some(0));
+    }
+}
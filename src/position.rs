@@ -0,0 +1,140 @@
+use cozy_chess::{
+    get_bishop_moves, get_king_moves, get_knight_moves, get_pawn_attacks, get_rook_moves, BitBoard,
+    Board, BoardBuilder, Color, Piece, Rank, Square,
+};
+
+use crate::Material;
+
+/// The information about a position needed to look it up in a tablebase.
+///
+/// This allows board representations other than [`Board`] to be probed without first being
+/// converted. See [`Tablebase::probe_wdl_position`][crate::Tablebase::probe_wdl_position].
+pub trait ProbePosition {
+    /// The squares containing the specified piece of either color.
+    fn pieces(&self, piece: Piece) -> BitBoard;
+
+    /// The squares containing pieces of the specified color.
+    fn colors(&self, color: Color) -> BitBoard;
+
+    fn side_to_move(&self) -> Color;
+
+    /// The square a pawn may move to when capturing en passant, if any.
+    fn en_passant(&self) -> Option<Square>;
+
+    /// Whether either side has any castling rights.
+    fn has_castle_rights(&self) -> bool;
+
+    /// Convert to a [`Board`], which is needed when the position has captures that must be
+    /// searched. Returns `None` if the position is not valid.
+    fn to_board(&self) -> Option<Board> {
+        if self.has_castle_rights() {
+            return None;
+        }
+        let mut builder = BoardBuilder::empty();
+        for c in Color::ALL {
+            for p in Piece::ALL {
+                for sq in self.pieces(p) & self.colors(c) {
+                    *builder.square_mut(sq) = Some((p, c));
+                }
+            }
+        }
+        builder.side_to_move = self.side_to_move();
+        builder.en_passant = self.en_passant();
+        builder.build().ok()
+    }
+}
+
+impl ProbePosition for Board {
+    fn pieces(&self, piece: Piece) -> BitBoard {
+        self.pieces(piece)
+    }
+
+    fn colors(&self, color: Color) -> BitBoard {
+        self.colors(color)
+    }
+
+    fn side_to_move(&self) -> Color {
+        self.side_to_move()
+    }
+
+    fn en_passant(&self) -> Option<Square> {
+        self.en_passant()
+            .map(|f| Square::new(f, Rank::Sixth.relative_to(self.side_to_move())))
+    }
+
+    fn has_castle_rights(&self) -> bool {
+        Color::ALL.into_iter().any(|c| {
+            let rights = self.castle_rights(c);
+            rights.short.is_some() || rights.long.is_some()
+        })
+    }
+
+    fn to_board(&self) -> Option<Board> {
+        Some(self.clone())
+    }
+}
+
+/// Whether a position is looked up in its table with the colors swapped and the board mirrored
+/// vertically.
+///
+/// Tables are only stored with the stronger side as white, so positions where black has the
+/// stronger material are flipped. Symmetric tables such as KQvKQ only store positions with white
+/// to move, so for symmetric materials, positions with black to move are flipped.
+pub fn is_color_flipped(position: &impl ProbePosition) -> bool {
+    Material::of(position).color_flip(position.side_to_move())
+}
+
+/// A position viewed from the other side: colors are swapped and the board is mirrored
+/// vertically. This has the same WDL value as the original position.
+#[cfg(any(test, feature = "check-color-flip"))]
+pub(crate) struct ColorFlipped<'a, P>(pub &'a P);
+
+#[cfg(any(test, feature = "check-color-flip"))]
+impl<P: ProbePosition> ProbePosition for ColorFlipped<'_, P> {
+    fn pieces(&self, piece: Piece) -> BitBoard {
+        self.0.pieces(piece).flip_ranks()
+    }
+
+    fn colors(&self, color: Color) -> BitBoard {
+        self.0.colors(!color).flip_ranks()
+    }
+
+    fn side_to_move(&self) -> Color {
+        !self.0.side_to_move()
+    }
+
+    fn en_passant(&self) -> Option<Square> {
+        self.0.en_passant().map(Square::flip_rank)
+    }
+
+    fn has_castle_rights(&self) -> bool {
+        self.0.has_castle_rights()
+    }
+}
+
+/// Whether the side to move has any pseudo-legal captures, not including en passant.
+pub(crate) fn has_pseudo_legal_capture(position: &impl ProbePosition) -> bool {
+    let us = position.colors(position.side_to_move());
+    let them = position.colors(!position.side_to_move());
+    let occupied = us | them;
+
+    let ours = |p| position.pieces(p) & us;
+    let diagonal = ours(Piece::Bishop) | ours(Piece::Queen);
+    let orthogonal = ours(Piece::Rook) | ours(Piece::Queen);
+
+    ours(Piece::Pawn)
+        .into_iter()
+        .any(|sq| !(get_pawn_attacks(sq, position.side_to_move()) & them).is_empty())
+        || ours(Piece::Knight)
+            .into_iter()
+            .any(|sq| !(get_knight_moves(sq) & them).is_empty())
+        || diagonal
+            .into_iter()
+            .any(|sq| !(get_bishop_moves(sq, occupied) & them).is_empty())
+        || orthogonal
+            .into_iter()
+            .any(|sq| !(get_rook_moves(sq, occupied) & them).is_empty())
+        || ours(Piece::King)
+            .into_iter()
+            .any(|sq| !(get_king_moves(sq) & them).is_empty())
+}
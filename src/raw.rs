@@ -0,0 +1,122 @@
+//! Direct access to the values stored in a single WDL table, for tools that work with table
+//! indices rather than positions, such as table generators, checkers and converters.
+
+use cozy_chess::Color;
+
+use crate::table::{self, WdlTable};
+use crate::{Data, Material, ProbePosition, SyzygyError, TableSource, Wdl};
+
+/// The location of a value in a table.
+///
+/// Tables are split into subtables by side to move and, for tables with pawns, by the file of
+/// the leading pawn, which is mirrored to files 0 to 3 (a to d). Tables without pawns only have
+/// file 0. Colors are those of the table, where white is the side with the material written
+/// first, so a position of `KvKR` with white to move is stored under black to move.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TableIndex {
+    pub side_to_move: Color,
+    pub file: usize,
+    pub index: u64,
+}
+
+/// A single WDL table, opened without a [`Tablebase`][crate::Tablebase].
+///
+/// Values are decoded without probing captures or en passant, so they are only correct for
+/// positions where the best move is not a capture, and do not take the halfmove clock into
+/// account. [`Tablebase::probe_wdl`][crate::Tablebase::probe_wdl] handles these.
+pub struct RawWdlTable {
+    table: WdlTable,
+    material: Material,
+}
+
+impl RawWdlTable {
+    /// Open the WDL table for the material, e.g. `KRvK`, from its data.
+    pub fn open(material: &str, data: impl TableSource) -> Result<Self, SyzygyError> {
+        let material = material.parse::<Material>()?.canonical();
+        if data.bytes().len() < table::min_size(material) {
+            return Err(SyzygyError::FileTooSmall(None));
+        }
+        let table = WdlTable::load(Data::Source(Box::new(data)), material)?;
+        Ok(RawWdlTable { table, material })
+    }
+
+    /// The material of the table, e.g. `KRvK`.
+    pub fn material(&self) -> String {
+        self.material.to_string()
+    }
+
+    /// The number of values in a subtable, or `None` if the table doesn't store the subtable.
+    pub fn len(&self, side_to_move: Color, file: usize) -> Option<u64> {
+        Some(self.table.subtable(side_to_move, file)?.values() as u64)
+    }
+
+    /// Find where the value of a position is stored. Returns `None` if the position does not have
+    /// the material of the table or its subtable is not stored.
+    pub fn index(&self, position: &impl ProbePosition) -> Option<TableIndex> {
+        let material = Material::of(position);
+        let color_flip = material.color_flip(position.side_to_move());
+        if material.canonical() != self.material {
+            return None;
+        }
+        let (side_to_move, file, index) = self.table.index_of(position, color_flip)?;
+        Some(TableIndex {
+            side_to_move,
+            file,
+            index,
+        })
+    }
+
+    /// Decode the value at an index. Returns `None` if the index is not in the table or the data
+    /// is corrupt.
+    pub fn value(&self, index: TableIndex) -> Option<Wdl> {
+        self.table
+            .value(index.side_to_move, index.file, index.index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cozy_chess::{Piece, Square};
+
+    use super::*;
+    use crate::tablebase::tests::{krvk_placement, winning_krvk, Placement};
+
+    #[test]
+    fn values_are_read_by_index() {
+        let data = winning_krvk();
+        let table = RawWdlTable::open("KvKR", data).unwrap();
+        assert_eq!(table.material(), "KRvK");
+
+        let len = table.len(Color::White, 0).unwrap();
+        assert!(table.len(Color::Black, 0).is_some());
+        assert_eq!(table.len(Color::White, 1), None);
+
+        let at = |side_to_move, index| TableIndex {
+            side_to_move,
+            file: 0,
+            index,
+        };
+        assert_eq!(table.value(at(Color::White, len)), None);
+        assert_eq!(table.value(at(Color::White, 1023)), Some(Wdl::Win));
+        assert_eq!(table.value(at(Color::White, 1024)), Some(Wdl::CursedWin));
+        assert_eq!(table.value(at(Color::Black, 0)), Some(Wdl::Loss));
+
+        let position = krvk_placement(Square::E1, Square::A7, Square::E8, Color::White);
+        let index = table.index(&position).unwrap();
+        assert_eq!(index.side_to_move, Color::White);
+        assert_eq!(table.value(index), Some(Wdl::Win));
+
+        // With the colors swapped, the rook is on the side of the table's white pieces.
+        let position = Placement {
+            pieces: vec![
+                (Piece::King, Color::Black, Square::A8),
+                (Piece::Rook, Color::Black, Square::B2),
+                (Piece::King, Color::White, Square::E1),
+            ],
+            side_to_move: Color::Black,
+        };
+        let index = table.index(&position).unwrap();
+        assert_eq!(index.side_to_move, Color::White);
+        assert_eq!(table.value(index), Some(Wdl::CursedWin));
+    }
+}
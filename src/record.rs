@@ -0,0 +1,223 @@
+use cozy_chess::{Board, BoardBuilder};
+
+use crate::{DataStream, ProbeError, Tablebase, Wdl};
+
+/// A compact, fixed-size record of a probe result.
+///
+/// Records encode to [`ProbeRecord::SIZE`] bytes in a stable little-endian layout:
+/// ```text
+/// hash: u64
+/// wdl: u8     0 = loss, 1 = blessed loss, 2 = draw, 3 = cursed win, 4 = win
+/// flags: u8   bit 0 = best move is a capture, bit 1 = dtz is present
+/// dtz: i16
+/// ```
+/// The hash is whatever the producer used to identify the position, usually
+/// [`Board::hash`][cozy_chess::Board::hash]. Records produced with different hash functions must
+/// not be mixed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProbeRecord {
+    pub hash: u64,
+    pub wdl: Wdl,
+    /// Whether the best move is a capture or en passant capture.
+    pub capture: bool,
+    pub dtz: Option<i16>,
+}
+
+const FLAG_CAPTURE: u8 = 1;
+const FLAG_DTZ: u8 = 2;
+
+impl ProbeRecord {
+    /// The size of an encoded record in bytes.
+    pub const SIZE: usize = 12;
+
+    /// Creates a record for a WDL probe result of the specified position.
+    pub fn new(position: &Board, (wdl, capture): (Wdl, bool)) -> Self {
+        ProbeRecord {
+            hash: position.hash(),
+            wdl,
+            capture,
+            dtz: None,
+        }
+    }
+
+    /// Probe the WDL value of a position for labeling training data.
+    ///
+    /// The first record is the result of [`Tablebase::probe_wdl`], which takes en passant
+    /// captures into account. If the position has an en passant square and the result without it
+    /// is different, the second record is the result with the en passant square removed, keyed by
+    /// [`Board::hash_without_ep`]. Consumers which need a conservative label can then use the
+    /// label that doesn't depend on the en passant capture.
+    pub fn probe_labels(
+        tb: &Tablebase,
+        position: &Board,
+    ) -> Result<(Self, Option<Self>), ProbeError> {
+        let record = ProbeRecord::new(position, tb.probe_wdl(position)?);
+        if position.en_passant().is_none() {
+            return Ok((record, None));
+        }
+
+        let mut builder = BoardBuilder::from_board(position);
+        builder.en_passant = None;
+        let without_ep = builder.build().map_err(|_| ProbeError::InvalidPosition)?;
+        let other = ProbeRecord::new(&without_ep, tb.probe_wdl(&without_ep)?);
+        let differs = (other.wdl, other.capture) != (record.wdl, record.capture);
+        Ok((record, differs.then_some(other)))
+    }
+
+    pub fn encode(&self) -> [u8; Self::SIZE] {
+        let mut flags = 0;
+        if self.capture {
+            flags |= FLAG_CAPTURE;
+        }
+        if self.dtz.is_some() {
+            flags |= FLAG_DTZ;
+        }
+
+        let mut result = [0; Self::SIZE];
+        result[0..8].copy_from_slice(&self.hash.to_le_bytes());
+        result[8] = match self.wdl {
+            Wdl::Loss => 0,
+            Wdl::BlessedLoss => 1,
+            Wdl::Draw => 2,
+            Wdl::CursedWin => 3,
+            Wdl::Win => 4,
+        };
+        result[9] = flags;
+        result[10..12].copy_from_slice(&self.dtz.unwrap_or(0).to_le_bytes());
+        result
+    }
+
+    /// Decodes a record, returning `None` if the bytes are not exactly one valid record.
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != Self::SIZE {
+            return None;
+        }
+        let mut stream = DataStream::new(bytes);
+        let hash = stream.read_u64().ok()?;
+        let wdl = match stream.read_u8().ok()? {
+            0 => Wdl::Loss,
+            1 => Wdl::BlessedLoss,
+            2 => Wdl::Draw,
+            3 => Wdl::CursedWin,
+            4 => Wdl::Win,
+            _ => return None,
+        };
+        let flags = stream.read_u8().ok()?;
+        if flags & !(FLAG_CAPTURE | FLAG_DTZ) != 0 {
+            return None;
+        }
+        let dtz = i16::from_le_bytes(stream.read().ok()?);
+
+        Some(ProbeRecord {
+            hash,
+            wdl,
+            capture: flags & FLAG_CAPTURE != 0,
+            dtz: (flags & FLAG_DTZ != 0).then_some(dtz),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cozy_chess::Piece;
+
+    use super::*;
+    use crate::Fallback;
+
+    /// The side with more pawns wins.
+    struct MorePawnsWin;
+
+    impl Fallback for MorePawnsWin {
+        fn wdl(&self, position: &Board) -> Option<Wdl> {
+            let pawns = |color| position.colored_pieces(color, Piece::Pawn).len();
+            let us = pawns(position.side_to_move());
+            let them = pawns(!position.side_to_move());
+            Some(match us.cmp(&them) {
+                std::cmp::Ordering::Less => Wdl::Loss,
+                std::cmp::Ordering::Equal => Wdl::Draw,
+                std::cmp::Ordering::Greater => Wdl::Win,
+            })
+        }
+    }
+
+    const WDLS: [Wdl; 5] = [
+        Wdl::Loss,
+        Wdl::BlessedLoss,
+        Wdl::Draw,
+        Wdl::CursedWin,
+        Wdl::Win,
+    ];
+
+    #[test]
+    fn records_round_trip() {
+        for (i, wdl) in WDLS.into_iter().enumerate() {
+            for capture in [false, true] {
+                for dtz in [None, Some(0), Some(-1), Some(i16::MAX), Some(i16::MIN)] {
+                    let record = ProbeRecord {
+                        hash: 0x0123_4567_89AB_CDEF,
+                        wdl,
+                        capture,
+                        dtz,
+                    };
+                    let bytes = record.encode();
+                    assert_eq!(bytes[8], i as u8);
+                    assert_eq!(
+                        bytes[9],
+                        capture as u8 * FLAG_CAPTURE + dtz.is_some() as u8 * FLAG_DTZ
+                    );
+                    assert_eq!(ProbeRecord::decode(&bytes), Some(record));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_records_are_rejected() {
+        let bytes = ProbeRecord {
+            hash: 1,
+            wdl: Wdl::Win,
+            capture: true,
+            dtz: Some(3),
+        }
+        .encode();
+
+        let mut bad_wdl = bytes;
+        bad_wdl[8] = 5;
+        assert_eq!(ProbeRecord::decode(&bad_wdl), None);
+        let mut bad_flags = bytes;
+        bad_flags[9] = 4;
+        assert_eq!(ProbeRecord::decode(&bad_flags), None);
+
+        assert_eq!(ProbeRecord::decode(&bytes[..ProbeRecord::SIZE - 1]), None);
+        assert_eq!(ProbeRecord::decode(&[]), None);
+        assert_eq!(ProbeRecord::decode(&[bytes, bytes].concat()), None);
+    }
+
+    #[test]
+    fn labels_without_en_passant_are_added_when_they_differ() {
+        let mut tb = Tablebase::new();
+        tb.set_fallback(Some(Box::new(MorePawnsWin)));
+
+        // Capturing en passant wins a pawn, so the label differs without it.
+        let position: Board = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2".parse().unwrap();
+        let (record, other) = ProbeRecord::probe_labels(&tb, &position).unwrap();
+        assert_eq!((record.hash, record.wdl), (position.hash(), Wdl::Win));
+        let other = other.unwrap();
+        assert_eq!(
+            (other.hash, other.wdl),
+            (position.hash_without_ep(), Wdl::Draw)
+        );
+
+        // Two pawns down, the en passant capture still loses, so the label is the same.
+        let position: Board = "4k3/pp6/8/3pP3/8/8/8/4K3 w - d6 0 2".parse().unwrap();
+        let (record, other) = ProbeRecord::probe_labels(&tb, &position).unwrap();
+        assert_eq!((record.wdl, record.capture), (Wdl::Loss, false));
+        assert_eq!(other, None);
+
+        // Without an en passant square there is only one label.
+        let position: Board = "4k3/8/8/3pP3/8/8/8/4K3 w - - 0 2".parse().unwrap();
+        let (record, other) = ProbeRecord::probe_labels(&tb, &position).unwrap();
+        assert_eq!(record.wdl, Wdl::Draw);
+        assert_eq!(other, None);
+    }
+}
@@ -0,0 +1,195 @@
+//! Checking probe results against a corpus of positions with known values, for embedding
+//! tablebase checks in the test suites of downstream projects.
+
+use cozy_chess::Board;
+
+use crate::{test_positions, Dtz, ProbeError, Tablebase, Wdl};
+
+/// The known result of a position in a regression corpus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Expected {
+    /// The WDL value for the side to move.
+    pub wdl: Wdl,
+    /// Whether the best move is a capture or en passant capture, or `None` to not check this.
+    pub capture: Option<bool>,
+    /// The DTZ value, or `None` to not check this. Checking it needs the DTZ tables.
+    pub dtz: Option<Dtz>,
+}
+
+impl Expected {
+    /// Only check the WDL value.
+    pub fn wdl(wdl: Wdl) -> Self {
+        Expected {
+            wdl,
+            capture: None,
+            dtz: None,
+        }
+    }
+
+    fn matches(&self, outcome: &Outcome) -> bool {
+        self.wdl == outcome.wdl
+            && self.capture.is_none_or(|c| c == outcome.capture)
+            && self.dtz.is_none_or(|dtz| Some(dtz) == outcome.dtz)
+    }
+}
+
+/// The result of probing a position in a regression corpus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    pub wdl: Wdl,
+    /// Whether the best move is a capture or en passant capture.
+    pub capture: bool,
+    /// The DTZ value, if the expected result includes one.
+    pub dtz: Option<Dtz>,
+}
+
+/// A position whose probe result did not match the expected result.
+#[derive(Clone, Debug)]
+pub struct Failure {
+    pub position: Board,
+    pub expected: Expected,
+    pub actual: Result<Outcome, ProbeError>,
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: expected {:?}", self.position, self.expected.wdl)?;
+        if let Some(capture) = self.expected.capture {
+            write!(f, ", capture {capture}")?;
+        }
+        if let Some(dtz) = self.expected.dtz {
+            write!(f, ", dtz {}", dtz.0)?;
+        }
+        match &self.actual {
+            Ok(outcome) => {
+                write!(f, "; got {:?}, capture {}", outcome.wdl, outcome.capture)?;
+                match outcome.dtz {
+                    Some(dtz) => write!(f, ", dtz {}", dtz.0),
+                    None => Ok(()),
+                }
+            }
+            Err(e) => write!(f, "; {e}"),
+        }
+    }
+}
+
+/// The results of [`run_regression`].
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    /// The number of positions whose probe result matched the expected result.
+    pub passed: usize,
+    /// The positions whose probe result did not match, in corpus order.
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    /// Whether every position matched its expected result.
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Probe every position of `corpus` and compare the results with the expected results.
+///
+/// Positions which can't be probed, e.g. because a table is missing, count as failures. To only
+/// check the positions a tablebase can cover, filter the corpus first.
+pub fn run_regression(
+    tb: &Tablebase,
+    corpus: impl IntoIterator<Item = (Board, Expected)>,
+) -> Report {
+    let mut report = Report::default();
+    for (position, expected) in corpus {
+        let actual = probe(tb, &position, expected.dtz.is_some());
+        match &actual {
+            Ok(outcome) if expected.matches(outcome) => report.passed += 1,
+            _ => report.failures.push(Failure {
+                position,
+                expected,
+                actual,
+            }),
+        }
+    }
+    report
+}
+
+/// The positions of [`test_positions`] as a regression corpus.
+pub fn test_position_corpus() -> impl Iterator<Item = (Board, Expected)> {
+    test_positions().iter().map(|pos| {
+        let expected = Expected {
+            wdl: pos.wdl,
+            capture: Some(pos.capture),
+            dtz: None,
+        };
+        (pos.fen.parse().unwrap(), expected)
+    })
+}
+
+fn probe(tb: &Tablebase, position: &Board, dtz: bool) -> Result<Outcome, ProbeError> {
+    let (wdl, capture) = tb.probe_wdl(position)?;
+    let dtz = match dtz {
+        true => Some(tb.probe_dtz(position)?.0),
+        false => None,
+    };
+    Ok(Outcome { wdl, capture, dtz })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tablebase::tests::{winning_krvk_dtz, winning_tablebase};
+
+    #[test]
+    fn mismatches_are_reported() {
+        let mut tb = winning_tablebase();
+        tb.load_bytes_owned("KRvK", winning_krvk_dtz().into())
+            .unwrap();
+        let win: Board = "4k3/R7/8/8/8/8/8/4K3 w - - 0 1".parse().unwrap();
+        let cursed_win: Board = "4k3/1R6/8/8/8/8/8/K7 w - - 0 1".parse().unwrap();
+        let missing: Board = "4k3/Q7/8/8/8/8/8/4K3 w - - 0 1".parse().unwrap();
+
+        let report = run_regression(
+            &tb,
+            [
+                (
+                    win.clone(),
+                    Expected {
+                        wdl: Wdl::Win,
+                        capture: Some(false),
+                        dtz: Some(Dtz(15)),
+                    },
+                ),
+                (cursed_win.clone(), Expected::wdl(Wdl::CursedWin)),
+            ],
+        );
+        assert_eq!(report.passed, 2);
+        assert!(report.is_success());
+
+        let report = run_regression(
+            &tb,
+            [
+                (win, Expected::wdl(Wdl::Win)),
+                (cursed_win.clone(), Expected::wdl(Wdl::Win)),
+                (missing.clone(), Expected::wdl(Wdl::Win)),
+            ],
+        );
+        assert_eq!(report.passed, 1);
+        assert!(!report.is_success());
+        assert_eq!(report.failures.len(), 2);
+        let failure = &report.failures[0];
+        assert_eq!(failure.position.hash(), cursed_win.hash());
+        assert_eq!(
+            failure.actual,
+            Ok(Outcome {
+                wdl: Wdl::CursedWin,
+                capture: false,
+                dtz: None,
+            })
+        );
+        let failure = &report.failures[1];
+        assert_eq!(failure.position.hash(), missing.hash());
+        assert_eq!(
+            failure.actual,
+            Err(ProbeError::MissingTable("KQvK".to_string()))
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use std::cmp::Reverse;
+
+use cozy_chess::Move;
+
+use crate::generate::SplitMix64;
+use crate::{Dtz, Wdl};
+
+/// The tablebase evaluation of a legal move in the root position.
+///
+/// See [`Tablebase::probe_root`][crate::Tablebase::probe_root].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RootMove {
+    pub mv: Move,
+    /// The outcome after playing the move, from the point of view of the side to move in the
+    /// root position. When the DTZ is known, this accounts for the halfmove clock of the root
+    /// position.
+    pub wdl: Wdl,
+    /// The DTZ after playing the move, counting the move itself, from the point of view of the
+    /// side to move in the root position. This is `None` if it is unknown.
+    pub dtz: Option<Dtz>,
+    /// Whether the DTZ table needed for this move could not be probed, so the DTZ comes from a
+    /// short search with the WDL tables, or is unknown if that search didn't reach a move which
+    /// resets the 50 move counter.
+    pub degraded: bool,
+    /// Whether the move is a capture or pawn move, which resets the 50 move counter.
+    pub zeroing: bool,
+}
+
+/// How [`Tablebase::probe_root_with_tie_break`][crate::Tablebase::probe_root_with_tie_break]
+/// orders moves with the same outcome and DTZ.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TieBreak {
+    /// Keep the order the moves are generated in. This is deterministic, but may change between
+    /// versions of cozy-chess.
+    #[default]
+    MoveOrder,
+    /// Order moves by their UCI notation, which is reproducible across versions.
+    Lexicographic,
+    /// Shuffle the moves with a pseudorandom generator seeded with the value, for variety in play.
+    /// The same seed always gives the same order.
+    Random(u64),
+    /// Put captures and pawn moves first, keeping the generation order otherwise.
+    PreferZeroing,
+}
+
+/// Adjust a WDL value for a halfmove clock, where `dtz` is counted from the position the clock
+/// belongs to.
+///
+/// Tables assume the halfmove clock is zero, so a win is only still a win if the counter is reset
+/// before the clock reaches 100.
+pub(crate) fn with_halfmove_clock(wdl: Wdl, dtz: Dtz, halfmove_clock: u8) -> Wdl {
+    let plies = dtz.0.unsigned_abs() + halfmove_clock as u32;
+    match wdl {
+        Wdl::Win if plies > 100 => Wdl::CursedWin,
+        Wdl::Loss if plies > 100 => Wdl::BlessedLoss,
+        wdl => wdl,
+    }
+}
+
+/// Sort root moves best-first.
+///
+/// Better outcomes come first. Among moves with the same outcome, the side to move should zero as
+/// soon as possible when winning, and as late as possible when losing, which in both cases is the
+/// lowest DTZ. The DTZ is only unknown when a search didn't reach a move which resets the
+/// counter, so such moves are taken to zero later than any other, coming last among wins and first
+/// among losses. Ties are broken with `tie_break`.
+pub(crate) fn sort(moves: &mut [RootMove], tie_break: TieBreak) {
+    match tie_break {
+        TieBreak::MoveOrder => {}
+        TieBreak::Lexicographic => moves.sort_by_cached_key(|m| m.mv.to_string()),
+        TieBreak::Random(seed) => shuffle(moves, seed),
+        TieBreak::PreferZeroing => moves.sort_by_key(|m| !m.zeroing),
+    }
+    // The sort is stable, so tied moves keep the order from above.
+    moves.sort_by_key(|m| {
+        let unknown = match m.wdl {
+            Wdl::Loss | Wdl::BlessedLoss => i32::MIN,
+            _ => i32::MAX,
+        };
+        (Reverse(m.wdl), m.dtz.map_or(unknown, |dtz| dtz.0))
+    });
+}
+
+/// A Fisher-Yates shuffle driven by SplitMix64.
+fn shuffle(moves: &mut [RootMove], seed: u64) {
+    let mut rng = SplitMix64(seed);
+    for i in (1..moves.len()).rev() {
+        moves.swap(i, rng.below(i + 1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cozy_chess::Square;
+
+    use super::*;
+
+    #[test]
+    fn halfmove_clock_curses_slow_wins() {
+        assert_eq!(with_halfmove_clock(Wdl::Win, Dtz(10), 90), Wdl::Win);
+        assert_eq!(with_halfmove_clock(Wdl::Win, Dtz(11), 90), Wdl::CursedWin);
+        assert_eq!(with_halfmove_clock(Wdl::Loss, Dtz(-10), 90), Wdl::Loss);
+        assert_eq!(
+            with_halfmove_clock(Wdl::Loss, Dtz(-11), 90),
+            Wdl::BlessedLoss
+        );
+        assert_eq!(
+            with_halfmove_clock(Wdl::CursedWin, Dtz(105), 0),
+            Wdl::CursedWin
+        );
+        assert_eq!(with_halfmove_clock(Wdl::Draw, Dtz(0), 99), Wdl::Draw);
+    }
+
+    #[test]
+    fn moves_are_sorted_best_first() {
+        let root_move = |wdl, dtz: Option<i32>| RootMove {
+            mv: Move {
+                from: Square::A1,
+                to: Square::A2,
+                promotion: None,
+            },
+            wdl,
+            dtz: dtz.map(Dtz),
+            degraded: dtz.is_none(),
+            zeroing: false,
+        };
+        let mut moves = vec![
+            root_move(Wdl::Loss, Some(-3)),
+            root_move(Wdl::Draw, Some(0)),
+            root_move(Wdl::Win, None),
+            root_move(Wdl::Loss, Some(-9)),
+            root_move(Wdl::Win, Some(7)),
+            root_move(Wdl::CursedWin, Some(103)),
+            root_move(Wdl::Win, Some(3)),
+        ];
+        sort(&mut moves, TieBreak::MoveOrder);
+        let order: Vec<_> = moves.iter().map(|m| (m.wdl, m.dtz.map(|d| d.0))).collect();
+        assert_eq!(
+            order,
+            [
+                (Wdl::Win, Some(3)),
+                (Wdl::Win, Some(7)),
+                (Wdl::Win, None),
+                (Wdl::CursedWin, Some(103)),
+                (Wdl::Draw, Some(0)),
+                (Wdl::Loss, Some(-9)),
+                (Wdl::Loss, Some(-3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_dtz_resists_longest_when_losing() {
+        let root_move = |from, dtz: Option<i32>| RootMove {
+            mv: Move {
+                from,
+                to: Square::H8,
+                promotion: None,
+            },
+            wdl: Wdl::Loss,
+            dtz: dtz.map(Dtz),
+            degraded: true,
+            zeroing: false,
+        };
+        let mut moves = vec![root_move(Square::A1, Some(-2)), root_move(Square::B1, None)];
+        sort(&mut moves, TieBreak::MoveOrder);
+        assert_eq!(moves[0].mv.from, Square::B1);
+        assert_eq!(moves[0].dtz, None);
+    }
+
+    #[test]
+    fn ties_are_broken_by_policy() {
+        let moves: Vec<_> = (0..8)
+            .map(|i| RootMove {
+                mv: Move {
+                    from: Square::index(i),
+                    to: Square::index(i + 8),
+                    promotion: None,
+                },
+                wdl: Wdl::Win,
+                dtz: Some(Dtz(5)),
+                degraded: false,
+                zeroing: i % 3 == 1,
+            })
+            .collect();
+        let sorted = |tie_break| {
+            let mut moves = moves.clone();
+            sort(&mut moves, tie_break);
+            moves.iter().map(|m| m.mv.from as usize).collect::<Vec<_>>()
+        };
+
+        assert_eq!(sorted(TieBreak::MoveOrder), [0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(sorted(TieBreak::PreferZeroing), [1, 4, 7, 0, 2, 3, 5, 6]);
+        assert_eq!(sorted(TieBreak::Random(7)), sorted(TieBreak::Random(7)));
+        assert_ne!(sorted(TieBreak::Random(7)), sorted(TieBreak::Random(8)));
+    }
+}
@@ -0,0 +1,143 @@
+//! WDL statistics over samples of positions, such as game collections or random positions of a
+//! material.
+//!
+//! These are statistics about the positions, not about probing; see [`Tablebase::stats`] for the
+//! latter.
+
+use std::borrow::Borrow;
+
+use cozy_chess::Board;
+
+use crate::generate::random_positions;
+use crate::{ProbePosition, SyzygyError, Tablebase, Wdl};
+
+/// WDL statistics over a sample of positions. See [`sample_wdl`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WdlStats {
+    /// The number of positions within tablebase range that were sampled.
+    pub sampled: u64,
+    /// The number of sampled positions that were found in the tablebase.
+    pub probed: u64,
+    /// The number of probed positions with each WDL value, from the point of view of the side to
+    /// move. Use [`WdlStats::count`] to access this.
+    pub counts: [u64; 5],
+}
+
+impl WdlStats {
+    /// The number of probed positions with the specified WDL value for the side to move.
+    pub fn count(&self, wdl: Wdl) -> u64 {
+        self.counts[wdl as usize]
+    }
+
+    /// The fraction of sampled positions that were found in the tablebase.
+    pub fn coverage(&self) -> f64 {
+        ratio(self.probed, self.sampled)
+    }
+
+    /// The fraction of probed positions in which one side is winning.
+    pub fn decisive_rate(&self) -> f64 {
+        ratio(self.count(Wdl::Win) + self.count(Wdl::Loss), self.probed)
+    }
+
+    /// The fraction of probed positions in which one side would be winning, but the game is drawn
+    /// by the 50 move rule.
+    pub fn cursed_rate(&self) -> f64 {
+        ratio(
+            self.count(Wdl::CursedWin) + self.count(Wdl::BlessedLoss),
+            self.probed,
+        )
+    }
+}
+
+fn ratio(n: u64, d: u64) -> f64 {
+    match d {
+        0 => 0.0,
+        _ => n as f64 / d as f64,
+    }
+}
+
+/// Probe every `every`-th position within tablebase range and collect statistics on the results.
+///
+/// A position is within range if it has at most [`Tablebase::max_pieces`] pieces, or any number
+/// of pieces if the tablebase has a [fallback][Tablebase::set_fallback], and no castling rights. Positions outside of the range are skipped without counting towards `every`, so e.g.
+/// the positions of a game collection can be passed in directly.
+///
+/// # Panics
+///
+/// Panics if `every` is zero.
+pub fn sample_wdl<B: Borrow<Board>>(
+    tb: &Tablebase,
+    positions: impl IntoIterator<Item = B>,
+    every: usize,
+) -> WdlStats {
+    assert!(every > 0, "every must be nonzero");
+
+    let mut stats = WdlStats::default();
+    let in_range = positions.into_iter().filter(|board| {
+        let board = board.borrow();
+        board.occupied().len() <= tb.max_probed_pieces() && !board.has_castle_rights()
+    });
+    for board in in_range.step_by(every) {
+        stats.sampled += 1;
+        if let Ok((wdl, _)) = tb.probe_wdl(board.borrow()) {
+            stats.probed += 1;
+            stats.counts[wdl as usize] += 1;
+        }
+    }
+    stats
+}
+
+/// Probe `count` random positions with the material, e.g. `KRPvKN`, and collect statistics on the
+/// results. See [`random_positions`].
+pub fn sample_material(
+    tb: &Tablebase,
+    material: &str,
+    count: usize,
+    seed: u64,
+) -> Result<WdlStats, SyzygyError> {
+    Ok(sample_wdl(
+        tb,
+        random_positions(material, seed)?.take(count),
+        1,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tablebase::tests::winning_tablebase;
+    use crate::Fallback;
+
+    struct AlwaysDraw;
+
+    impl Fallback for AlwaysDraw {
+        fn wdl(&self, _: &Board) -> Option<Wdl> {
+            Some(Wdl::Draw)
+        }
+    }
+
+    #[test]
+    fn positions_covered_by_the_fallback_are_sampled() {
+        let mut tb = winning_tablebase();
+        let positions: Vec<Board> = [
+            "4k3/R7/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/1R6/8/8/8/8/8/K7 b - - 0 1",
+            "4k3/8/8/8/8/8/P7/2B1K3 w - - 0 1",
+            "r3k3/8/8/8/8/8/8/4K2R w Kq - 0 1",
+        ]
+        .iter()
+        .map(|fen| fen.parse().unwrap())
+        .collect();
+
+        // The four piece position is out of range of the loaded tables.
+        let stats = sample_wdl(&tb, &positions, 1);
+        assert_eq!((stats.sampled, stats.probed), (2, 2));
+        assert_eq!(stats.count(Wdl::Win), 1);
+        assert_eq!(stats.count(Wdl::Loss), 1);
+
+        tb.set_fallback(Some(Box::new(AlwaysDraw)));
+        let stats = sample_wdl(&tb, &positions, 1);
+        assert_eq!((stats.sampled, stats.probed), (3, 3));
+        assert_eq!(stats.count(Wdl::Draw), 1);
+    }
+}
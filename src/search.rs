@@ -0,0 +1,242 @@
+//! Helpers for using the tablebase inside of a search.
+
+use cozy_chess::{Board, GameStatus, Move};
+
+use crate::{Dtz, ProbeError, RootMove, Tablebase, Wdl};
+
+/// Configuration for [`should_probe`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProbeConfig {
+    /// The largest number of pieces for which positions are probed. This is usually the number of
+    /// pieces in the largest loaded table.
+    pub max_pieces: u32,
+    /// The minimum remaining depth at which positions with exactly `max_pieces` pieces are
+    /// probed. Positions with fewer pieces are always probed, since those tables are smaller and
+    /// more likely to be cached.
+    pub probe_depth: i32,
+}
+
+impl ProbeConfig {
+    /// Creates a configuration which probes every position covered by the tablebase.
+    pub fn new(tb: &Tablebase) -> Self {
+        ProbeConfig {
+            max_pieces: tb.max_pieces(),
+            probe_depth: 0,
+        }
+    }
+}
+
+/// Decide whether to probe the WDL tables at an interior node of a search.
+///
+/// This implements the usual heuristics:
+/// - Positions with more than `cfg.max_pieces` pieces are not probed.
+/// - Positions with exactly `cfg.max_pieces` pieces are only probed at depths of at least
+///   `cfg.probe_depth`.
+/// - Positions are only probed when the halfmove clock is zero, i.e. directly after a capture or
+///   pawn move. The WDL result is only exact for such positions, and since reversible moves
+///   cannot leave the table, probing after every zeroing move is enough to catch every
+///   transition into the tablebase.
+///
+/// The root position should not be gated through this function, since the move chosen there must
+/// also account for the 50 move rule.
+pub fn should_probe(depth: i32, halfmove_clock: u8, piece_count: u32, cfg: &ProbeConfig) -> bool {
+    halfmove_clock == 0
+        && piece_count <= cfg.max_pieces
+        && (piece_count < cfg.max_pieces || depth >= cfg.probe_depth)
+}
+
+/// A mapping from tablebase results to the score type of an engine.
+///
+/// This lets engines with unusual score encodings, such as win probabilities, convert results
+/// directly rather than through an intermediate centipawn score. Any `Fn(Wdl, u32) -> S` is a
+/// mapping which ignores the DTZ.
+pub trait TbScoreMapping {
+    type Score;
+
+    /// The score of a position with the specified WDL value, from the point of view of the side
+    /// to move. `ply` is the distance from the root, for engines which prefer nearer wins.
+    fn wdl(&self, wdl: Wdl, ply: u32) -> Self::Score;
+
+    /// The score of a position whose DTZ is also known. By default, the DTZ is ignored.
+    fn dtz(&self, wdl: Wdl, dtz: Dtz, ply: u32) -> Self::Score {
+        let _ = dtz;
+        self.wdl(wdl, ply)
+    }
+}
+
+impl<S, F: Fn(Wdl, u32) -> S> TbScoreMapping for F {
+    type Score = S;
+
+    fn wdl(&self, wdl: Wdl, ply: u32) -> S {
+        self(wdl, ply)
+    }
+}
+
+/// Probe the WDL tables and convert the result with `mapping`.
+pub fn probe_score<M: TbScoreMapping>(
+    tb: &Tablebase,
+    position: &Board,
+    ply: u32,
+    mapping: &M,
+) -> Result<M::Score, ProbeError> {
+    let (wdl, _) = tb.probe_wdl(position)?;
+    Ok(mapping.wdl(wdl, ply))
+}
+
+/// Probe the DTZ tables and convert the result with `mapping`.
+pub fn probe_score_dtz<M: TbScoreMapping>(
+    tb: &Tablebase,
+    position: &Board,
+    ply: u32,
+    mapping: &M,
+) -> Result<M::Score, ProbeError> {
+    let (dtz, wdl) = tb.probe_dtz(position)?;
+    Ok(mapping.dtz(wdl, dtz, ply))
+}
+
+/// The moves to search at the root, found by [`filter_root_moves`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RootFilter {
+    /// The legal moves which keep the best outcome, best first.
+    pub moves: Vec<Move>,
+    /// The outcome of the root position with best play, accounting for its halfmove clock.
+    pub wdl: Wdl,
+    /// The number of successful table probes, to add to the `tbhits` reported by a UCI engine.
+    /// This is a WDL probe for each move which doesn't checkmate, and a DTZ probe for each of
+    /// those which doesn't reset the 50 move counter and didn't fall back to a search.
+    pub tb_hits: u64,
+}
+
+/// Find the root moves which keep the best outcome, so that the search only has to choose among
+/// them. This uses [`Tablebase::probe_root`], so it takes the halfmove clock of the root into
+/// account, and captures, en passant and stalemates are handled as in
+/// [`Tablebase::probe_wdl`].
+///
+/// The search still picks the move, so a winning engine may pick a slower win among the moves.
+/// Since the moves are ordered by DTZ, searching them in order favours progress. Returns an error
+/// if the position can't be probed, in which case all legal moves should be searched.
+pub fn filter_root_moves(tb: &Tablebase, position: &Board) -> Result<RootFilter, ProbeError> {
+    let root_moves = tb.probe_root(position)?;
+    let wdl = match root_moves.first() {
+        Some(best) => best.wdl,
+        None if position.status() == GameStatus::Won => Wdl::Loss,
+        None => Wdl::Draw,
+    };
+    let tb_hits = root_moves
+        .iter()
+        .map(|m| {
+            let mut child = position.clone();
+            child.play_unchecked(m.mv);
+            let probed_wdl = child.status() != GameStatus::Won;
+            let probed_dtz = probed_wdl && !m.zeroing && !m.degraded;
+            probed_wdl as u64 + probed_dtz as u64
+        })
+        .sum();
+    Ok(RootFilter {
+        moves: best_moves(&root_moves),
+        wdl,
+        tb_hits,
+    })
+}
+
+/// The moves with the outcome of the first move, which is the best since the moves are sorted.
+fn best_moves(root_moves: &[RootMove]) -> Vec<Move> {
+    root_moves
+        .iter()
+        .take_while(|m| m.wdl == root_moves[0].wdl)
+        .map(|m| m.mv)
+        .collect()
+}
+
+/// Which side of a score the true score is on, as stored in a transposition table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    /// The true score is at least the score.
+    Lower,
+    /// The true score is at most the score.
+    Upper,
+}
+
+/// Score a WDL probe at an interior node of a search, where a tablebase win `ply` plies from the
+/// root scores `tb_win - ply`. `tb_win` should be below the engine's mate scores.
+///
+/// Wins are lower bounds, since the search may still find a mate, and losses are upper bounds.
+/// The node should return the score if it is exact or the bound causes a cutoff, and otherwise
+/// search with the window narrowed by the bound. Cursed wins and blessed losses are drawn by the
+/// 50 move rule, so they are exact draws like other draws.
+pub fn adjudicate(wdl: Wdl, ply: u32, tb_win: i32) -> (i32, Bound) {
+    match wdl {
+        Wdl::Win => (tb_win - ply as i32, Bound::Lower),
+        Wdl::Loss => (-(tb_win - ply as i32), Bound::Upper),
+        Wdl::CursedWin | Wdl::Draw | Wdl::BlessedLoss => (0, Bound::Exact),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cozy_chess::Square;
+
+    use super::*;
+    use crate::tablebase::tests::{winning_krvk_dtz, winning_tablebase};
+
+    #[test]
+    fn only_the_best_root_moves_are_kept() {
+        let root_move = |to, wdl| RootMove {
+            mv: Move {
+                from: Square::A1,
+                to,
+                promotion: None,
+            },
+            wdl,
+            dtz: None,
+            degraded: true,
+            zeroing: false,
+        };
+        let moves = [
+            root_move(Square::A2, Wdl::Win),
+            root_move(Square::A3, Wdl::Win),
+            root_move(Square::A4, Wdl::CursedWin),
+            root_move(Square::A5, Wdl::Loss),
+        ];
+        let kept: Vec<_> = best_moves(&moves).into_iter().map(|m| m.to).collect();
+        assert_eq!(kept, [Square::A2, Square::A3]);
+        assert_eq!(best_moves(&moves[3..]).len(), 1);
+        assert!(best_moves(&[]).is_empty());
+    }
+
+    #[test]
+    fn probes_are_adjudicated_as_bounds() {
+        assert_eq!(adjudicate(Wdl::Win, 3, 20000), (19997, Bound::Lower));
+        assert_eq!(adjudicate(Wdl::Loss, 3, 20000), (-19997, Bound::Upper));
+        assert_eq!(adjudicate(Wdl::CursedWin, 3, 20000), (0, Bound::Exact));
+        assert_eq!(adjudicate(Wdl::BlessedLoss, 3, 20000), (0, Bound::Exact));
+    }
+
+    #[test]
+    fn only_successful_probes_are_hits() {
+        let mut tb = winning_tablebase();
+        let legal_moves = |board: &Board| {
+            let mut n = 0;
+            board.generate_moves(|moves| {
+                n += moves.len();
+                false
+            });
+            n as u64
+        };
+
+        // Ra8 is checkmate, so it isn't probed, and the DTZ table isn't loaded.
+        let mate_in_one: Board = "4k3/R7/4K3/8/8/8/8/8 w - - 0 1".parse().unwrap();
+        let filter = filter_root_moves(&tb, &mate_in_one).unwrap();
+        assert_eq!(filter.moves[0], "a7a8".parse().unwrap());
+        assert_eq!(filter.tb_hits, legal_moves(&mate_in_one) - 1);
+
+        // The DTZ table stores white to move, so both probes succeed after each black move.
+        tb.load_bytes_owned("KRvK", winning_krvk_dtz().into())
+            .unwrap();
+        let losing: Board = "4k3/R7/8/8/8/8/8/4K3 b - - 0 1".parse().unwrap();
+        let filter = filter_root_moves(&tb, &losing).unwrap();
+        assert_eq!(filter.wdl, Wdl::Loss);
+        assert_eq!(filter.tb_hits, 2 * legal_moves(&losing));
+    }
+}
@@ -0,0 +1,141 @@
+#[cfg(feature = "fs")]
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+#[cfg(feature = "fs")]
+use std::thread::JoinHandle;
+
+#[cfg(feature = "fs")]
+use crate::tablebase::fs::table_files;
+use crate::Tablebase;
+#[cfg(feature = "fs")]
+use crate::{LoadProgress, LoadSummary, SyzygyError};
+
+/// A handle to a [`Tablebase`] that can be replaced while it is in use.
+///
+/// Search threads call [`SharedTablebase::load`] to get the current tablebase, while a new
+/// tablebase is built in the background (e.g. after downloading more tables) and then published
+/// with [`SharedTablebase::store`]. Threads holding the old tablebase keep using it until they load
+/// again, and it is dropped once the last of them is done with it.
+pub struct SharedTablebase {
+    current: RwLock<Arc<Tablebase>>,
+}
+
+impl SharedTablebase {
+    pub fn new(tb: Tablebase) -> Self {
+        SharedTablebase {
+            current: RwLock::new(Arc::new(tb)),
+        }
+    }
+
+    /// Returns the current tablebase.
+    ///
+    /// This is cheap, but it does synchronize with other threads, so it should be called once per
+    /// search rather than once per probe.
+    pub fn load(&self) -> Arc<Tablebase> {
+        // The lock only guards an Arc assignment, so it can't be poisoned in an invalid state.
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Replace the current tablebase, returning the previous one.
+    pub fn store(&self, tb: Tablebase) -> Arc<Tablebase> {
+        let tb = Arc::new(tb);
+        let mut current = self.current.write().unwrap_or_else(|e| e.into_inner());
+        std::mem::replace(&mut *current, tb)
+    }
+
+    /// Load the tables in the specified directories into `tb` on a background thread, and then
+    /// publish it with [`SharedTablebase::store`], so that servers are not blocked while a large
+    /// set of tables is opened. Probes use the current tablebase until then.
+    ///
+    /// `tb` is usually [`Tablebase::new`], or a tablebase with the settings and tables to keep.
+    /// `progress` is called on the loading thread after each file. Files which fail to load are
+    /// skipped and listed in the summary, as with [`Tablebase::add_directory_lenient`]; an error is
+    /// only returned if a directory can't be read, in which case nothing is published. Join the
+    /// returned thread, e.g. from `spawn_blocking` in async code, to wait for loading to finish.
+    #[cfg(feature = "fs")]
+    pub fn add_directory_in_background(
+        self: &Arc<Self>,
+        mut tb: Tablebase,
+        dirs: Vec<PathBuf>,
+        mut progress: impl FnMut(LoadProgress) + Send + 'static,
+    ) -> JoinHandle<Result<LoadSummary, SyzygyError>> {
+        let shared = self.clone();
+        std::thread::spawn(move || {
+            let mut files = vec![];
+            for dir in &dirs {
+                files.extend(table_files(dir)?);
+            }
+
+            let mut summary = LoadSummary::default();
+            for (i, path) in files.iter().enumerate() {
+                if let Err(e) = tb.load_listed_file(path, false, &mut summary) {
+                    summary.failed.push((path.clone(), e));
+                }
+                progress(LoadProgress {
+                    files_done: i + 1,
+                    files_total: files.len(),
+                    bytes: summary.bytes,
+                });
+            }
+            shared.store(tb);
+            Ok(summary)
+        })
+    }
+}
+
+impl From<Tablebase> for SharedTablebase {
+    fn from(tb: Tablebase) -> Self {
+        SharedTablebase::new(tb)
+    }
+}
+
+#[cfg(all(test, feature = "fs"))]
+mod tests {
+    use std::sync::mpsc;
+
+    use cozy_chess::{Color, Square};
+
+    use super::*;
+    use crate::tablebase::tests::{krvk_placement, winning_krvk};
+    use crate::Wdl;
+
+    #[test]
+    fn tables_are_published_after_loading() {
+        let dir = std::env::temp_dir().join(format!("cozy-syzygy-shared-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let data = winning_krvk();
+        std::fs::write(dir.join("KRvK.rtbw"), &data).unwrap();
+        std::fs::write(dir.join("KQvK.rtbw"), [0; 64]).unwrap();
+
+        let shared = Arc::new(SharedTablebase::new(Tablebase::new()));
+        let (send, receive) = mpsc::channel();
+        let loading =
+            shared.add_directory_in_background(Tablebase::new(), vec![dir.clone()], move |p| {
+                send.send(p).unwrap();
+            });
+        let summary = loading.join().unwrap().unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(summary.loaded, 1);
+        assert_eq!(summary.failed.len(), 1);
+        let progress: Vec<_> = receive.iter().collect();
+        assert_eq!(progress.len(), 2);
+        assert_eq!(
+            progress[1],
+            LoadProgress {
+                files_done: 2,
+                files_total: 2,
+                bytes: data.len() as u64,
+            }
+        );
+        let tb = shared.load();
+        assert_eq!(tb.table_info().count(), 1);
+        let position = krvk_placement(Square::E1, Square::A7, Square::E8, Color::White);
+        assert_eq!(tb.probe_wdl_quiet(&position), Ok(Wdl::Win));
+        let position = krvk_placement(Square::A1, Square::B7, Square::E8, Color::White);
+        assert_eq!(tb.probe_wdl_quiet(&position), Ok(Wdl::CursedWin));
+    }
+}
@@ -0,0 +1,36 @@
+use std::sync::Arc;
+
+/// Storage holding the data of a table loaded with
+/// [`Tablebase::load_source`][crate::Tablebase::load_source].
+///
+/// This lets tables be backed by memory the caller manages, such as buffers filled from browser
+/// storage on targets without memory mapping. Tables are decoded directly from the data, so the
+/// whole table must be available as one slice, and `bytes` must return the same slice every time
+/// it is called.
+pub trait TableSource: Send + Sync + 'static {
+    fn bytes(&self) -> &[u8];
+}
+
+impl TableSource for Vec<u8> {
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl TableSource for Box<[u8]> {
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl TableSource for Arc<[u8]> {
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl TableSource for &'static [u8] {
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+}
@@ -0,0 +1,149 @@
+use std::ops::Range;
+
+use crate::SyzygyError;
+
+/// A bounds-checked little-endian reader over binary data, used for every binary format the crate
+/// parses.
+///
+/// Reads past the end of the data fail with [`UnexpectedEnd`], which converts to
+/// [`SyzygyError::Corrupt`], and leave the stream where it was, so the position of the failed read
+/// can be reported.
+pub(crate) struct DataStream<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+/// A read of `size` bytes at `position` would have run past the end of the data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct UnexpectedEnd {
+    pub position: usize,
+    pub size: usize,
+}
+
+impl From<UnexpectedEnd> for SyzygyError {
+    fn from(_: UnexpectedEnd) -> Self {
+        SyzygyError::Corrupt
+    }
+}
+
+impl<'a> DataStream<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        DataStream { data, position: 0 }
+    }
+
+    /// The number of bytes read so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The number of bytes left to read.
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+
+    /// Read `N` bytes without moving past them.
+    pub fn peek<const N: usize>(&self) -> Result<[u8; N], UnexpectedEnd> {
+        let end = self.end_of(N)?;
+        Ok(self.data[self.position..end].try_into().unwrap())
+    }
+
+    pub fn peek_u32(&self) -> Result<u32, UnexpectedEnd> {
+        Ok(u32::from_le_bytes(self.peek()?))
+    }
+
+    /// Skip `size` bytes, returning the range they occupy.
+    pub fn read_range(&mut self, size: usize) -> Result<Range<usize>, UnexpectedEnd> {
+        let start = self.position;
+        self.read_array(size)?;
+        Ok(start..start + size)
+    }
+
+    /// Skip bytes until the position is a multiple of `bytes`.
+    pub fn align_to(&mut self, bytes: usize) -> Result<(), UnexpectedEnd> {
+        let over = self.position % bytes;
+        if over > 0 {
+            self.read_array(bytes - over)?;
+        }
+        Ok(())
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, UnexpectedEnd> {
+        Ok(u8::from_le_bytes(self.read()?))
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, UnexpectedEnd> {
+        Ok(u16::from_le_bytes(self.read()?))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, UnexpectedEnd> {
+        Ok(u32::from_le_bytes(self.read()?))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, UnexpectedEnd> {
+        Ok(u64::from_le_bytes(self.read()?))
+    }
+
+    pub fn read<const N: usize>(&mut self) -> Result<[u8; N], UnexpectedEnd> {
+        Ok(self.read_array(N)?.try_into().unwrap())
+    }
+
+    pub fn read_array(&mut self, size: usize) -> Result<&'a [u8], UnexpectedEnd> {
+        let end = self.end_of(size)?;
+        let a = &self.data[self.position..end];
+        self.position = end;
+        Ok(a)
+    }
+
+    /// The end of a read of `size` bytes from the position, if it is within the data.
+    fn end_of(&self, size: usize) -> Result<usize, UnexpectedEnd> {
+        self.position
+            .checked_add(size)
+            .filter(|&end| end <= self.data.len())
+            .ok_or(UnexpectedEnd {
+                position: self.position,
+                size,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_are_bounds_checked() {
+        let mut stream = DataStream::new(&[1, 2, 3, 4, 5]);
+        assert_eq!(stream.read_u16().unwrap(), 0x0201);
+        assert!(stream.read_u32().is_err());
+        assert_eq!(stream.position(), 2);
+        assert_eq!(stream.read_range(2).unwrap(), 2..4);
+        assert_eq!(stream.remaining(), 1);
+        assert!(stream.read_array(usize::MAX).is_err());
+        assert_eq!(stream.read_u8().unwrap(), 5);
+        assert_eq!(
+            stream.read_u8(),
+            Err(UnexpectedEnd {
+                position: 5,
+                size: 1
+            })
+        );
+    }
+
+    #[test]
+    fn peeks_do_not_move_on() {
+        let mut stream = DataStream::new(&[1, 0, 0, 0, 2]);
+        assert_eq!(stream.peek_u32(), Ok(1));
+        assert_eq!(stream.position(), 0);
+
+        assert_eq!(stream.read_u32(), Ok(1));
+        assert_eq!(
+            stream.peek::<2>(),
+            Err(UnexpectedEnd {
+                position: 4,
+                size: 2
+            })
+        );
+        assert_eq!(stream.read::<1>(), Ok([2]));
+    }
+}
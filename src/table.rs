@@ -1,57 +1,495 @@
-use cozy_chess::{Board, Color, Piece};
-use ouroboros::self_referencing;
+use std::ops::Range;
 
-use crate::{Data, DataStream, Material, SyzygyError, Wdl};
+use cozy_chess::{Color, Piece, Square};
+
+use crate::block_cache::BlockCache;
+use crate::pairs::PairsData;
+use crate::{
+    ColoredPiece, Data, DataStream, Material, ProbePosition, SyzygyError, Wdl, MAX_PIECES,
+};
 
 mod pawnful;
 mod pawnless;
 
-#[self_referencing]
-pub struct WdlTable {
+#[cfg(feature = "internals")]
+pub use pawnful::norm_and_factors as pawnful_norm_and_factors;
+#[cfg(feature = "internals")]
+pub use pawnless::norm_and_factors as pawnless_norm_and_factors;
+
+const WDL_MAGIC: u32 = 0x5d23e871;
+const DTZ_MAGIC: u32 = 0xa50c66d7;
+
+/// Flags stored with the pairs data of DTZ tables.
+const DTZ_MAPPED: u8 = 2;
+const DTZ_WIN_PLIES: u8 = 4;
+const DTZ_LOSS_PLIES: u8 = 8;
+const DTZ_WIDE: u8 = 16;
+
+/// Which kind of values a table contains.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Wdl,
+    Dtz,
+}
+
+impl Kind {
+    /// The kind of table, going by the magic at the start of the data. Data that doesn't start
+    /// with the DTZ magic is assumed to be WDL, and fails to load if it isn't.
+    pub(super) fn of(data: &[u8]) -> Kind {
+        match DataStream::new(data).peek_u32() == Ok(DTZ_MAGIC) {
+            true => Kind::Dtz,
+            false => Kind::Wdl,
+        }
+    }
+}
+
+/// The smallest possible size of a file for the specified material: the magic, flags, and the
+/// piece order of the first table.
+pub(super) fn min_size(material: Material) -> usize {
+    4 + 1 + 1 + material.count() as usize
+}
+
+/// The compressed data of a WDL or DTZ table.
+pub struct RawTable {
     data: Data,
-    #[borrows(data)]
-    #[covariant]
-    variant: Variant<'this>,
+    encoding: Encoding,
+    subtables: Subtables,
 }
 
-enum Variant<'data> {
-    Pawnless(pawnless::WdlTable<'data>),
-    Pawnful(pawnful::WdlTable<'data>),
+/// How positions are indexed, which depends on whether the table has pawns.
+enum Encoding {
+    Pawnless(pawnless::PawnlessEncoding),
+    Pawnful(pawnful::PawnfulEncoding),
 }
 
-impl WdlTable {
-    pub(super) fn load(data: Data, material: Material) -> Result<Self, SyzygyError> {
-        WdlTable::try_new(data, |data| {
-            let mut data = DataStream::new(data.as_ref());
+/// The order of the pieces of a subtable and how they are indexed, read from the header of a
+/// table.
+struct Layout {
+    pieces: [ColoredPiece; MAX_PIECES],
+    norm: [u8; MAX_PIECES],
+    factors: [usize; MAX_PIECES],
+    size: usize,
+}
 
-            if data.read_u32() != 0x5d23e871 {
-                return Err(SyzygyError::NotSyzygy);
+/// The values of a table for one side to move and, in tables with pawns, one file of the leading
+/// pawn.
+struct Subtable {
+    pieces: [ColoredPiece; MAX_PIECES],
+    norm: [u8; MAX_PIECES],
+    factors: [usize; MAX_PIECES],
+    pairs_data: PairsData,
+    dtz_map: Option<DtzMap>,
+}
+
+/// The subtables of a table, by side to move and file. Pawnless tables only have file 0, and
+/// tables storing one side to move only have white subtables.
+///
+/// DTZ tables only store one side to move, which is stored as white regardless of which side it
+/// is.
+struct Subtables([Vec<Subtable>; 2]);
+
+impl Subtables {
+    /// Read the compressed data of subtables with the specified layouts. The data of all
+    /// subtables is stored together, in the order of the layouts with files outermost.
+    fn read(
+        data: &mut DataStream,
+        layouts: [Vec<Layout>; 2],
+        kind: Kind,
+    ) -> Result<Self, SyzygyError> {
+        let [white, black] = layouts;
+        let files = white.len();
+        let mut subtables = [vec![], vec![]];
+        let mut sizes = [vec![], vec![]];
+        let mut layouts = [white.into_iter(), black.into_iter()];
+        for _ in 0..files {
+            for side in 0..2 {
+                let Some(layout) = layouts[side].next() else {
+                    continue;
+                };
+                let (pairs_data, s) = PairsData::create(data, layout.size, kind == Kind::Wdl)?;
+                subtables[side].push(Subtable {
+                    pieces: layout.pieces,
+                    norm: layout.norm,
+                    factors: layout.factors,
+                    pairs_data,
+                    dtz_map: None,
+                });
+                sizes[side].push(s);
             }
+        }
 
-            let wpawns = material[(Color::White, Piece::Pawn)];
-            let bpawns = material[(Color::Black, Piece::Pawn)];
-
-            if wpawns + bpawns == 0 {
-                Ok(Variant::Pawnless(pawnless::WdlTable::new(
-                    &mut data, material,
-                )))
-            } else {
-                Ok(Variant::Pawnful(pawnful::WdlTable::new(
-                    &mut data, material,
-                )))
+        if kind == Kind::Dtz {
+            for subtable in &mut subtables[0] {
+                subtable.dtz_map = DtzMap::read(data, subtable.pairs_data.flags())?;
             }
+            data.align_to(2)?;
+        }
+
+        let mut subtables = Subtables(subtables);
+        for (subtable, s) in subtables.file_major(&sizes) {
+            subtable.pairs_data.index_table = data.read_range(s.index_table_size)?;
+        }
+        for (subtable, s) in subtables.file_major(&sizes) {
+            subtable.pairs_data.size_table = data.read_range(s.size_table_size)?;
+        }
+        for (subtable, s) in subtables.file_major(&sizes) {
+            data.align_to(64)?;
+            subtable.pairs_data.data = data.read_range(s.data_table_size)?;
+        }
+
+        Ok(subtables)
+    }
+
+    /// The subtables in the order their data is stored, with the corresponding `sizes`.
+    fn file_major<'a, T>(&'a mut self, sizes: &'a [Vec<T>; 2]) -> Vec<(&'a mut Subtable, &'a T)> {
+        let [white, black] = &mut self.0;
+        let mut black = black.iter_mut().zip(&sizes[1]);
+        let mut result = vec![];
+        for white in white.iter_mut().zip(&sizes[0]) {
+            result.push(white);
+            result.extend(black.next());
+        }
+        result
+    }
+
+    fn get(&self, stm: Color, file: usize) -> Option<&Subtable> {
+        self.0[stm as usize].get(file)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Subtable> {
+        self.0.iter().flatten()
+    }
+}
+
+impl RawTable {
+    fn load(data: Data, material: Material, kind: Kind) -> Result<Self, SyzygyError> {
+        let mut stream = DataStream::new(data.as_ref());
+
+        let magic = match kind {
+            Kind::Wdl => WDL_MAGIC,
+            Kind::Dtz => DTZ_MAGIC,
+        };
+        if stream.read_u32()? != magic {
+            return Err(SyzygyError::NotSyzygy);
+        }
+
+        let wpawns = material[(Color::White, Piece::Pawn)];
+        let bpawns = material[(Color::Black, Piece::Pawn)];
+
+        let tables = match wpawns + bpawns == 0 {
+            true => pawnless::PawnlessEncoding::new(&mut stream, material, kind)
+                .map(|(e, layouts)| (Encoding::Pawnless(e), layouts)),
+            false => pawnful::PawnfulEncoding::new(&mut stream, material, kind)
+                .map(|(e, layouts)| (Encoding::Pawnful(e), layouts)),
+        }
+        .and_then(|(encoding, layouts)| {
+            Ok((encoding, Subtables::read(&mut stream, layouts, kind)?))
+        });
+
+        // The stream stops where the problem was found, which helps when comparing against a good
+        // copy of the file.
+        let (encoding, subtables) = tables.map_err(|e| match e {
+            SyzygyError::Corrupt => SyzygyError::Corrupted {
+                material: material.to_string(),
+                offset: stream.position(),
+            },
+            e => e,
+        })?;
+
+        Ok(RawTable {
+            data,
+            encoding,
+            subtables,
         })
     }
 
-    pub(super) fn read(&self, pos: &Board, color_flip: bool) -> Wdl {
-        match self.borrow_variant() {
-            Variant::Pawnless(table) => table.read(pos, color_flip),
-            Variant::Pawnful(table) => table.read(pos, color_flip),
+    /// Load a table and decode every compressed block, for data which may be malformed. Fails
+    /// with [`SyzygyError::Corrupted`] at the first block which can't be decoded.
+    fn load_checked(data: Data, material: Material, kind: Kind) -> Result<Self, SyzygyError> {
+        let table = RawTable::load(data, material, kind)?;
+        let data = table.data.as_ref();
+        for pd in table.pairs_data() {
+            if let Some(block) = (0..pd.blocks()).find(|&b| pd.check_block(data, b).is_none()) {
+                return Err(SyzygyError::Corrupted {
+                    material: material.to_string(),
+                    offset: pd.block_offset(block),
+                });
+            }
+        }
+        Ok(table)
+    }
+
+    /// Find the subtable of a position, returning it and its file. `stm` is the side to move of
+    /// the subtable, which is white for tables storing one side. Pass the same `piece_squares` to
+    /// [`RawTable::index`].
+    fn subtable(
+        &self,
+        pos: &impl ProbePosition,
+        color_flip: bool,
+        stm: Color,
+        piece_squares: &mut [Square; MAX_PIECES],
+    ) -> Option<(&Subtable, usize)> {
+        let file = match &self.encoding {
+            Encoding::Pawnless(_) => 0,
+            Encoding::Pawnful(e) => {
+                e.lead_pawns(&self.subtables, pos, color_flip, piece_squares)?
+            }
+        };
+        Some((self.subtables.get(stm, file)?, file))
+    }
+
+    /// Find the index of a position in a subtable found with [`RawTable::subtable`].
+    fn index(
+        &self,
+        subtable: &Subtable,
+        pos: &impl ProbePosition,
+        color_flip: bool,
+        piece_squares: &mut [Square; MAX_PIECES],
+    ) -> Option<u64> {
+        match &self.encoding {
+            Encoding::Pawnless(e) => e.index(subtable, pos, color_flip, piece_squares),
+            Encoding::Pawnful(e) => e.index(subtable, pos, color_flip, piece_squares),
+        }
+    }
+
+    /// The number of compressed blocks, which can be checked with [`RawTable::check_block`].
+    pub(super) fn blocks(&self) -> usize {
+        self.pairs_data().iter().map(|pd| pd.blocks()).sum()
+    }
+
+    /// Check that a compressed block can be decoded. The blocks of the subtables are numbered
+    /// consecutively.
+    pub(super) fn check_block(&self, mut block: usize) -> bool {
+        for pd in self.pairs_data() {
+            if block < pd.blocks() {
+                return pd.check_block(self.data.as_ref(), block).is_some();
+            }
+            block -= pd.blocks();
+        }
+        false
+    }
+
+    /// Read the parts of the table needed by the first probes, so that they are loaded into
+    /// memory. See [`PairsData::touch`].
+    pub(super) fn touch(&self) -> u8 {
+        self.pairs_data()
+            .iter()
+            .fold(0, |acc, pd| acc ^ pd.touch(self.data.as_ref()))
+    }
+
+    /// The data the table was loaded from.
+    pub(super) fn data(&self) -> &Data {
+        &self.data
+    }
+
+    fn pairs_data(&self) -> Vec<&PairsData> {
+        self.subtables.iter().map(|s| &s.pairs_data).collect()
+    }
+}
+
+pub struct WdlTable(RawTable);
+
+impl WdlTable {
+    pub(super) fn load(data: Data, material: Material) -> Result<Self, SyzygyError> {
+        RawTable::load(data, material, Kind::Wdl).map(WdlTable)
+    }
+
+    /// Load a table and decode every compressed block. See [`RawTable::load_checked`].
+    pub(super) fn load_checked(data: Data, material: Material) -> Result<Self, SyzygyError> {
+        RawTable::load_checked(data, material, Kind::Wdl).map(WdlTable)
+    }
+
+    /// Read the WDL value of a position. Returns `None` if the data is corrupt.
+    pub(super) fn read(
+        &self,
+        cache: Option<&BlockCache>,
+        pos: &impl ProbePosition,
+        color_flip: bool,
+    ) -> Option<Wdl> {
+        let stm = match color_flip {
+            true => !pos.side_to_move(),
+            false => pos.side_to_move(),
+        };
+        let mut piece_squares = [Square::A1; MAX_PIECES];
+        let (subtable, _) = self.0.subtable(pos, color_flip, stm, &mut piece_squares)?;
+        let index = self
+            .0
+            .index(subtable, pos, color_flip, &mut piece_squares)?;
+        decode_wdl(
+            subtable
+                .pairs_data
+                .lookup(self.0.data.as_ref(), cache, index)?,
+        )
+    }
+
+    pub(super) fn raw(&self) -> &RawTable {
+        &self.0
+    }
+
+    /// Find the subtable and index of a position. See [`crate::raw::TableIndex`].
+    #[cfg(feature = "raw")]
+    pub(super) fn index_of(
+        &self,
+        pos: &impl ProbePosition,
+        color_flip: bool,
+    ) -> Option<(Color, usize, u64)> {
+        let stm = match color_flip {
+            true => !pos.side_to_move(),
+            false => pos.side_to_move(),
+        };
+        let mut piece_squares = [Square::A1; MAX_PIECES];
+        let (subtable, file) = self.0.subtable(pos, color_flip, stm, &mut piece_squares)?;
+        let index = self
+            .0
+            .index(subtable, pos, color_flip, &mut piece_squares)?;
+        Some((stm, file, index))
+    }
+
+    /// The compressed data of the subtable for a side to move and file, if it is stored.
+    #[cfg(feature = "raw")]
+    pub(super) fn subtable(&self, stm: Color, file: usize) -> Option<&PairsData> {
+        self.0.subtables.get(stm, file).map(|s| &s.pairs_data)
+    }
+
+    /// Decode the value at an index of a subtable. Returns `None` if the subtable is not stored,
+    /// the index is out of range, or the data is corrupt.
+    #[cfg(feature = "raw")]
+    pub(super) fn value(&self, stm: Color, file: usize, index: u64) -> Option<Wdl> {
+        let pairs_data = self.subtable(stm, file)?;
+        decode_wdl(pairs_data.lookup(self.0.data.as_ref(), None, index)?)
+    }
+}
+
+pub struct DtzTable(RawTable);
+
+/// The result of reading a DTZ table.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DtzRead {
+    /// The distance to zeroing in plies. This may be one more than the true distance, since some
+    /// tables store distances in moves.
+    Plies(u32),
+    /// The table only stores positions with the other side to move.
+    OtherSide,
+}
+
+impl DtzTable {
+    pub(super) fn load(data: Data, material: Material) -> Result<Self, SyzygyError> {
+        RawTable::load(data, material, Kind::Dtz).map(DtzTable)
+    }
+
+    /// Load a table and decode every compressed block. See [`RawTable::load_checked`].
+    pub(super) fn load_checked(data: Data, material: Material) -> Result<Self, SyzygyError> {
+        RawTable::load_checked(data, material, Kind::Dtz).map(DtzTable)
+    }
+
+    /// Read the DTZ of a position with the specified WDL value, which must not be a draw.
+    /// Returns `None` if the data is corrupt.
+    pub(super) fn read(
+        &self,
+        cache: Option<&BlockCache>,
+        pos: &impl ProbePosition,
+        color_flip: bool,
+        wdl: Wdl,
+    ) -> Option<DtzRead> {
+        let stm = match color_flip {
+            true => !pos.side_to_move(),
+            false => pos.side_to_move(),
+        };
+
+        let data = self.0.data.as_ref();
+        let mut piece_squares = [Square::A1; MAX_PIECES];
+        let (subtable, _) = self
+            .0
+            .subtable(pos, color_flip, Color::White, &mut piece_squares)?;
+        let flags = subtable.pairs_data.flags();
+        // Symmetric tables are always read with white to move, so either side can be stored.
+        let either_side = matches!(&self.0.encoding, Encoding::Pawnless(e) if e.symmetric);
+        if !either_side && flags & 1 != stm as u8 {
+            return Some(DtzRead::OtherSide);
         }
+
+        let index = self
+            .0
+            .index(subtable, pos, color_flip, &mut piece_squares)?;
+        let v = subtable.pairs_data.lookup(data, cache, index)?;
+        DtzMap::plies(subtable.dtz_map.as_ref(), data, flags, v, wdl).map(DtzRead::Plies)
+    }
+
+    pub(super) fn raw(&self) -> &RawTable {
+        &self.0
+    }
+}
+
+/// Maps the values stored in a DTZ table to distances. Each WDL value has its own mapping.
+struct DtzMap {
+    wide: bool,
+    /// The mapped values for wins, losses, cursed wins, and blessed losses.
+    entries: [Range<usize>; 4],
+}
+
+impl DtzMap {
+    /// Read the map of a subtable with the specified flags. Returns `None` if the subtable isn't
+    /// mapped.
+    fn read(data: &mut DataStream, flags: u8) -> Result<Option<DtzMap>, SyzygyError> {
+        if flags & DTZ_MAPPED == 0 {
+            return Ok(None);
+        }
+        let wide = flags & DTZ_WIDE != 0;
+        if wide {
+            data.align_to(2)?;
+        }
+        let mut entries = [0..0, 0..0, 0..0, 0..0];
+        for e in &mut entries {
+            *e = match wide {
+                true => {
+                    let len = data.read_u16()? as usize;
+                    data.read_range(2 * len)?
+                }
+                false => {
+                    let len = data.read_u8()? as usize;
+                    data.read_range(len)?
+                }
+            };
+        }
+        Ok(Some(DtzMap { wide, entries }))
+    }
+
+    /// Convert a value read from a subtable with the specified flags and map to plies.
+    fn plies(map: Option<&DtzMap>, data: &[u8], flags: u8, v: u16, wdl: Wdl) -> Option<u32> {
+        let mut v = v as u32;
+        if let Some(map) = map {
+            let i = match wdl {
+                Wdl::Win | Wdl::Draw => 0,
+                Wdl::Loss => 1,
+                Wdl::CursedWin => 2,
+                Wdl::BlessedLoss => 3,
+            };
+            let entries = data.get(map.entries[i].clone())?;
+            let j = v as usize;
+            v = match map.wide {
+                true => {
+                    let e = entries.get(2 * j..2 * j + 2)?;
+                    u16::from_le_bytes([e[0], e[1]]) as u32
+                }
+                false => *entries.get(j)? as u32,
+            };
+        }
+
+        let in_plies = match wdl {
+            Wdl::Win => flags & DTZ_WIN_PLIES != 0,
+            Wdl::Loss => flags & DTZ_LOSS_PLIES != 0,
+            Wdl::CursedWin | Wdl::BlessedLoss => false,
+            Wdl::Draw => true,
+        };
+        if !in_plies {
+            v *= 2;
+        }
+        Some(v + 1)
     }
 }
 
-fn subfactor(k: usize, n: usize) -> usize {
+/// The number of ways to place `k` identical pieces on `n` squares.
+pub fn subfactor(k: usize, n: usize) -> usize {
     let mut f = n;
     let mut l = 1;
     for i in 1..k {
@@ -61,3 +499,180 @@ fn subfactor(k: usize, n: usize) -> usize {
 
     f / l
 }
+
+/// Collect the squares of `pieces` in `pos`, in the same order. Identical pieces are adjacent in
+/// the piece lists of tables, and their squares are collected in increasing order.
+///
+/// If `color_flip` is set, the position is viewed from the other side: colors are swapped and the
+/// board is mirrored vertically.
+///
+/// Returns `None` if the position does not have exactly the pieces.
+fn collect_squares(
+    pos: &impl ProbePosition,
+    pieces: &[ColoredPiece],
+    color_flip: bool,
+    squares: &mut [Square],
+) -> Option<()> {
+    let mut i = 0;
+    while i < pieces.len() {
+        let n = pieces[i..].iter().take_while(|&&p| p == pieces[i]).count();
+        let color = match color_flip {
+            true => !pieces[i].color(),
+            false => pieces[i].color(),
+        };
+        let bb = pos.pieces(pieces[i].piece()) & pos.colors(color);
+        if bb.len() as usize != n {
+            return None;
+        }
+        for (sq, dest) in bb.into_iter().zip(squares.get_mut(i..i + n)?) {
+            *dest = match color_flip {
+                true => sq.flip_rank(),
+                false => sq,
+            };
+        }
+        i += n;
+    }
+    Some(())
+}
+
+/// Whether a piece list read from a file contains exactly the pieces of the material.
+fn matches_material(pieces: &[ColoredPiece], material: Material) -> bool {
+    let mut counts = Material::default();
+    let mut kings = [0; 2];
+    for p in pieces {
+        match p.piece() {
+            Piece::King => kings[p.color() as usize] += 1,
+            piece => counts[(p.color(), piece)] += 1,
+        }
+    }
+    kings == [1, 1] && counts == material
+}
+
+/// Converts a value stored in a WDL table to a [`Wdl`].
+fn decode_wdl(v: u16) -> Option<Wdl> {
+    match v {
+        0 => Some(Wdl::Loss),
+        1 => Some(Wdl::BlessedLoss),
+        2 => Some(Wdl::Draw),
+        3 => Some(Wdl::CursedWin),
+        4 => Some(Wdl::Win),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cozy_chess::BitBoard;
+
+    use super::*;
+    use ColoredPiece::*;
+
+    struct Position(Vec<(ColoredPiece, Square)>);
+
+    impl ProbePosition for Position {
+        fn pieces(&self, piece: Piece) -> BitBoard {
+            self.0
+                .iter()
+                .filter(|(p, _)| p.piece() == piece)
+                .fold(BitBoard::EMPTY, |bb, &(_, sq)| bb | sq.bitboard())
+        }
+
+        fn colors(&self, color: Color) -> BitBoard {
+            self.0
+                .iter()
+                .filter(|(p, _)| p.color() == color)
+                .fold(BitBoard::EMPTY, |bb, &(_, sq)| bb | sq.bitboard())
+        }
+
+        fn side_to_move(&self) -> Color {
+            Color::White
+        }
+
+        fn en_passant(&self) -> Option<Square> {
+            None
+        }
+
+        fn has_castle_rights(&self) -> bool {
+            false
+        }
+    }
+
+    // KRRvKP, with the pieces given out of table order.
+    fn position() -> Position {
+        Position(vec![
+            (BlackPawn, Square::C7),
+            (WhiteRook, Square::H1),
+            (BlackKing, Square::E8),
+            (WhiteRook, Square::A2),
+            (WhiteKing, Square::E1),
+        ])
+    }
+
+    #[test]
+    fn collect_squares_in_table_order() {
+        let pieces = [WhiteKing, BlackKing, WhiteRook, WhiteRook, BlackPawn];
+        let mut squares = [Square::A1; 5];
+        collect_squares(&position(), &pieces, false, &mut squares).unwrap();
+        assert_eq!(
+            squares,
+            [Square::E1, Square::E8, Square::H1, Square::A2, Square::C7]
+        );
+    }
+
+    #[test]
+    fn collect_squares_color_flipped() {
+        let pieces = [BlackKing, WhiteKing, BlackRook, BlackRook, WhitePawn];
+        let mut squares = [Square::A1; 5];
+        collect_squares(&position(), &pieces, true, &mut squares).unwrap();
+        assert_eq!(
+            squares,
+            [Square::E8, Square::E1, Square::H8, Square::A7, Square::C2]
+        );
+    }
+
+    #[test]
+    fn dtz_values_are_mapped() {
+        #[rustfmt::skip]
+        let mut data = vec![
+            // magic, flags, order, pieces (white king, white rook, black king), padding
+            0xD7, 0x66, 0x0C, 0xA5, 1, 0, 0x06, 0x04, 0x0E, 0,
+            // a constant table storing white to move, which is mapped and stores losses in plies
+            0x8A, 0,
+            // the maps for wins, losses, cursed wins, and blessed losses
+            1, 7, 1, 9, 1, 20, 1, 30,
+        ];
+        data.resize(64, 0);
+        let table = DtzTable::load(Data::OwnedBytes(data.into()), "KRvK".parse().unwrap()).unwrap();
+
+        let position = Position(vec![
+            (WhiteKing, Square::E1),
+            (WhiteRook, Square::A2),
+            (BlackKing, Square::E8),
+        ]);
+        let read = |wdl| table.read(None, &position, false, wdl);
+        assert_eq!(read(Wdl::Win), Some(DtzRead::Plies(15)));
+        assert_eq!(read(Wdl::Loss), Some(DtzRead::Plies(10)));
+        assert_eq!(read(Wdl::CursedWin), Some(DtzRead::Plies(41)));
+        assert_eq!(read(Wdl::BlessedLoss), Some(DtzRead::Plies(61)));
+
+        // Color flipped, black is to move.
+        let position = Position(vec![
+            (BlackKing, Square::E8),
+            (BlackRook, Square::A7),
+            (WhiteKing, Square::E1),
+        ]);
+        let read = table.read(None, &position, true, Wdl::Win);
+        assert_eq!(read, Some(DtzRead::OtherSide));
+    }
+
+    #[test]
+    fn collect_squares_missing_pieces() {
+        let mut squares = [Square::A1; 5];
+        let pieces = [WhiteKing, BlackKing, WhiteRook, WhiteRook, BlackKnight];
+        assert!(collect_squares(&position(), &pieces, false, &mut squares).is_none());
+
+        let mut squares = [Square::A1; 4];
+        let pieces = [WhiteKing, BlackKing, WhiteRook, BlackPawn];
+        assert!(collect_squares(&position(), &pieces, false, &mut squares).is_none());
+    }
+}
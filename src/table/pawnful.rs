@@ -1,38 +1,37 @@
-use cozy_chess::{Board, Color, File, Piece, Square};
+use cozy_chess::{Color, File, Piece, Square};
 
 use crate::constants::{BINOMIAL, FILE_TO_FILE, FLAP, PAWN_FACTOR, PAWN_INDEX, PAWN_TWIST};
-use crate::pairs::PairsData;
-use crate::{ColoredPiece, DataStream, Material, Wdl, MAX_PIECES};
+use crate::{ColoredPiece, DataStream, Material, ProbePosition, SyzygyError, MAX_PIECES};
 
-use super::subfactor;
+use super::{collect_squares, matches_material, subfactor, Kind, Layout, Subtable, Subtables};
 
-pub struct WdlTable<'data> {
+/// How the positions of a table with pawns are indexed.
+pub struct PawnfulEncoding {
     men: usize,
     white_pawns: usize,
     black_pawns: usize,
-    // todo: refactor
-    tables: [[Option<Table<'data>>; 4]; 2],
 }
 
-struct Table<'data> {
-    pieces: [ColoredPiece; MAX_PIECES],
-    norm: [u8; MAX_PIECES],
-    factors: [usize; MAX_PIECES],
-    pairs_data: PairsData<'data>,
-}
-
-impl<'data> WdlTable<'data> {
-    pub(crate) fn new(data: &mut DataStream<'data>, material: Material) -> Self {
+impl PawnfulEncoding {
+    /// Read the header of a table, returning its encoding and the layouts of its subtables.
+    pub(super) fn new(
+        data: &mut DataStream,
+        material: Material,
+        kind: Kind,
+    ) -> Result<(Self, [Vec<Layout>; 2]), SyzygyError> {
         let men = material.count() as usize;
 
-        let flags = data.read_u8();
+        let flags = data.read_u8()?;
         let split = flags & 1 != 0;
         let files = match flags & 2 != 0 {
             true => 4,
             false => 1,
         };
 
-        assert_eq!(split, !material.is_symmetric());
+        if split == material.is_symmetric() {
+            return Err(SyzygyError::MaterialMismatch);
+        }
+        let both_sides = split && kind == Kind::Wdl;
 
         let mut white_pawns = material[(Color::White, Piece::Pawn)];
         let mut black_pawns = material[(Color::Black, Piece::Pawn)];
@@ -41,42 +40,52 @@ impl<'data> WdlTable<'data> {
         }
         let black_has_pawns = black_pawns > 0;
 
-        let mut wtm_pieces = [[ColoredPiece::WhitePawn; MAX_PIECES]; 4];
-        let mut btm_pieces = [[ColoredPiece::WhitePawn; MAX_PIECES]; 4];
-        let mut wtm_tb_sizes = [0; 4];
-        let mut btm_tb_sizes = [0; 4];
-        let mut wtm_norm = [[0; MAX_PIECES]; 4];
-        let mut btm_norm = [[0; MAX_PIECES]; 4];
-        let mut wtm_factor = [[0; MAX_PIECES]; 4];
-        let mut btm_factor = [[0; MAX_PIECES]; 4];
-
+        let mut layouts: [Vec<Layout>; 2] = [vec![], vec![]];
         for f in 0..files {
-            let order = data.read_u8();
+            let order = data.read_u8()?;
             let order2 = match black_has_pawns {
-                true => data.read_u8(),
+                true => data.read_u8()?,
                 false => 0xFF,
             };
-            let pieces = data.read_array(men);
+            let pieces = data.read_array(men)?;
 
+            let mut wtm_pieces = [ColoredPiece::WhitePawn; MAX_PIECES];
+            let mut btm_pieces = [ColoredPiece::WhitePawn; MAX_PIECES];
             for i in 0..men {
-                wtm_pieces[f][i] = ColoredPiece::decode(pieces[i] & 0xF).unwrap();
-                if split {
-                    btm_pieces[f][i] = ColoredPiece::decode(pieces[i] >> 4).unwrap();
+                wtm_pieces[i] =
+                    ColoredPiece::decode(pieces[i] & 0xF).ok_or(SyzygyError::Corrupt)?;
+                if both_sides {
+                    btm_pieces[i] =
+                        ColoredPiece::decode(pieces[i] >> 4).ok_or(SyzygyError::Corrupt)?;
+                }
+            }
+
+            // The leading pawns are those of the first file.
+            let lead = layouts[0].first().map_or(wtm_pieces[0], |l| l.pieces[0]);
+            let sides = [
+                (wtm_pieces, order & 0xF, order2 & 0xF),
+                (btm_pieces, order >> 4, order2 >> 4),
+            ];
+            let sides = &sides[..1 + both_sides as usize];
+            for (pieces, ..) in sides {
+                if !matches_material(&pieces[..men], material) {
+                    return Err(SyzygyError::MaterialMismatch);
+                }
+                if !pawns_first(&pieces[..men], lead, white_pawns, black_pawns) {
+                    return Err(SyzygyError::Corrupt);
                 }
             }
 
-            wtm_norm[f] = calculate_norm(white_pawns, black_pawns, men, &wtm_pieces[f]);
-            let (tb_size, factors) =
-                calculate_factors(&wtm_norm[f], men, order & 0xF, order2 & 0xF, f);
-            wtm_tb_sizes[f] = tb_size;
-            wtm_factor[f] = factors;
-
-            if split {
-                btm_norm[f] = calculate_norm(white_pawns, black_pawns, men, &btm_pieces[f]);
-                let (tb_size, factors) =
-                    calculate_factors(&btm_norm[f], men, order >> 4, order2 >> 4, f);
-                btm_tb_sizes[f] = tb_size;
-                btm_factor[f] = factors;
+            for (side, &(pieces, order, order2)) in sides.iter().enumerate() {
+                let norm = calculate_norm(white_pawns, black_pawns, men, &pieces);
+                let (size, factors) =
+                    calculate_factors(&norm, men, order, order2, f).ok_or(SyzygyError::Corrupt)?;
+                layouts[side].push(Layout {
+                    pieces,
+                    norm,
+                    factors,
+                    size,
+                });
             }
         }
 
@@ -87,200 +96,183 @@ impl<'data> WdlTable<'data> {
                     true => men + 2,
                     false => men + 1,
                 },
-            );
-        }
-
-        data.align_to(2);
-
-        // yike
-        let mut tables = [[(); 4]; 2].map(|a| a.map(|_| None));
-        let mut sizes = [[None; 4]; 2];
-
-        for f in 0..files {
-            let (pairs_data, s) = PairsData::create(data, wtm_tb_sizes[f], true);
-            tables[0][f] = Some(Table {
-                pieces: wtm_pieces[f],
-                norm: wtm_norm[f],
-                factors: wtm_factor[f],
-                pairs_data,
-            });
-            sizes[0][f] = Some(s);
-            if split {
-                let (pairs_data, s) = PairsData::create(data, btm_tb_sizes[f], true);
-                tables[1][f] = Some(Table {
-                    pieces: btm_pieces[f],
-                    norm: btm_norm[f],
-                    factors: btm_factor[f],
-                    pairs_data,
-                });
-                sizes[1][f] = Some(s);
-            }
-        }
-
-        for f in 0..files {
-            tables[0][f].as_mut().unwrap().pairs_data.index_table =
-                data.read_array(sizes[0][f].as_ref().unwrap().index_table_size);
-            if split {
-                tables[1][f].as_mut().unwrap().pairs_data.index_table =
-                    data.read_array(sizes[1][f].as_ref().unwrap().index_table_size);
-            }
+            )?;
         }
 
-        for f in 0..files {
-            tables[0][f].as_mut().unwrap().pairs_data.size_table =
-                data.read_array(sizes[0][f].as_ref().unwrap().size_table_size);
-            if split {
-                tables[1][f].as_mut().unwrap().pairs_data.size_table =
-                    data.read_array(sizes[1][f].as_ref().unwrap().size_table_size);
-            }
-        }
-
-        for f in 0..files {
-            data.align_to(64);
-            tables[0][f].as_mut().unwrap().pairs_data.data =
-                data.read_array(sizes[0][f].as_ref().unwrap().data_table_size);
-            if split {
-                data.align_to(64);
-                tables[1][f].as_mut().unwrap().pairs_data.data =
-                    data.read_array(sizes[1][f].as_ref().unwrap().data_table_size);
-            }
-        }
+        data.align_to(2)?;
 
-        WdlTable {
-            tables,
+        let encoding = PawnfulEncoding {
             men,
             white_pawns: white_pawns as usize,
             black_pawns: black_pawns as usize,
-        }
-    }
-
-    pub fn read(&self, pos: &Board, color_flip: bool) -> Wdl {
-        let flip_color = |c: Color| match color_flip {
-            true => !c,
-            false => c,
-        };
-        let flip_rank = |sq: Square| match color_flip {
-            true => sq.flip_rank(),
-            false => sq,
         };
+        Ok((encoding, layouts))
+    }
 
-        let mut piece_squares = [Square::A1; MAX_PIECES];
-
-        let k = self.tables[0][0].as_ref().unwrap().pieces[0];
-        let mut i = 0;
-        let bb = pos.pieces(k.piece()) & pos.colors(flip_color(k.color()));
-        for sq in bb {
-            piece_squares[i] = flip_rank(sq);
-            i += 1;
-        }
-
-        let f = pawn_file(self.white_pawns, &mut piece_squares);
-        let table = self.tables[flip_color(pos.side_to_move()) as usize][f]
-            .as_ref()
-            .unwrap();
-
-        while i < self.men {
-            let bb = pos.pieces(table.pieces[i].piece())
-                & pos.colors(flip_color(table.pieces[i].color()));
-            for sq in bb {
-                piece_squares[i] = flip_rank(sq);
-                i += 1;
-            }
-        }
+    /// Collect the squares of the leading pawns, returning the file of the subtables they select.
+    pub(super) fn lead_pawns(
+        &self,
+        subtables: &Subtables,
+        pos: &impl ProbePosition,
+        color_flip: bool,
+        piece_squares: &mut [Square; MAX_PIECES],
+    ) -> Option<usize> {
+        // The leading pawns are the same in every subtable.
+        let lead = &subtables.get(Color::White, 0)?.pieces[..self.white_pawns];
+        collect_squares(
+            pos,
+            lead,
+            color_flip,
+            &mut piece_squares[..self.white_pawns],
+        )?;
+        Some(pawn_file(self.white_pawns, piece_squares))
+    }
 
-        match table.pairs_data.lookup(table.index(
+    /// Collect the squares of the other pieces of a position in the order of a subtable, after
+    /// the leading pawns have been collected, and find the index of the position in it.
+    pub(super) fn index(
+        &self,
+        subtable: &Subtable,
+        pos: &impl ProbePosition,
+        color_flip: bool,
+        piece_squares: &mut [Square; MAX_PIECES],
+    ) -> Option<u64> {
+        collect_squares(
+            pos,
+            &subtable.pieces[self.white_pawns..self.men],
+            color_flip,
+            &mut piece_squares[self.white_pawns..self.men],
+        )?;
+        Some(index(
+            subtable,
             self.white_pawns,
             self.black_pawns,
             &mut piece_squares[..self.men],
-        )) {
-            0 => Wdl::Loss,
-            1 => Wdl::BlessedLoss,
-            2 => Wdl::Draw,
-            3 => Wdl::CursedWin,
-            4 => Wdl::Win,
-            _ => unreachable!(),
-        }
+        ))
     }
 }
 
-impl Table<'_> {
-    fn index(&self, white_pawns: usize, black_pawns: usize, piece_squares: &mut [Square]) -> u64 {
-        if piece_squares[0].file() > File::D {
-            for sq in &mut *piece_squares {
-                *sq = sq.flip_file();
-            }
+fn index(
+    subtable: &Subtable,
+    white_pawns: usize,
+    black_pawns: usize,
+    piece_squares: &mut [Square],
+) -> u64 {
+    if piece_squares[0].file() > File::D {
+        for sq in &mut *piece_squares {
+            *sq = sq.flip_file();
         }
+    }
 
-        for i in 1..white_pawns {
-            for j in i + 1..white_pawns {
-                if PAWN_TWIST[piece_squares[i] as usize] < PAWN_TWIST[piece_squares[j] as usize] {
-                    piece_squares.swap(i, j);
-                }
+    for i in 1..white_pawns {
+        for j in i + 1..white_pawns {
+            if PAWN_TWIST[piece_squares[i] as usize] < PAWN_TWIST[piece_squares[j] as usize] {
+                piece_squares.swap(i, j);
             }
         }
+    }
 
-        let t = white_pawns - 1;
-        let mut index = PAWN_INDEX[t][FLAP[piece_squares[0] as usize] as usize] as u64;
-        for i in (1..=t).rev() {
-            index += BINOMIAL[t - i][PAWN_TWIST[piece_squares[i] as usize] as usize] as u64;
-        }
-        index *= self.factors[0] as u64;
-
-        let mut i = white_pawns;
-        let t = white_pawns + black_pawns;
-        if t > i {
-            for j in i..t {
-                for k in j + 1..t {
-                    if piece_squares[j] > piece_squares[k] {
-                        piece_squares.swap(j, k);
-                    }
+    let t = white_pawns - 1;
+    let mut index = PAWN_INDEX[t][FLAP[piece_squares[0] as usize] as usize] as u64;
+    for i in (1..=t).rev() {
+        index += BINOMIAL[t - i][PAWN_TWIST[piece_squares[i] as usize] as usize] as u64;
+    }
+    index *= subtable.factors[0] as u64;
+
+    let mut i = white_pawns;
+    let t = white_pawns + black_pawns;
+    if t > i {
+        for j in i..t {
+            for k in j + 1..t {
+                if piece_squares[j] > piece_squares[k] {
+                    piece_squares.swap(j, k);
                 }
             }
+        }
 
-            let mut s = 0;
-            for m in i..t {
-                let sq = piece_squares[m];
-                let mut j = 0;
-                for k in 0..i {
-                    if sq > piece_squares[k] {
-                        j += 1;
-                    }
+        let mut s = 0;
+        for m in i..t {
+            let sq = piece_squares[m];
+            let mut j = 0;
+            for k in 0..i {
+                if sq > piece_squares[k] {
+                    j += 1;
                 }
-                s += BINOMIAL[m - i][sq as usize - j - 8] as u64;
             }
-
-            index += s * self.factors[i] as u64;
-            i = t;
+            s += BINOMIAL[m - i][sq as usize - j - 8] as u64;
         }
 
-        while i < piece_squares.len() {
-            let t = self.norm[i] as usize;
-            for j in i..i + t {
-                for k in j + 1..i + t {
-                    if piece_squares[j] > piece_squares[k] {
-                        piece_squares.swap(j, k);
-                    }
+        index += s * subtable.factors[i] as u64;
+        i = t;
+    }
+
+    while i < piece_squares.len() {
+        let t = subtable.norm[i] as usize;
+        for j in i..i + t {
+            for k in j + 1..i + t {
+                if piece_squares[j] > piece_squares[k] {
+                    piece_squares.swap(j, k);
                 }
             }
+        }
 
-            let mut s = 0;
-            for m in i..i + t {
-                let sq = piece_squares[m];
-                let mut j = 0;
-                for k in 0..i {
-                    if sq > piece_squares[k] {
-                        j += 1;
-                    }
+        let mut s = 0;
+        for m in i..i + t {
+            let sq = piece_squares[m];
+            let mut j = 0;
+            for k in 0..i {
+                if sq > piece_squares[k] {
+                    j += 1;
                 }
-                s += BINOMIAL[m - i][sq as usize - j] as u64;
             }
-
-            index += s * self.factors[i] as u64;
-            i += t;
+            s += BINOMIAL[m - i][sq as usize - j] as u64;
         }
 
-        index
+        index += s * subtable.factors[i] as u64;
+        i += t;
+    }
+
+    index
+}
+
+/// Computes the table size, `norm`, and `factors` of one file of a pawnful table from its piece
+/// order, as stored in the file. `file` is the file of the leading pawn, from 0 (a-file) to 3
+/// (d-file), and `order2` is `0xF` if only one side has pawns.
+///
+/// Returns `None` if any piece code is invalid, there are too many pieces, or the first piece is
+/// not a pawn.
+#[cfg(feature = "internals")]
+pub fn norm_and_factors(
+    pieces: &[u8],
+    order: u8,
+    order2: u8,
+    file: usize,
+) -> Option<(usize, [u8; MAX_PIECES], [usize; MAX_PIECES])> {
+    let men = pieces.len();
+    if men > MAX_PIECES || file >= 4 {
+        return None;
+    }
+    let mut decoded = [ColoredPiece::WhitePawn; MAX_PIECES];
+    for (d, &p) in decoded.iter_mut().zip(pieces) {
+        *d = ColoredPiece::decode(p)?;
+    }
+
+    let lead = decoded[0];
+    if lead.piece() != Piece::Pawn {
+        return None;
     }
+    let count = |c: Color| {
+        decoded[..men]
+            .iter()
+            .filter(|p| p.piece() == Piece::Pawn && p.color() == c)
+            .count() as u8
+    };
+    let lead_pawns = count(lead.color());
+    let other_pawns = count(!lead.color());
+
+    let norm = calculate_norm(lead_pawns, other_pawns, men, &decoded);
+    let (size, factors) = calculate_factors(&norm, men, order, order2, file)?;
+    Some((size, norm, factors))
 }
 
 fn calculate_norm(
@@ -307,28 +299,37 @@ fn calculate_norm(
     result
 }
 
+/// Returns `None` if `order` or `order2` is out of range.
 fn calculate_factors(
     norm: &[u8; MAX_PIECES],
     men: usize,
     order: u8,
     order2: u8,
     file: usize,
-) -> (usize, [usize; MAX_PIECES]) {
+) -> Option<(usize, [usize; MAX_PIECES])> {
     let mut i = norm[0] as usize;
     if order2 < 0xF {
         i += norm[i] as usize;
     }
 
+    // PAWN_FACTOR and BINOMIAL only cover groups of up to 5 identical pieces.
+    if norm.iter().any(|&n| n > 5) {
+        return None;
+    }
+
     let mut factor = [0; MAX_PIECES];
+    let mut placed = (false, order2 >= 0xF);
 
     let mut f: usize = 1;
     for k in 0.. {
         if k == order {
             factor[0] = f;
             f *= PAWN_FACTOR[norm[0] as usize - 1][file] as usize;
+            placed.0 = true;
         } else if k == order2 {
             factor[norm[0] as usize] = f;
             f *= subfactor(norm[norm[0] as usize] as usize, 48 - norm[0] as usize) as usize;
+            placed.1 = true;
         } else if i < men {
             factor[i] = f;
             f *= subfactor(norm[i] as usize, 64 - i) as usize;
@@ -338,7 +339,27 @@ fn calculate_factors(
         }
     }
 
-    (f, factor)
+    (placed == (true, true)).then_some((f, factor))
+}
+
+/// Whether a piece list read from a file starts with the leading pawns followed by the other
+/// side's pawns, which the indexing relies on.
+fn pawns_first(
+    pieces: &[ColoredPiece],
+    lead: ColoredPiece,
+    lead_pawns: u8,
+    other_pawns: u8,
+) -> bool {
+    let (lead_pawns, other_pawns) = (lead_pawns as usize, other_pawns as usize);
+    let other = match lead {
+        ColoredPiece::WhitePawn => ColoredPiece::BlackPawn,
+        ColoredPiece::BlackPawn => ColoredPiece::WhitePawn,
+        _ => return false,
+    };
+    pieces[..lead_pawns].iter().all(|&p| p == lead)
+        && pieces[lead_pawns..lead_pawns + other_pawns]
+            .iter()
+            .all(|&p| p == other)
 }
 
 fn pawn_file(white_pawns: usize, piece_squares: &mut [Square; MAX_PIECES]) -> usize {
@@ -349,3 +370,210 @@ fn pawn_file(white_pawns: usize, piece_squares: &mut [Square; MAX_PIECES]) -> us
     }
     FILE_TO_FILE[piece_squares[0].file() as usize] as usize
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use cozy_chess::Rank;
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::pairs::PairsData;
+    use ColoredPiece::*;
+
+    struct Case {
+        pieces: &'static [ColoredPiece],
+        lead_pawns: usize,
+        other_pawns: usize,
+        order: u8,
+        order2: u8,
+    }
+
+    const MATERIALS: &[Case] = &[
+        Case {
+            pieces: &[WhitePawn, WhiteKing, BlackKing],
+            lead_pawns: 1,
+            other_pawns: 0,
+            order: 0,
+            order2: 0xF,
+        },
+        Case {
+            pieces: &[WhitePawn, WhiteKing, BlackKing, WhiteRook, BlackRook],
+            lead_pawns: 1,
+            other_pawns: 0,
+            order: 2,
+            order2: 0xF,
+        },
+        Case {
+            pieces: &[BlackPawn, WhitePawn, WhitePawn, WhiteKing, BlackKing],
+            lead_pawns: 1,
+            other_pawns: 2,
+            order: 0,
+            order2: 1,
+        },
+        Case {
+            pieces: &[
+                WhitePawn, WhitePawn, BlackPawn, BlackPawn, WhiteKing, BlackKing,
+            ],
+            lead_pawns: 2,
+            other_pawns: 2,
+            order: 1,
+            order2: 0,
+        },
+        Case {
+            pieces: &[
+                WhitePawn,
+                WhiteKing,
+                BlackKing,
+                WhiteKnight,
+                WhiteKnight,
+                BlackQueen,
+            ],
+            lead_pawns: 1,
+            other_pawns: 0,
+            order: 3,
+            order2: 0xF,
+        },
+    ];
+
+    /// Builds the tables of all four files, along with their sizes.
+    fn tables(m: &Case) -> [(Subtable, usize); 4] {
+        let men = m.pieces.len();
+        let mut pieces = [WhitePawn; MAX_PIECES];
+        pieces[..men].copy_from_slice(m.pieces);
+
+        let norm = calculate_norm(m.lead_pawns as u8, m.other_pawns as u8, men, &pieces);
+        [0, 1, 2, 3].map(|file| {
+            let (size, factors) = calculate_factors(&norm, men, m.order, m.order2, file).unwrap();
+            let (pairs_data, _) =
+                PairsData::create(&mut DataStream::new(&[0x80, 2]), size, true).unwrap();
+            let table = Subtable {
+                pieces,
+                norm,
+                factors,
+                pairs_data,
+                dtz_map: None,
+            };
+            (table, size)
+        })
+    }
+
+    fn is_valid(m: &Case, squares: &[Square]) -> bool {
+        let distinct = squares
+            .iter()
+            .enumerate()
+            .all(|(i, sq)| !squares[..i].contains(sq));
+        let pawns_valid = squares[..m.lead_pawns + m.other_pawns]
+            .iter()
+            .all(|sq| (Rank::Second..=Rank::Seventh).contains(&sq.rank()));
+        distinct && pawns_valid
+    }
+
+    /// Returns the file of the leading pawn, the index, and the size of the table for that file.
+    fn index(m: &Case, tables: &[(Subtable, usize); 4], squares: &[Square]) -> (usize, u64, usize) {
+        let mut piece_squares = [Square::A1; MAX_PIECES];
+        piece_squares[..squares.len()].copy_from_slice(squares);
+        let f = pawn_file(m.lead_pawns, &mut piece_squares);
+        let (table, size) = &tables[f];
+        let index = super::index(
+            table,
+            m.lead_pawns,
+            m.other_pawns,
+            &mut piece_squares[..squares.len()],
+        );
+        (f, index, *size)
+    }
+
+    /// Whether two placements are the same position up to mirroring the files and reordering of
+    /// identical pieces.
+    fn equivalent(pieces: &[ColoredPiece], a: &[Square], b: &[Square]) -> bool {
+        let canonical = |squares: &mut [Square]| {
+            let mut i = 0;
+            while i < pieces.len() {
+                let n = pieces[i..].iter().take_while(|&&p| p == pieces[i]).count();
+                squares[i..i + n].sort();
+                i += n;
+            }
+        };
+
+        let mut b = b.to_vec();
+        canonical(&mut b);
+        [false, true].into_iter().any(|flip| {
+            let mut a: Vec<_> = a
+                .iter()
+                .map(|&sq| if flip { sq.flip_file() } else { sq })
+                .collect();
+            canonical(&mut a);
+            a == b
+        })
+    }
+
+    fn placement() -> impl Strategy<Value = (usize, Vec<Square>)> {
+        (0..MATERIALS.len()).prop_flat_map(|m| {
+            let men = MATERIALS[m].pieces.len();
+            let squares = prop::collection::vec((0..64usize).prop_map(Square::index), men);
+            (Just(m), squares)
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn index_is_in_range((m, squares) in placement()) {
+            let m = &MATERIALS[m];
+            prop_assume!(is_valid(m, &squares));
+            let (_, index, size) = index(m, &tables(m), &squares);
+            prop_assert!(index < size as u64);
+        }
+
+        #[test]
+        fn index_ignores_file_mirroring((m, squares) in placement()) {
+            let m = &MATERIALS[m];
+            prop_assume!(is_valid(m, &squares));
+            let tables = tables(m);
+            let flipped: Vec<_> = squares.iter().map(|sq| sq.flip_file()).collect();
+            prop_assert_eq!(index(m, &tables, &flipped), index(m, &tables, &squares));
+        }
+
+        #[test]
+        fn index_is_injective(
+            (m, a) in placement(),
+            b in prop::collection::vec((0..64usize).prop_map(Square::index), MAX_PIECES),
+        ) {
+            let m = &MATERIALS[m];
+            let b = &b[..m.pieces.len()];
+            prop_assume!(is_valid(m, &a) && is_valid(m, b));
+            let tables = tables(m);
+            let (a_file, a_index, _) = index(m, &tables, &a);
+            let (b_file, b_index, _) = index(m, &tables, b);
+            if (a_file, a_index) == (b_file, b_index) {
+                prop_assert!(equivalent(m.pieces, &a, b));
+            }
+        }
+    }
+
+    #[test]
+    fn three_men_index_is_injective() {
+        let m = &MATERIALS[0];
+        let tables = tables(m);
+        let mut seen: HashMap<(usize, u64), [Square; 3]> = HashMap::new();
+        for a in Square::ALL {
+            for b in Square::ALL {
+                for c in Square::ALL {
+                    let squares = [a, b, c];
+                    if !is_valid(m, &squares) {
+                        continue;
+                    }
+                    let (f, i, size) = index(m, &tables, &squares);
+                    assert!(i < size as u64);
+                    if let Some(other) = seen.insert((f, i), squares) {
+                        assert!(
+                            equivalent(m.pieces, other.as_slice(), &squares),
+                            "{other:?} and {squares:?} share index {i} of file {f}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,25 +1,19 @@
-use cozy_chess::{Board, Color, File, Piece, Rank, Square};
+use cozy_chess::{Color, File, Piece, Rank, Square};
 
 use crate::constants::{
     BINOMIAL, DIAGONAL, FLIP_DIAGONAL, KK_INDEX, LOWER, OFF_DIAGONAL, TRIANGLE,
 };
-use crate::pairs::PairsData;
-use crate::{ColoredPiece, DataStream, Material, Wdl, MAX_PIECES};
+use crate::{ColoredPiece, DataStream, Material, ProbePosition, SyzygyError, MAX_PIECES};
 
-use super::subfactor;
+use super::{collect_squares, matches_material, subfactor, Kind, Layout, Subtable};
 
-pub struct WdlTable<'data> {
+/// How the positions of a pawnless table are indexed.
+pub struct PawnlessEncoding {
     men: usize,
+    /// Whether the material is symmetric, in which case positions are always read with white to
+    /// move.
+    pub(super) symmetric: bool,
     encoding_type: EncodingType,
-    white_to_move: Table<'data>,
-    black_to_move: Option<Table<'data>>,
-}
-
-struct Table<'data> {
-    pieces: [ColoredPiece; MAX_PIECES],
-    norm: [u8; MAX_PIECES],
-    factors: [i32; MAX_PIECES],
-    pairs_data: PairsData<'data>,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -28,8 +22,13 @@ enum EncodingType {
     Two,
 }
 
-impl<'data> WdlTable<'data> {
-    pub(crate) fn new(data: &mut DataStream<'data>, material: Material) -> Self {
+impl PawnlessEncoding {
+    /// Read the header of a table, returning its encoding and the layouts of its subtables.
+    pub(super) fn new(
+        data: &mut DataStream,
+        material: Material,
+        kind: Kind,
+    ) -> Result<(Self, [Vec<Layout>; 2]), SyzygyError> {
         let mut encoding_type = EncodingType::Two;
         'outer: for c in Color::ALL {
             for p in Piece::ALL {
@@ -46,217 +45,206 @@ impl<'data> WdlTable<'data> {
 
         let men = material.count() as usize;
 
-        let flags = data.read_u8();
+        let flags = data.read_u8()?;
         let split = flags & 1 != 0;
 
-        assert_eq!(split, !material.is_symmetric());
+        if split == material.is_symmetric() {
+            return Err(SyzygyError::MaterialMismatch);
+        }
+        let both_sides = split && kind == Kind::Wdl;
 
-        let order = data.read_u8();
+        let order = data.read_u8()?;
         let wtm_order = order & 0xF;
         let btm_order = order >> 4;
         let mut wtm_pieces = [ColoredPiece::WhitePawn; MAX_PIECES];
         let mut btm_pieces = [ColoredPiece::WhitePawn; MAX_PIECES];
         for i in 0..men {
-            let p = data.read_u8();
-            wtm_pieces[i] = ColoredPiece::decode(p & 0xF).unwrap();
-            if split {
-                btm_pieces[i] = ColoredPiece::decode(p >> 4).unwrap();
+            let p = data.read_u8()?;
+            wtm_pieces[i] = ColoredPiece::decode(p & 0xF).ok_or(SyzygyError::Corrupt)?;
+            if both_sides {
+                btm_pieces[i] = ColoredPiece::decode(p >> 4).ok_or(SyzygyError::Corrupt)?;
             }
         }
-
-        data.align_to(2);
-
-        let wtm_norm = calculate_norm(men, enc, &wtm_pieces);
-        let (wtm_tbsize, wtm_factors) = calculate_factors(men, wtm_order, &wtm_norm, enc);
-
-        let (wtm_pd, wtm_sizes) = PairsData::create(data, wtm_tbsize, true);
-        let mut wtm = Table {
-            pieces: wtm_pieces,
-            norm: wtm_norm,
-            factors: wtm_factors,
-            pairs_data: wtm_pd,
-        };
-
-        let mut btm = split.then(|| {
-            let btm_norm = calculate_norm(men, enc, &btm_pieces);
-            let (btm_tbsize, btm_factors) = calculate_factors(men, btm_order, &btm_norm, enc);
-            let (btm_pd, btm_sizes) = PairsData::create(data, btm_tbsize, true);
-            (
-                Table {
-                    pieces: btm_pieces,
-                    norm: btm_norm,
-                    factors: btm_factors,
-                    pairs_data: btm_pd,
-                },
-                btm_sizes,
-            )
-        });
-
-        wtm.pairs_data.index_table = data.read_array(wtm_sizes.index_table_size);
-        if let Some((btm, btm_sizes)) = btm.as_mut() {
-            btm.pairs_data.index_table = data.read_array(btm_sizes.index_table_size)
-        }
-
-        wtm.pairs_data.size_table = data.read_array(wtm_sizes.size_table_size);
-        if let Some((btm, btm_sizes)) = btm.as_mut() {
-            btm.pairs_data.size_table = data.read_array(btm_sizes.size_table_size)
+        if !matches_material(&wtm_pieces[..men], material)
+            || both_sides && !matches_material(&btm_pieces[..men], material)
+        {
+            return Err(SyzygyError::MaterialMismatch);
         }
 
-        data.align_to(64);
-        wtm.pairs_data.data = data.read_array(wtm_sizes.data_table_size);
-        if let Some((btm, btm_sizes)) = btm.as_mut() {
-            data.align_to(64);
-            btm.pairs_data.data = data.read_array(btm_sizes.data_table_size)
+        data.align_to(2)?;
+
+        let sides = [(wtm_pieces, wtm_order), (btm_pieces, btm_order)];
+        let mut layouts = [vec![], vec![]];
+        for (side, (pieces, order)) in sides.into_iter().enumerate().take(1 + both_sides as usize) {
+            let norm = calculate_norm(men, enc, &pieces);
+            let (size, factors) =
+                calculate_factors(men, order, &norm, enc).ok_or(SyzygyError::Corrupt)?;
+            layouts[side].push(Layout {
+                pieces,
+                norm,
+                factors,
+                size,
+            });
         }
 
-        WdlTable {
+        let encoding = PawnlessEncoding {
             men,
+            symmetric: !split,
             encoding_type: enc,
-            white_to_move: wtm,
-            black_to_move: btm.map(|(pd, _)| pd),
-        }
-    }
-
-    pub fn read(&self, position: &Board, color_flip: bool) -> Wdl {
-        let color_flip = |c: Color| match color_flip {
-            true => !c,
-            false => c,
-        };
-
-        let table = match color_flip(position.side_to_move()) {
-            Color::White => &self.white_to_move,
-            Color::Black => self.black_to_move.as_ref().unwrap(),
         };
+        Ok((encoding, layouts))
+    }
 
-        let mut piece_squares = [Square::A1; MAX_PIECES];
-
-        let mut i = 0;
-        while i < self.men {
-            let bb = position.pieces(table.pieces[i].piece())
-                & position.colors(color_flip(table.pieces[i].color()));
-            debug_assert!(!bb.is_empty());
-            for sq in bb {
-                piece_squares[i] = sq;
-                i += 1;
-            }
-        }
-
-        match table
-            .pairs_data
-            .lookup(table.index(self.encoding_type, &mut piece_squares[..self.men]))
-        {
-            0 => Wdl::Loss,
-            1 => Wdl::BlessedLoss,
-            2 => Wdl::Draw,
-            3 => Wdl::CursedWin,
-            4 => Wdl::Win,
-            _ => unreachable!(),
-        }
+    /// Collect the squares of the pieces of a position in the order of a subtable, and find the
+    /// index of the position in it.
+    pub(super) fn index(
+        &self,
+        subtable: &Subtable,
+        position: &impl ProbePosition,
+        color_flip: bool,
+        piece_squares: &mut [Square; MAX_PIECES],
+    ) -> Option<u64> {
+        collect_squares(
+            position,
+            &subtable.pieces[..self.men],
+            color_flip,
+            &mut piece_squares[..self.men],
+        )?;
+        index(subtable, self.encoding_type, &mut piece_squares[..self.men])
     }
 }
 
-impl Table<'_> {
-    fn index(&self, enc: EncodingType, piece_squares: &mut [Square]) -> u64 {
-        // We make aggressive use of mirroring here.
-        // If the first piece is not in the bottom-left quadrant, it is mirrored there.
-        if piece_squares[0].file() > File::D {
-            for sq in &mut *piece_squares {
-                *sq = sq.flip_file();
-            }
+/// Returns `None` if the kings are adjacent in a table where they are indexed together.
+fn index(subtable: &Subtable, enc: EncodingType, piece_squares: &mut [Square]) -> Option<u64> {
+    // We make aggressive use of mirroring here.
+    // If the first piece is not in the bottom-left quadrant, it is mirrored there.
+    if piece_squares[0].file() > File::D {
+        for sq in &mut *piece_squares {
+            *sq = sq.flip_file();
         }
-        if piece_squares[0].rank() > Rank::Fourth {
-            for sq in &mut *piece_squares {
-                *sq = sq.flip_rank();
-            }
+    }
+    if piece_squares[0].rank() > Rank::Fourth {
+        for sq in &mut *piece_squares {
+            *sq = sq.flip_rank();
         }
+    }
 
-        // Diagonal mirroring
-        let to_check = match enc {
-            EncodingType::Zero => 3,
-            EncodingType::Two => 2,
-        };
-        for &sq in piece_squares.iter().take(to_check) {
-            match OFF_DIAGONAL[sq as usize] {
-                -1 => break,
-                0 => continue,
-                1 => {
-                    for sq in &mut *piece_squares {
-                        *sq = FLIP_DIAGONAL[*sq as usize];
-                    }
-                    break;
+    // Diagonal mirroring
+    let to_check = match enc {
+        EncodingType::Zero => 3,
+        EncodingType::Two => 2,
+    };
+    for &sq in piece_squares.iter().take(to_check) {
+        match OFF_DIAGONAL[sq as usize] {
+            0 => continue,
+            1 => {
+                for sq in &mut *piece_squares {
+                    *sq = FLIP_DIAGONAL[*sq as usize];
                 }
-                _ => unreachable!(),
+                break;
             }
+            _ => break,
         }
+    }
 
-        let (mut i, mut index) = match enc {
-            EncodingType::Zero => {
-                let i = (piece_squares[1] > piece_squares[0]) as u64;
-                let j = (piece_squares[2] > piece_squares[0]) as u64
-                    + (piece_squares[2] > piece_squares[1]) as u64;
-
-                let index = if OFF_DIAGONAL[piece_squares[0] as usize] != 0 {
-                    0 * 0
-                        + 62 * 63 * TRIANGLE[piece_squares[0] as usize] as u64
-                        + 62 * (piece_squares[1] as u64 - i)
-                        + (piece_squares[2] as u64 - j)
-                } else if OFF_DIAGONAL[piece_squares[1] as usize] != 0 {
-                    62 * 63 * 6
-                        + 62 * 28 * DIAGONAL[piece_squares[0] as usize] as u64
-                        + 62 * LOWER[piece_squares[1] as usize] as u64
-                        + (piece_squares[2] as u64 - j)
-                } else if OFF_DIAGONAL[piece_squares[2] as usize] != 0 {
-                    62 * 63 * 6
-                        + 62 * 28 * 4
-                        + 28 * 7 * DIAGONAL[piece_squares[0] as usize] as u64
-                        + 28 * (DIAGONAL[piece_squares[1] as usize] as u64 - i)
-                        + LOWER[piece_squares[2] as usize] as u64
-                } else {
-                    62 * 63 * 6
-                        + 62 * 28 * 4
-                        + 28 * 7 * 4
-                        + 6 * 7 * DIAGONAL[piece_squares[0] as usize] as u64
-                        + 6 * (DIAGONAL[piece_squares[1] as usize] as u64 - i)
-                        + (DIAGONAL[piece_squares[2] as usize] as u64 - j)
-                };
-                (3, index)
-            }
-            EncodingType::Two => (
-                2,
-                KK_INDEX[TRIANGLE[piece_squares[0] as usize] as usize][piece_squares[1] as usize]
-                    .try_into()
-                    .unwrap(),
-            ),
-        };
+    let (mut i, mut index) = match enc {
+        EncodingType::Zero => {
+            let i = (piece_squares[1] > piece_squares[0]) as u64;
+            let j = (piece_squares[2] > piece_squares[0]) as u64
+                + (piece_squares[2] > piece_squares[1]) as u64;
+
+            let index = if OFF_DIAGONAL[piece_squares[0] as usize] != 0 {
+                0 * 0
+                    + 62 * 63 * TRIANGLE[piece_squares[0] as usize] as u64
+                    + 62 * (piece_squares[1] as u64 - i)
+                    + (piece_squares[2] as u64 - j)
+            } else if OFF_DIAGONAL[piece_squares[1] as usize] != 0 {
+                62 * 63 * 6
+                    + 62 * 28 * DIAGONAL[piece_squares[0] as usize] as u64
+                    + 62 * LOWER[piece_squares[1] as usize] as u64
+                    + (piece_squares[2] as u64 - j)
+            } else if OFF_DIAGONAL[piece_squares[2] as usize] != 0 {
+                62 * 63 * 6
+                    + 62 * 28 * 4
+                    + 28 * 7 * DIAGONAL[piece_squares[0] as usize] as u64
+                    + 28 * (DIAGONAL[piece_squares[1] as usize] as u64 - i)
+                    + LOWER[piece_squares[2] as usize] as u64
+            } else {
+                62 * 63 * 6
+                    + 62 * 28 * 4
+                    + 28 * 7 * 4
+                    + 6 * 7 * DIAGONAL[piece_squares[0] as usize] as u64
+                    + 6 * (DIAGONAL[piece_squares[1] as usize] as u64 - i)
+                    + (DIAGONAL[piece_squares[2] as usize] as u64 - j)
+            };
+            (3, index)
+        }
+        EncodingType::Two => (
+            2,
+            KK_INDEX[TRIANGLE[piece_squares[0] as usize] as usize][piece_squares[1] as usize]
+                .try_into()
+                .ok()?,
+        ),
+    };
 
-        index *= self.factors[0] as u64;
+    index *= subtable.factors[0] as u64;
 
-        while i < piece_squares.len() {
-            let t = self.norm[i] as usize;
-            for j in i..i + t {
-                for k in j + 1..i + t {
-                    if piece_squares[j] > piece_squares[k] {
-                        piece_squares.swap(j, k);
-                    }
+    while i < piece_squares.len() {
+        let t = subtable.norm[i] as usize;
+        for j in i..i + t {
+            for k in j + 1..i + t {
+                if piece_squares[j] > piece_squares[k] {
+                    piece_squares.swap(j, k);
                 }
             }
+        }
 
-            let mut s = 0;
-            for m in i..i + t {
-                let p = piece_squares[m];
-                let mut j = 0;
-                for l in 0..i {
-                    j += (p > piece_squares[l]) as usize;
-                }
-                s += BINOMIAL[m - i][p as usize - j] as u64;
+        let mut s = 0;
+        for m in i..i + t {
+            let p = piece_squares[m];
+            let mut j = 0;
+            for l in 0..i {
+                j += (p > piece_squares[l]) as usize;
             }
-
-            index += s * self.factors[i] as u64;
-            i += t;
+            s += BINOMIAL[m - i][p as usize - j] as u64;
         }
 
-        index
+        index += s * subtable.factors[i] as u64;
+        i += t;
     }
+
+    Some(index)
+}
+
+/// Computes the table size, `norm`, and `factors` of a pawnless table from its piece order, as
+/// stored in the file.
+///
+/// Returns `None` if any piece code is invalid or there are too many pieces.
+#[cfg(feature = "internals")]
+pub fn norm_and_factors(
+    pieces: &[u8],
+    order: u8,
+) -> Option<(usize, [u8; MAX_PIECES], [usize; MAX_PIECES])> {
+    let men = pieces.len();
+    if men > MAX_PIECES {
+        return None;
+    }
+    let mut decoded = [ColoredPiece::WhitePawn; MAX_PIECES];
+    for (d, &p) in decoded.iter_mut().zip(pieces) {
+        *d = ColoredPiece::decode(p)?;
+    }
+
+    let lone_piece = decoded[..men].iter().any(|&p| {
+        p.piece() != Piece::King && decoded[..men].iter().filter(|&&q| q == p).count() == 1
+    });
+    let enc = match lone_piece {
+        true => EncodingType::Zero,
+        false => EncodingType::Two,
+    };
+
+    let norm = calculate_norm(men, enc, &decoded);
+    let (size, factors) = calculate_factors(men, order, &norm, enc)?;
+    Some((size, norm, factors))
 }
 
 fn calculate_norm(
@@ -286,12 +274,15 @@ fn calculate_norm(
     norm
 }
 
+/// Returns `None` if `order` is out of range.
+///
+/// The factors of 7-man tables don't fit in 32 bits, so they are stored as `usize`.
 fn calculate_factors(
     men: usize,
     order: u8,
     norm: &[u8; MAX_PIECES],
     enc: EncodingType,
-) -> (usize, [i32; MAX_PIECES]) {
+) -> Option<(usize, [usize; MAX_PIECES])> {
     let mut factors = [0; MAX_PIECES];
 
     let pivfac = match enc {
@@ -300,13 +291,19 @@ fn calculate_factors(
     };
 
     let mut i: usize = norm[0].into();
-    let mut f = 1;
+    let mut f: usize = 1;
+    let mut placed_pivot = false;
     for k in 0.. {
         if k == order {
-            factors[0] = f.try_into().unwrap();
+            factors[0] = f;
             f *= pivfac;
+            placed_pivot = true;
         } else if i < men {
-            factors[i] = f.try_into().unwrap();
+            // BINOMIAL only covers groups of up to 5 identical pieces.
+            if norm[i] > 5 {
+                return None;
+            }
+            factors[i] = f;
             f *= subfactor(norm[i].into(), 64 - i);
             i += usize::from(norm[i]);
         } else {
@@ -314,5 +311,233 @@ fn calculate_factors(
         }
     }
 
-    (f, factors)
+    placed_pivot.then_some((f, factors))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::pairs::PairsData;
+    use ColoredPiece::*;
+
+    fn table(pieces: &[ColoredPiece], order: u8) -> (Subtable, EncodingType, usize) {
+        let men = pieces.len();
+        let mut all = [WhitePawn; MAX_PIECES];
+        all[..men].copy_from_slice(pieces);
+
+        let lone_piece = pieces
+            .iter()
+            .any(|&p| p.piece() != Piece::King && pieces.iter().filter(|&&q| q == p).count() == 1);
+        let enc = match lone_piece {
+            true => EncodingType::Zero,
+            false => EncodingType::Two,
+        };
+
+        let norm = calculate_norm(men, enc, &all);
+        let (size, factors) = calculate_factors(men, order, &norm, enc).unwrap();
+        let (pairs_data, _) =
+            PairsData::create(&mut DataStream::new(&[0x80, 2]), size, true).unwrap();
+        let table = Subtable {
+            pieces: all,
+            norm,
+            factors,
+            pairs_data,
+            dtz_map: None,
+        };
+        (table, enc, size)
+    }
+
+    fn is_valid(enc: EncodingType, squares: &[Square]) -> bool {
+        let distinct = squares
+            .iter()
+            .enumerate()
+            .all(|(i, sq)| !squares[..i].contains(sq));
+        // KK_INDEX only covers placements where the kings are not adjacent.
+        let kings_apart = enc == EncodingType::Zero
+            || (squares[0].file() as usize).abs_diff(squares[1].file() as usize) > 1
+            || (squares[0].rank() as usize).abs_diff(squares[1].rank() as usize) > 1;
+        distinct && kings_apart
+    }
+
+    fn index(table: &Subtable, enc: EncodingType, squares: &[Square]) -> u64 {
+        super::index(table, enc, &mut squares.to_vec()).unwrap()
+    }
+
+    /// Whether two placements are the same position up to the board symmetries and reordering of
+    /// identical pieces.
+    fn equivalent(pieces: &[ColoredPiece], a: &[Square], b: &[Square]) -> bool {
+        let canonical = |squares: &mut [Square]| {
+            let mut i = 0;
+            while i < pieces.len() {
+                let n = pieces[i..].iter().take_while(|&&p| p == pieces[i]).count();
+                squares[i..i + n].sort();
+                i += n;
+            }
+        };
+
+        let mut b = b.to_vec();
+        canonical(&mut b);
+        for sym in 0..8 {
+            let mut a: Vec<_> = a
+                .iter()
+                .map(|&sq| {
+                    let sq = if sym & 1 != 0 { sq.flip_file() } else { sq };
+                    let sq = if sym & 2 != 0 { sq.flip_rank() } else { sq };
+                    if sym & 4 != 0 {
+                        FLIP_DIAGONAL[sq as usize]
+                    } else {
+                        sq
+                    }
+                })
+                .collect();
+            canonical(&mut a);
+            if a == b {
+                return true;
+            }
+        }
+        false
+    }
+
+    const MATERIALS: &[(&[ColoredPiece], u8)] = &[
+        (&[WhiteKing, WhiteRook, BlackKing], 0),
+        (&[WhiteKing, BlackKing, WhiteRook, WhiteRook], 0),
+        (&[WhiteQueen, WhiteKing, BlackKing, BlackRook], 1),
+        (
+            &[BlackBishop, WhiteKing, BlackKing, WhiteKnight, WhiteKnight],
+            1,
+        ),
+        (
+            &[
+                WhiteKing,
+                BlackKing,
+                WhiteBishop,
+                WhiteBishop,
+                BlackKnight,
+                BlackKnight,
+            ],
+            2,
+        ),
+        (
+            &[
+                BlackQueen, WhiteKing, BlackKing, WhiteQueen, WhiteQueen, WhiteRook,
+            ],
+            2,
+        ),
+    ];
+
+    fn placement() -> impl Strategy<Value = (usize, Vec<Square>)> {
+        (0..MATERIALS.len()).prop_flat_map(|m| {
+            let men = MATERIALS[m].0.len();
+            let squares = prop::collection::vec((0..64usize).prop_map(Square::index), men);
+            (Just(m), squares)
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn index_is_in_range((m, squares) in placement()) {
+            let (pieces, order) = MATERIALS[m];
+            let (table, enc, size) = table(pieces, order);
+            prop_assume!(is_valid(enc, &squares));
+            prop_assert!(index(&table, enc, &squares) < size as u64);
+        }
+
+        #[test]
+        fn index_ignores_file_and_rank_mirroring((m, squares) in placement()) {
+            let (pieces, order) = MATERIALS[m];
+            let (table, enc, _) = table(pieces, order);
+            prop_assume!(is_valid(enc, &squares));
+            let expected = index(&table, enc, &squares);
+            let flipped: Vec<_> = squares.iter().map(|sq| sq.flip_file()).collect();
+            prop_assert_eq!(index(&table, enc, &flipped), expected);
+            let flipped: Vec<_> = squares.iter().map(|sq| sq.flip_rank()).collect();
+            prop_assert_eq!(index(&table, enc, &flipped), expected);
+        }
+
+        #[test]
+        fn index_is_injective(
+            (m, a) in placement(),
+            b in prop::collection::vec((0..64usize).prop_map(Square::index), MAX_PIECES),
+        ) {
+            let (pieces, order) = MATERIALS[m];
+            let (table, enc, _) = table(pieces, order);
+            let b = &b[..pieces.len()];
+            prop_assume!(is_valid(enc, &a) && is_valid(enc, b));
+            if index(&table, enc, &a) == index(&table, enc, b) {
+                prop_assert!(equivalent(pieces, &a, b));
+            }
+        }
+    }
+
+    #[test]
+    fn three_men_index_is_injective() {
+        let pieces = [WhiteKing, WhiteRook, BlackKing];
+        let (table, enc, size) = table(&pieces, 0);
+        let mut seen: HashMap<u64, [Square; 3]> = HashMap::new();
+        for a in Square::ALL {
+            for b in Square::ALL {
+                for c in Square::ALL {
+                    let squares = [a, b, c];
+                    if !is_valid(enc, &squares) {
+                        continue;
+                    }
+                    let i = index(&table, enc, &squares);
+                    assert!(i < size as u64);
+                    if let Some(other) = seen.insert(i, squares) {
+                        assert!(
+                            equivalent(&pieces, other.as_slice(), &squares),
+                            "{other:?} and {squares:?} share index {i}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn seven_men_factors_do_not_overflow() {
+        let pieces = [
+            WhiteKing,
+            WhiteQueen,
+            WhiteRook,
+            WhiteBishop,
+            BlackKing,
+            BlackQueen,
+            BlackRook,
+        ];
+        let (table, enc, size) = table(&pieces, 0);
+        assert!(size > u32::MAX as usize);
+        // The factor of the last piece did not fit in the old `i32` factors.
+        assert!(table.factors.iter().any(|&f| f > i32::MAX as usize));
+
+        let mut squares = [
+            Square::B1,
+            Square::A3,
+            Square::C2,
+            Square::E5,
+            Square::F6,
+            Square::G7,
+            Square::H2,
+        ];
+        let mut seen: HashMap<u64, [Square; 7]> = HashMap::new();
+        for sq in Square::ALL {
+            squares[6] = sq;
+            if !is_valid(enc, &squares) {
+                continue;
+            }
+            let i = index(&table, enc, &squares);
+            assert!(i < size as u64);
+            if let Some(other) = seen.insert(i, squares) {
+                assert!(
+                    equivalent(&pieces, other.as_slice(), &squares),
+                    "{other:?} and {squares:?} share index {i}"
+                );
+            }
+        }
+        assert!(seen.keys().any(|&i| i > u32::MAX as u64));
+    }
 }
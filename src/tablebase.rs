@@ -1,151 +1,657 @@
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "fs")]
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
-use cozy_chess::{BitBoard, Board, Color, Piece, Rank, Square};
+use cozy_chess::{
+    BitBoard, Board, BoardBuilder, CastleRights, Color, GameStatus, Move, Piece, Rank, Square,
+};
 
-use crate::table::WdlTable;
-use crate::{Data, Material, SyzygyError, Wdl, MAX_PIECES};
+use crate::block_cache::BlockCache;
+use crate::bundle;
+#[cfg(feature = "checksum")]
+use crate::checksum::{self, ChecksumResult, Checksums};
+use crate::classify::{self, EndgameClass};
+use crate::instrument::Counters;
+#[cfg(all(unix, feature = "mmap"))]
+use crate::mmap::{self, Advice, MmapOptions};
+use crate::position::has_pseudo_legal_capture;
+#[cfg(any(test, feature = "check-color-flip"))]
+use crate::position::ColorFlipped;
+use crate::root::{self, RootMove, TieBreak};
+use crate::table::{self, DtzRead, DtzTable, Kind, RawTable, WdlTable};
+use crate::transforms;
+use crate::{
+    CastlingWdl, Data, Dtz, EndgameInfo, Fallback, Material, MemoryUsage, MissingTables,
+    PackedPosition, ProbeError, ProbePosition, ProbeResult, ProbeStats, ReadHook, Rule50Wdl,
+    SyzygyError, TableInfo, TableRead, TableSource, ValueSource, Verifier, Wdl, MAX_PIECES,
+};
+
+#[cfg(feature = "fs")]
+pub(crate) mod fs;
+
+/// How many plies [`Tablebase::probe_root`] searches after a move to find its DTZ when the DTZ
+/// table isn't loaded. Each ply multiplies the number of WDL probes by the number of moves.
+const DEGRADED_PLIES: u32 = 3;
+
+/// Loading a file taking longer than this usually means that it is on slow storage, such as a
+/// network mount, which will also make probing slow.
+#[cfg(feature = "log")]
+const SLOW_LOAD: Duration = Duration::from_millis(50);
 
 /// A collection of tablebase files that can be probed.
+///
+/// `Tablebase` is `Send` and `Sync`, and probing only needs a shared reference, so a single
+/// instance can be probed by any number of search threads at once.
 pub struct Tablebase {
     max_pieces: u32,
-    wdl: HashMap<Material, WdlTable>,
+    min_pieces: u32,
+    require_complete_coverage: bool,
+    block_cache: Option<BlockCache>,
+    budget: ByteBudget,
+    counters: Option<Counters>,
+    read_hook: Option<ReadHook>,
+    fallback: Option<Box<dyn Fallback>>,
+    #[cfg(all(unix, feature = "mmap"))]
+    mmap_options: MmapOptions,
+    wdl: HashMap<Material, Slot<WdlTable>>,
+    dtz: HashMap<Material, Slot<DtzTable>>,
+}
+
+struct LoadedTable<T> {
+    table: T,
+    info: TableInfo,
+}
+
+/// The total size of the loaded tables, and the most it may grow to.
+///
+/// The size is atomic since lazily added tables are loaded while probing.
+struct ByteBudget {
+    used: AtomicUsize,
+    limit: usize,
+}
+
+impl ByteBudget {
+    /// Account for a table of `size` bytes, or fail if it doesn't fit.
+    fn reserve(&self, size: usize) -> Result<(), SyzygyError> {
+        self.used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                used.checked_add(size).filter(|&total| total <= self.limit)
+            })
+            .map(drop)
+            .map_err(|_| SyzygyError::TableBytesExceeded)
+    }
+
+    fn release(&self, size: usize) {
+        self.used.fetch_sub(size, Ordering::Relaxed);
+    }
+}
+
+/// A table which has either been loaded, or was recorded by [`Tablebase::add_directory_lazy`] and
+/// is loaded the first time it is probed.
+enum Slot<T> {
+    Loaded(LoadedTable<T>),
+    #[cfg(feature = "fs")]
+    /// The file of the table, and the result of loading it once it has been probed. A file which
+    /// failed to load is not retried.
+    Lazy(PathBuf, OnceLock<Result<LoadedTable<T>, SyzygyError>>),
 }
 
 impl Tablebase {
     pub fn new() -> Tablebase {
         Tablebase {
             max_pieces: 2,
+            min_pieces: 2,
+            require_complete_coverage: false,
+            block_cache: None,
+            budget: ByteBudget {
+                used: AtomicUsize::new(0),
+                limit: usize::MAX,
+            },
+            counters: None,
+            read_hook: None,
+            fallback: None,
+            #[cfg(all(unix, feature = "mmap"))]
+            mmap_options: MmapOptions::default(),
             wdl: HashMap::new(),
+            dtz: HashMap::new(),
         }
     }
 
-    /// Load all of the Syzygy tablebase files in the specified directory.
+    /// Load a Syzygy tablebase file from static memory.
     ///
-    /// Syzygy tablebase files have the extension `rtbw` for WDL data and `rtbz` for DTZ data. See
-    /// [`Tablebase::load_file`][Tablebase::load_file] for more information.
-    pub fn add_directory(&mut self, dir: impl AsRef<Path>) -> Result<(), SyzygyError> {
-        for f in std::fs::read_dir(dir)? {
-            let f = f?;
-            if !f.file_type()?.is_file() {
-                continue;
-            }
-            let path = f.path();
-            if path.extension().and_then(|s| s.to_str()) != Some("rtbw") {
-                continue;
+    /// The material string must be in the standard `K#vK#` format, where `#` is any number of
+    /// piece characters. If this is not correct for the file contents, loading usually fails with
+    /// [`SyzygyError::MaterialMismatch`], but may also succeed and give incorrect results.
+    ///
+    /// Whether the file is a WDL or DTZ table is determined from its contents.
+    pub fn load_bytes_static(
+        &mut self,
+        material: &str,
+        bytes: &'static [u8],
+    ) -> Result<(), SyzygyError> {
+        let material: Material = material.parse()?;
+        self.insert(material, Kind::of(bytes), None, false, || {
+            Ok(Data::StaticBytes(bytes))
+        })
+    }
+
+    /// Load every table in a bundle written by [`BundleWriter`][crate::BundleWriter] from static
+    /// memory, e.g. from `include_bytes!`.
+    ///
+    /// Tables stored as is are used in place, like [`Tablebase::load_bytes_static`]. Compressed
+    /// tables are decompressed into memory, which needs the `zstd` feature; without it they fail
+    /// with [`SyzygyError::UnsupportedCompression`]. Loading stops at the first table which
+    /// fails.
+    pub fn load_bundle_static(&mut self, bundle: &'static [u8]) -> Result<(), SyzygyError> {
+        for entry in bundle::entries(bundle)? {
+            match entry.compressed {
+                true => {
+                    self.load_bytes_owned(&entry.material, bundle::decompress(&entry)?.into())?
+                }
+                false => self.load_bytes_static(&entry.material, entry.data)?,
             }
-            self.load_file(path)?;
         }
         Ok(())
     }
 
-    /// Load a Syzygy tablebase file from the file system.
+    /// Load a Syzygy tablebase file from owned memory.
     ///
-    /// The non-extension part of the filename is used to determine the material of the tablebase
-    /// file, which is information not contained within the Syzygy tablebase file format. It must
-    /// be in the standard `K#vK#` format, where `#` is any number of piece characters. If this is
-    /// not correct for the file contents, using it may result in panics or incorrect results.
+    /// The material string must be in the standard `K#vK#` format, where `#` is any number of
+    /// piece characters. If this is not correct for the file contents, loading usually fails with
+    /// [`SyzygyError::MaterialMismatch`], but may also succeed and give incorrect results.
     ///
-    /// This memory-maps the file.
-    pub fn load_file(&mut self, file: impl AsRef<Path>) -> Result<(), SyzygyError> {
-        let path = file.as_ref();
-
-        let material = path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .ok_or(SyzygyError::UnknownMaterial)?;
+    /// Whether the file is a WDL or DTZ table is determined from its contents.
+    pub fn load_bytes_owned(
+        &mut self,
+        material: &str,
+        bytes: Box<[u8]>,
+    ) -> Result<(), SyzygyError> {
+        let material: Material = material.parse()?;
+        self.insert(material, Kind::of(&bytes), None, false, || {
+            Ok(Data::OwnedBytes(bytes))
+        })
+    }
 
-        self.load_file_with_material(material, path)
+    /// Load a Syzygy tablebase file from owned memory which may be malformed, such as fuzzer input
+    /// or an upload, checking all of its data.
+    ///
+    /// This is the same as [`Tablebase::load_bytes_owned`], except that every compressed block is
+    /// also decoded, so corrupt data is rejected here rather than making probes fail later. Fails
+    /// with [`SyzygyError::Corrupted`] at the first block which can't be decoded. Checking takes
+    /// time proportional to the size of the table, which can be limited with
+    /// [`Tablebase::set_max_table_bytes`].
+    pub fn load_bytes_checked(
+        &mut self,
+        material: &str,
+        bytes: Box<[u8]>,
+    ) -> Result<(), SyzygyError> {
+        let material: Material = material.parse()?;
+        self.insert(material, Kind::of(&bytes), None, true, || {
+            Ok(Data::OwnedBytes(bytes))
+        })
     }
 
-    /// Load a Syzygy tablebase file from the file system.
+    /// Load a Syzygy tablebase file from caller-managed storage. See [`TableSource`].
     ///
-    /// The non-extension part of the filename is used to determine the material of the tablebase
-    /// file, which is information not contained within the Syzygy tablebase file format. It must
-    /// be in the standard `K#vK#` format, where `#` is any number of piece characters. If this is
-    /// not correct for the file contents, using it may result in panics or incorrect results.
+    /// The material string must be in the standard `K#vK#` format, where `#` is any number of
+    /// piece characters. If this is not correct for the file contents, loading usually fails with
+    /// [`SyzygyError::MaterialMismatch`], but may also succeed and give incorrect results.
     ///
-    /// This memory-maps the file.
-    pub fn load_file_with_material(
+    /// Whether the file is a WDL or DTZ table is determined from its contents.
+    pub fn load_source(
         &mut self,
         material: &str,
-        file: impl AsRef<Path>,
+        source: impl TableSource,
     ) -> Result<(), SyzygyError> {
-        let path = file.as_ref();
-
         let material: Material = material.parse()?;
+        self.insert(material, Kind::of(source.bytes()), None, false, || {
+            Ok(Data::Source(Box::new(source)))
+        })
+    }
 
-        assert!(
-            material.count() as usize <= MAX_PIECES,
-            "Cannot load tablebase for positions with more than {} pieces",
-            MAX_PIECES
-        );
+    /// Load a table from the data produced by `data`, unless a table of the same kind is already
+    /// loaded for the material. If `checked` is set, every compressed block is decoded first.
+    fn insert(
+        &mut self,
+        material: Material,
+        kind: Kind,
+        path: Option<&Path>,
+        checked: bool,
+        data: impl FnOnce() -> Result<Data, SyzygyError>,
+    ) -> Result<(), SyzygyError> {
+        if material.count() as usize > MAX_PIECES {
+            return Err(SyzygyError::TooManyPieces);
+        }
+
+        let path = path.map(Path::to_owned);
+        let start = Instant::now();
+        match kind {
+            Kind::Wdl => {
+                if let Entry::Vacant(entry) = self.wdl.entry(material) {
+                    let data = data()?;
+                    let load = match checked {
+                        true => WdlTable::load_checked,
+                        false => WdlTable::load,
+                    };
+                    let table = load_table(&self.budget, data, material, path, start, load)?;
+                    entry.insert(Slot::Loaded(table));
+                    self.update_piece_counts();
+                }
+            }
+            Kind::Dtz => {
+                if let Entry::Vacant(entry) = self.dtz.entry(material) {
+                    let data = data()?;
+                    let load = match checked {
+                        true => DtzTable::load_checked,
+                        false => DtzTable::load,
+                    };
+                    let table = load_table(&self.budget, data, material, path, start, load)?;
+                    entry.insert(Slot::Loaded(table));
+                }
+            }
+        }
+        Ok(())
+    }
 
-        if let Entry::Vacant(entry) = self.wdl.entry(material) {
-            let file = std::fs::File::open(path)?;
-            let mmap = unsafe { memmap::Mmap::map(&file)? };
+    /// Returns the number of pieces in the largest Syzygy tablebase file that has been loaded.
+    pub fn max_pieces(&self) -> u32 {
+        self.max_pieces
+    }
 
-            entry.insert(WdlTable::load(Data::File(mmap), material)?);
-            self.max_pieces = self.max_pieces.max(material.count() as u32);
+    /// Returns the largest number of pieces a position can have to be probed: that of the largest
+    /// loaded table, or any number of pieces the tablebase format supports with a fallback.
+    pub(crate) fn max_probed_pieces(&self) -> u32 {
+        match self.fallback {
+            Some(_) => MAX_PIECES as u32,
+            None => self.max_pieces,
         }
+    }
 
-        Ok(())
+    /// Returns the largest number of pieces such that the tables for every material with at most
+    /// that many pieces are loaded.
+    ///
+    /// Positions with at most this many pieces can always be probed, since the captures searched
+    /// by [`Tablebase::probe_wdl`] only lead to positions with fewer pieces.
+    pub fn min_pieces(&self) -> u32 {
+        self.min_pieces
     }
 
-    /// Load a Syzygy tablebase file from static memory.
+    /// Set whether to only probe positions with at most [`Tablebase::min_pieces`] pieces.
     ///
-    /// The material string must be in the standard `K#vK#` format, where `#` is any number of
-    /// piece characters. If this is not correct for the file contents, using it may result in
-    /// panics or incorrect results.
-    pub fn load_bytes_static(
-        &mut self,
-        material: &str,
-        bytes: &'static [u8],
-    ) -> Result<(), SyzygyError> {
-        let material: Material = material.parse()?;
+    /// When this is set, probes of positions with more pieces return
+    /// [`ProbeError::IncompleteCoverage`] immediately instead of reading their table, which may
+    /// succeed or fail part way through the capture search depending on which smaller tables are
+    /// loaded. This gives probes a predictable cost when only some of the largest tables are
+    /// available. It is not set by default.
+    pub fn set_require_complete_coverage(&mut self, require: bool) {
+        self.require_complete_coverage = require;
+    }
 
-        assert!(
-            material.count() as usize <= MAX_PIECES,
-            "Cannot load tablebase for positions with more than {} pieces",
-            MAX_PIECES
-        );
+    /// Cache up to `blocks` decoded blocks, or disable the cache if `blocks` is zero.
+    ///
+    /// Without the cache, every probe decodes the compressed block containing the position up to
+    /// the position. With it, each block is decoded once and later probes of the block only do a
+    /// binary search, which helps when many threads probe the same positions. The cache is shared
+    /// by every table and is disabled by default. Changing the size discards the cached blocks.
+    pub fn set_block_cache(&mut self, blocks: usize) {
+        self.block_cache = match blocks {
+            0 => None,
+            blocks => Some(BlockCache::new(blocks)),
+        };
+    }
+
+    /// Set how table files loaded from now on are memory-mapped. Tables added with
+    /// [`Tablebase::add_directory_lazy`] are mapped with the options set when they are first
+    /// probed. Files read into memory are not affected. See [`MmapOptions`].
+    #[cfg(all(unix, feature = "mmap"))]
+    pub fn set_mmap_options(&mut self, options: MmapOptions) {
+        self.mmap_options = options;
+    }
 
-        if let Entry::Vacant(entry) = self.wdl.entry(material) {
-            entry.insert(WdlTable::load(Data::StaticBytes(bytes), material)?);
-            self.max_pieces = self.max_pieces.max(material.count() as u32);
+    /// Give `advice` for every memory-mapped table which is loaded, e.g. [`Advice::Random`] after
+    /// warming up the tables with sequential reads.
+    #[cfg(all(unix, feature = "mmap"))]
+    pub fn advise_all(&self, advice: Advice) -> Result<(), SyzygyError> {
+        for (table, _) in self.tables() {
+            if let Data::File(map) = table.data() {
+                mmap::advise(map, advice)?;
+            }
         }
         Ok(())
     }
 
-    /// Load a Syzygy tablebase file from owned memory.
+    /// Returns the tables for materials with at most `pieces` pieces, including kings, which are
+    /// not loaded.
+    pub fn missing_tables(&self, pieces: u32) -> MissingTables {
+        let all = (3..=(pieces as usize).min(MAX_PIECES)).flat_map(materials);
+        self.missing_of(all, true)
+    }
+
+    /// Returns the tables needed to probe positions with the specified material which are not
+    /// loaded.
     ///
-    /// The material string must be in the standard `K#vK#` format, where `#` is any number of
-    /// piece characters. If this is not correct for the file contents, using it may result in
-    /// panics or incorrect results.
-    pub fn load_bytes_owned(
-        &mut self,
-        material: &str,
-        bytes: Box<[u8]>,
-    ) -> Result<(), SyzygyError> {
+    /// WDL probes need the WDL tables of the material and of every material reachable from it by
+    /// captures and promotions, since captures are searched. DTZ probes additionally need the DTZ
+    /// table of the material itself.
+    pub fn missing_tables_for(&self, material: &str) -> Result<MissingTables, SyzygyError> {
         let material: Material = material.parse()?;
+        if material.count() as usize > MAX_PIECES {
+            return Err(SyzygyError::TooManyPieces);
+        }
+        let mut missing = self.missing_of(reachable_materials(material), false);
+        let material = material.canonical();
+        if material != Material::default() && !self.dtz.contains_key(&material) {
+            missing.dtz.push(material.to_string());
+        }
+        Ok(missing)
+    }
 
-        assert!(
-            material.count() as usize <= MAX_PIECES,
-            "Cannot load tablebase for positions with more than {} pieces",
-            MAX_PIECES
-        );
+    /// Returns the materials with a WDL table, including tables added with
+    /// [`Tablebase::add_directory_lazy`] which have not been loaded yet, in no particular order.
+    /// The materials are canonical, e.g. `KRvK` rather than `KvKR`.
+    pub fn loaded_materials(&self) -> impl Iterator<Item = Material> + '_ {
+        self.wdl.keys().copied()
+    }
+
+    /// Whether the WDL table of the material, with either color, is loaded. KvK needs no table,
+    /// so it is always available.
+    pub fn has_material(&self, material: &Material) -> bool {
+        let material = material.canonical();
+        material == Material::default() || self.wdl.contains_key(&material)
+    }
 
-        if let Entry::Vacant(entry) = self.wdl.entry(material) {
-            entry.insert(WdlTable::load(Data::OwnedBytes(bytes), material)?);
-            self.max_pieces = self.max_pieces.max(material.count() as u32);
+    /// Whether the WDL tables needed to probe the position are loaded: those of its material and
+    /// of every material reachable from it by captures and promotions. See
+    /// [`Tablebase::missing_tables_for`].
+    ///
+    /// This doesn't count tables which a [`Fallback`] could answer for, and doesn't check that the
+    /// tables are readable, so a probe may still fail.
+    pub fn can_probe(&self, position: &Board) -> bool {
+        let material = Material::of(position);
+        if ProbePosition::has_castle_rights(position) || material.count() as usize > MAX_PIECES {
+            return false;
         }
-        Ok(())
+        if self.require_complete_coverage && material.count() as u32 > self.min_pieces {
+            return false;
+        }
+        self.missing_of(reachable_materials(material), false)
+            .wdl
+            .is_empty()
     }
 
-    /// Returns the number of pieces in the largest Syzygy tablebase file that has been loaded.
-    pub fn max_pieces(&self) -> u32 {
-        self.max_pieces
+    /// The missing WDL tables, and DTZ tables if `dtz` is set, of `materials`, sorted by piece
+    /// count and then name.
+    fn missing_of(
+        &self,
+        materials: impl IntoIterator<Item = Material>,
+        dtz: bool,
+    ) -> MissingTables {
+        let mut missing = MissingTables::default();
+        let mut materials: Vec<_> = materials.into_iter().collect();
+        materials.sort_by_cached_key(|m| (m.count(), m.to_string()));
+        for m in materials {
+            if !self.wdl.contains_key(&m) {
+                missing.wdl.push(m.to_string());
+            }
+            if dtz && !self.dtz.contains_key(&m) {
+                missing.dtz.push(m.to_string());
+            }
+        }
+        missing
+    }
+
+    /// Limit the total size of the loaded tables to `bytes`, counting both memory-mapped files and
+    /// tables held in memory. Loading a table which would go over the limit fails with
+    /// [`SyzygyError::TableBytesExceeded`], so this should be set before loading any tables.
+    ///
+    /// This guards against a misconfigured directory mapping far more address space than
+    /// intended. There is no limit by default.
+    pub fn set_max_table_bytes(&mut self, bytes: usize) {
+        self.budget.limit = bytes;
+    }
+
+    /// Returns the total size of the loaded tables in bytes.
+    pub fn table_bytes(&self) -> usize {
+        self.budget.used.load(Ordering::Relaxed)
+    }
+
+    /// Set whether to count the table reads done by probes, which are reported by
+    /// [`Tablebase::stats`]. Enabling this resets the counts. Reads by
+    /// [`Tablebase::probe_wdl_quiet`] are not counted.
+    ///
+    /// The counters are behind a lock shared by every thread, so this is meant for finding out
+    /// which tables are probed and how often, rather than for production use. It is not set by
+    /// default.
+    pub fn set_collect_stats(&mut self, collect: bool) {
+        self.counters = collect.then(Counters::default);
+    }
+
+    /// Returns the counts of table reads since [`Tablebase::set_collect_stats`] was enabled, the
+    /// block cache counts since [`Tablebase::set_block_cache`] was last called, and the lazily
+    /// added tables which failed to load.
+    pub fn stats(&self) -> ProbeStats {
+        let mut stats = ProbeStats::default();
+        if let Some(counters) = &self.counters {
+            counters.collect(&mut stats);
+        }
+        if let Some(cache) = &self.block_cache {
+            (stats.block_cache_hits, stats.block_cache_misses) = cache.hits_and_misses();
+        }
+        let wdl = self.wdl.values().filter_map(Slot::load_error);
+        let dtz = self.dtz.values().filter_map(Slot::load_error);
+        stats.quarantined = wdl
+            .chain(dtz)
+            .map(|(path, e)| (path.clone(), e.to_string()))
+            .collect();
+        stats
+    }
+
+    /// Call `hook` after every table read except those by [`Tablebase::probe_wdl_quiet`], on the
+    /// probing thread, for tracing. The hook should be cheap, since it is called several times
+    /// per probe. `None` removes the hook.
+    pub fn set_read_hook(&mut self, hook: Option<ReadHook>) {
+        self.read_hook = hook;
+    }
+
+    /// Ask `fallback` for the values of positions whose tables are not loaded, e.g. to query an
+    /// online tablebase. See [`Fallback`]. With a fallback, positions with more pieces than the
+    /// largest loaded table are probed as well. `None` removes the fallback.
+    pub fn set_fallback(&mut self, fallback: Option<Box<dyn Fallback>>) {
+        self.fallback = fallback;
+    }
+
+    /// Unload the WDL and DTZ tables of a material, e.g. `KRvK`, so that their memory can be
+    /// reclaimed once no probe is using them. Returns whether any table was loaded.
+    ///
+    /// The tables can be loaded again later. Since this needs `&mut self`, a tablebase shared
+    /// between threads is usually rebuilt and replaced instead; see [`SharedTablebase`].
+    ///
+    /// [`SharedTablebase`]: crate::SharedTablebase
+    pub fn unload(&mut self, material: &str) -> Result<bool, SyzygyError> {
+        let material = material.parse::<Material>()?.canonical();
+        let (wdl, dtz) = (self.wdl.len(), self.dtz.len());
+        self.retain_materials(|m| m != material);
+        Ok(self.wdl.len() != wdl || self.dtz.len() != dtz)
+    }
+
+    /// Unload every table.
+    pub fn clear(&mut self) {
+        self.retain_materials(|_| false);
+    }
+
+    /// Keep only the tables whose material, e.g. `KRvK`, satisfies `keep`, unloading the WDL and
+    /// DTZ tables of every other material. See [`Tablebase::unload`].
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.retain_materials(|m| keep(&m.to_string()));
+    }
+
+    fn retain_materials(&mut self, mut keep: impl FnMut(Material) -> bool) {
+        let budget = &self.budget;
+        let mut keep_table = |material, size: Option<usize>| {
+            let kept = keep(material);
+            if let (false, Some(size)) = (kept, size) {
+                budget.release(size);
+            }
+            kept
+        };
+        self.wdl
+            .retain(|&m, slot| keep_table(m, slot.loaded().map(|t| t.info.size)));
+        self.dtz
+            .retain(|&m, slot| keep_table(m, slot.loaded().map(|t| t.info.size)));
+        self.update_piece_counts();
+
+        // Blocks are cached by address, which a newly loaded table could reuse.
+        if let Some(cache) = &mut self.block_cache {
+            cache.clear();
+        }
+    }
+
+    /// Returns how much memory the tables of each endgame use, by material, e.g. `KRvK`.
+    pub fn memory_usage(&self) -> HashMap<String, MemoryUsage> {
+        let mut usage: HashMap<String, MemoryUsage> = HashMap::new();
+        for (table, info) in self.tables() {
+            let endgame = usage.entry(info.material.clone()).or_default();
+            match table.data() {
+                Data::OwnedBytes(_) => endgame.owned += info.size,
+                #[cfg(feature = "mmap")]
+                Data::File(_) => endgame.mapped += info.size,
+                Data::StaticBytes(_) | Data::Source(_) => endgame.external += info.size,
+            }
+        }
+        usage
+    }
+
+    fn update_piece_counts(&mut self) {
+        self.max_pieces = self.wdl.keys().map(|m| m.count() as u32).fold(2, u32::max);
+
+        let mut loaded = [0; MAX_PIECES + 1];
+        for m in self.wdl.keys().filter(|m| m.is_canonical()) {
+            loaded[m.count() as usize] += 1;
+        }
+        self.min_pieces = (3..=MAX_PIECES)
+            .take_while(|&n| loaded[n] == table_count(n))
+            .last()
+            .unwrap_or(2) as u32;
+    }
+
+    /// Returns information about each loaded table.
+    pub fn table_info(&self) -> impl Iterator<Item = &TableInfo> {
+        self.tables().map(|(_, info)| info)
+    }
+
+    /// Returns information about each endgame with a loaded table, with its WDL and DTZ tables
+    /// together, sorted by material.
+    pub fn endgame_info(&self) -> Vec<EndgameInfo<'_>> {
+        let mut endgames: BTreeMap<&str, EndgameInfo> = BTreeMap::new();
+        for info in self.table_info() {
+            let endgame = endgames
+                .entry(&info.material)
+                .or_insert_with(|| EndgameInfo {
+                    material: &info.material,
+                    wdl: None,
+                    dtz: None,
+                });
+            match info.dtz {
+                true => endgame.dtz = Some(info),
+                false => endgame.wdl = Some(info),
+            }
+        }
+        endgames.into_values().collect()
+    }
+
+    /// Check every loaded table against its checksum in `checksums`. The tables are matched to
+    /// the list by their official file names, e.g. `KRvK.rtbw`.
+    ///
+    /// This reads and hashes all of the table data, which takes a while for large tablebases.
+    #[cfg(feature = "checksum")]
+    pub fn verify_checksums(&self, checksums: &Checksums) -> Vec<ChecksumResult> {
+        self.tables()
+            .map(|(table, info)| {
+                let name = checksum::file_name(&info.material, info.dtz);
+                ChecksumResult {
+                    material: info.material.clone(),
+                    dtz: info.dtz,
+                    path: info.path.clone(),
+                    status: checksums.check(&name, table.data().as_ref()),
+                }
+            })
+            .collect()
+    }
+
+    /// Start a background thread which repeatedly walks the compressed blocks of every loaded
+    /// table, checking that they can be decoded. At most `blocks_per_second` blocks are checked
+    /// each second, so the verifier doesn't compete with probing for disk bandwidth.
+    ///
+    /// `on_corrupt` is called on the verifier thread with the table that a corrupt block was found
+    /// in, at most once per table per pass. The thread stops when the returned [`Verifier`] is
+    /// dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `blocks_per_second` is zero.
+    pub fn spawn_verifier(
+        self: &Arc<Self>,
+        blocks_per_second: u32,
+        on_corrupt: impl FnMut(&TableInfo) + Send + 'static,
+    ) -> Verifier {
+        Verifier::spawn(self.clone(), blocks_per_second, on_corrupt)
+    }
+
+    /// Load the index tables and first blocks of the loaded tables into memory, so that the first
+    /// probes don't have to wait for them to be read from disk. This stops once `duration` has
+    /// passed, and returns the number of tables that were warmed up.
+    ///
+    /// WDL tables are warmed up before DTZ tables, since they are probed much more often, and
+    /// tables with fewer pieces go first, since they are reached by more searches.
+    pub fn warmup(&self, duration: Duration) -> usize {
+        let deadline = Instant::now() + duration;
+
+        let wdl = self.wdl.iter().filter_map(|(m, t)| {
+            let t = t.loaded()?;
+            Some((false, m, t.info.size, t.table.raw()))
+        });
+        let dtz = self.dtz.iter().filter_map(|(m, t)| {
+            let t = t.loaded()?;
+            Some((true, m, t.info.size, t.table.raw()))
+        });
+        let mut tables: Vec<_> = wdl.chain(dtz).collect();
+        tables.sort_by_key(|&(dtz, material, size, _)| (dtz, material.count(), size));
+
+        let mut warmed = 0;
+        for (.., table) in tables {
+            if Instant::now() >= deadline {
+                break;
+            }
+            std::hint::black_box(table.touch());
+            warmed += 1;
+        }
+        warmed
+    }
+
+    /// Returns each loaded table along with its information.
+    pub(crate) fn tables(&self) -> impl Iterator<Item = (&RawTable, &TableInfo)> {
+        let wdl = self.wdl.values().filter_map(Slot::loaded);
+        let wdl = wdl.map(|t| (t.table.raw(), &t.info));
+        let dtz = self.dtz.values().filter_map(Slot::loaded);
+        let dtz = dtz.map(|t| (t.table.raw(), &t.info));
+        wdl.chain(dtz)
+    }
+
+    /// Describe the endgame of the specified position.
+    pub fn classify(&self, position: &Board) -> EndgameClass {
+        let material = Material::of(position).canonical();
+        EndgameClass {
+            material: material.to_string(),
+            wdl: material == Material::default() || self.wdl.contains_key(&material),
+            pawnless: position.pieces(Piece::Pawn).is_empty(),
+            opposite_bishops: classify::opposite_bishops(position),
+            fortress_prone: classify::fortress_prone(position),
+        }
     }
 
     /// Find the WDL value of the specified position, and whether the best move is a capture or
@@ -153,9 +659,48 @@ impl Tablebase {
     ///
     /// Note that due to the way Syzygy tablebases work, the Syzygy tablebase files for subsets
     /// of the material in the specified position may also need to be loaded in order for this
-    /// function to return a result.
-    pub fn probe_wdl(&self, position: &Board) -> Option<(Wdl, bool)> {
+    /// function to return a result. The error names the first table found to be missing or
+    /// corrupt.
+    pub fn probe_wdl(&self, position: &Board) -> Result<(Wdl, bool), ProbeError> {
+        self.probe_wdl_with_source(position)
+            .map(|(wdl, capture, _)| (wdl, capture))
+    }
+
+    /// Find the WDL value of the specified position, whether the best move is a capture or en
+    /// passant capture, and where the value came from.
+    ///
+    /// This is the same as [`Tablebase::probe_wdl`], for analysis tools which want to show the
+    /// provenance of the value.
+    pub fn probe_wdl_with_source(
+        &self,
+        position: &Board,
+    ) -> Result<(Wdl, bool, ValueSource), ProbeError> {
+        self.search_wdl(position)
+            .map(|(wdl, capture, source)| (wdl, capture.is_some(), source))
+    }
+
+    /// Find the WDL value of the specified position, and the capture or en passant capture which
+    /// achieves it if the best move is one. See [`ProbeResult`].
+    ///
+    /// This lets engines and adjudicators play the resolving capture at the root without
+    /// searching the captures again.
+    pub fn probe_wdl_result(&self, position: &Board) -> Result<ProbeResult, ProbeError> {
+        let (wdl, best_capture, _) = self.search_wdl(position)?;
+        Ok(ProbeResult { wdl, best_capture })
+    }
+
+    /// Find the WDL value of the specified position, the best move if it is a capture or en
+    /// passant capture, and where the value came from.
+    fn search_wdl(&self, position: &Board) -> Result<(Wdl, Option<Move>, ValueSource), ProbeError> {
         let v = self.read_wdl(position)?;
+        let material = Material::of(position);
+        let source = if material == Material::default() {
+            ValueSource::BuiltIn
+        } else if !self.wdl.contains_key(&material.canonical()) {
+            ValueSource::Fallback
+        } else {
+            ValueSource::Table
+        };
 
         // We need to search the capture moves (See Self::probe_alpha_beta).
         // We also need to know if the position without EP is stalemate, since in that case we
@@ -165,10 +710,9 @@ impl Tablebase {
             Some(f) => Square::new(f, Rank::Sixth.relative_to(position.side_to_move())).bitboard(),
             None => BitBoard::EMPTY,
         };
-        let mut captures = vec![];
-        let mut num_moves_without_ep = 0;
+        let mut captures = Captures::new();
+        let mut has_ep = false;
         position.generate_moves(|mut mvs| {
-            num_moves_without_ep += mvs.len();
             mvs.to &= their_pieces
                 | match mvs.piece {
                     Piece::Pawn => ep_mask,
@@ -176,125 +720,1816 @@ impl Tablebase {
                 };
             for mv in mvs {
                 let ep = mvs.piece == Piece::Pawn && mv.to.bitboard() == ep_mask;
-                if ep {
-                    // uncount en passant move
-                    num_moves_without_ep -= 1;
-                }
-                captures.push((mv, ep));
+                has_ep |= ep;
+                captures.push(mv, ep);
             }
             false
         });
 
+        // Only a position with an en passant capture can be a false stalemate, so we only need to
+        // look for other legal moves in that case. Move generation stops at the first one found.
+        let false_stalemate = has_ep
+            && !position.generate_moves(|mvs| match mvs.piece {
+                Piece::Pawn => !(mvs.to & !ep_mask).is_empty(),
+                _ => !mvs.is_empty(),
+            });
+
         // The TB provides a lower bound on the WDL unless the position is stalemate without
         // en passant, in which case the lower bound is a loss. Additionally, since we need to know
         // if the best move is a capture when it is better than a draw, it is simpler to initialize
         // alpha to draw even if the tablebase WDL is better than a draw.
-        let false_stalemate = num_moves_without_ep == 0 && !captures.is_empty();
         let mut alpha = match false_stalemate {
             true => Wdl::Loss,
             false => Wdl::Draw.min(v),
         };
 
-        let mut best_is_ep = false;
+        // In a false stalemate every legal move is an en passant capture, so the first one is the
+        // best move unless another is better.
+        let mut best = match false_stalemate {
+            true => captures.iter().next(),
+            false => None,
+        };
         let mut best_is_capture = false;
-        for (mv, ep) in captures {
+        let mut searched = false_stalemate;
+        for (mv, ep) in captures.iter() {
             let mut new_pos = position.clone();
             new_pos.play_unchecked(mv);
             let v = -self.probe_alpha_beta(&new_pos, Wdl::Loss, -alpha)?;
             if v > alpha {
                 best_is_capture = v > Wdl::Draw;
-                best_is_ep = ep;
+                best = Some((mv, ep));
                 if v == Wdl::Win {
-                    return Some((Wdl::Win, true));
+                    return Ok((Wdl::Win, Some(mv), ValueSource::CaptureSearch));
                 }
                 alpha = v;
+                searched = true;
             }
         }
 
         if !false_stalemate && v > alpha {
-            Some((v, false))
+            Ok((v, None, source))
         } else {
-            Some((alpha, best_is_capture || best_is_ep || false_stalemate))
+            let capture = best.filter(|&(_, ep)| best_is_capture || ep || false_stalemate);
+            let capture = capture.map(|(mv, _)| mv);
+            // Alpha may still be the value from the table if no capture was better.
+            match searched {
+                true => Ok((alpha, capture, ValueSource::CaptureSearch)),
+                false => Ok((alpha, capture, source)),
+            }
         }
     }
 
-    fn probe_alpha_beta(&self, position: &Board, mut alpha: Wdl, beta: Wdl) -> Option<Wdl> {
-        debug_assert!(position.en_passant().is_none());
+    /// Find the WDL value of the specified position both under the 50 move rule and ignoring it.
+    ///
+    /// Both values come from the same table read; see [`Tablebase::probe_wdl`] for details.
+    pub fn probe_wdl_dual(&self, position: &Board) -> Result<(Wdl, Wdl), ProbeError> {
+        let (wdl, _) = self.probe_wdl(position)?;
+        Ok((wdl, wdl.ignoring_50_move_rule()))
+    }
 
-        // Read the WDL value of the position from the tablebase. This may be worse than the true
-        // WDL of the position; if a position has a capture producing a position with the same WDL
-        // as this position, then the tablebase can achieve better compression by storing a worse
-        // WDL for this position instead.
-        let v = self.read_wdl(position)?;
-        if v > alpha {
-            if v >= beta {
-                return Some(v);
-            }
-            alpha = v;
+    /// Find the WDL value of a position given in any representation, and whether the best move is
+    /// a capture or en passant capture.
+    ///
+    /// Positions without any captures available are looked up directly. Otherwise, the position
+    /// is converted to a [`Board`] so that the captures can be searched; see
+    /// [`Tablebase::probe_wdl`] for details.
+    pub fn probe_wdl_position(
+        &self,
+        position: &impl ProbePosition,
+    ) -> Result<(Wdl, bool), ProbeError> {
+        if position.en_passant().is_none() && !has_pseudo_legal_capture(position) {
+            return self.read_wdl(position).map(|v| (v, false));
         }
+        let board = position.to_board().ok_or(ProbeError::InvalidPosition)?;
+        self.probe_wdl(&board)
+    }
 
-        // To deal with the above complication, we iterate over capture moves recursively to
-        // determine the capture-move WDL, and use that if it is greater than the stored WDL.
-        // This is low depth, as tablebase positions do not have very many pieces available for
-        // capture, and we further limit the extent of the search by doing alpha-beta pruning.
-        let their_pieces = position.colors(!position.side_to_move());
-        let mut captures = vec![];
-        position.generate_moves(|mut mvs| {
-            mvs.to &= their_pieces;
-            captures.extend(mvs);
-            false
-        });
+    /// Read the WDL value of a position which the caller knows has no captures or en passant
+    /// capture available, e.g. from its own move generation, skipping the capture search.
+    ///
+    /// The value is exact for such positions. For other positions it may be worse than the true
+    /// value; see [`Tablebase::probe_wdl`]. This doesn't allocate unless it fails, and since it is
+    /// meant for the hottest loops of a search, the read isn't timed, counted in
+    /// [`Tablebase::stats`], or passed to the read hook.
+    pub fn probe_wdl_quiet(&self, position: &impl ProbePosition) -> Result<Wdl, ProbeError> {
+        self.read_wdl_recorded(position, false)
+    }
 
-        for mv in captures {
-            let mut new_pos = position.clone();
-            new_pos.play_unchecked(mv);
-            let v = -self.probe_alpha_beta(&new_pos, -beta, -alpha)?;
-            if v > alpha {
-                if v >= beta {
-                    return Some(v);
-                }
-                alpha = v;
+    /// Find the WDL value of a position in the 192-bit packed encoding described by
+    /// [`PackedPosition`], and whether the best move is a capture or en passant capture.
+    ///
+    /// Returns [`ProbeError::InvalidPosition`] if the encoding is not valid.
+    pub fn probe_wdl_packed(&self, packed: &[u8; 24]) -> Result<(Wdl, bool), ProbeError> {
+        let position = PackedPosition::from_bytes(packed).ok_or(ProbeError::InvalidPosition)?;
+        self.probe_wdl_position(&position)
+    }
+
+    /// Find the WDL values of many positions, as returned by [`Tablebase::probe_wdl`], with `None`
+    /// for positions which could not be probed.
+    ///
+    /// This is meant for labeling large sets of positions, such as training data. Positions are
+    /// probed grouped by material, so that each table is read in one run, and the index and size
+    /// tables of each table are read into memory before its positions are probed, which avoids
+    /// most of the page faults of probing random positions one by one. With the `rayon` feature,
+    /// the groups are probed in parallel.
+    pub fn probe_wdl_batch(&self, positions: &[Board]) -> Vec<Option<(Wdl, bool)>> {
+        let mut order: Vec<_> = positions
+            .iter()
+            .enumerate()
+            .map(|(i, position)| (Material::of(position).canonical().0, i))
+            .collect();
+        order.sort_unstable();
+        let groups: Vec<_> = order.chunk_by(|a, b| a.0 == b.0).collect();
+
+        let probe_group = |group: &&[([[u8; 5]; 2], usize)]| {
+            if let Some(table) = self.wdl.get(&Material(group[0].0)).and_then(Slot::loaded) {
+                std::hint::black_box(table.table.raw().touch());
             }
+            group
+                .iter()
+                .map(|&(_, i)| (i, self.probe_wdl(&positions[i]).ok()))
+                .collect::<Vec<_>>()
+        };
+        #[cfg(feature = "rayon")]
+        let probed: Vec<_> = {
+            use rayon::prelude::*;
+            groups.par_iter().flat_map_iter(probe_group).collect()
+        };
+        #[cfg(not(feature = "rayon"))]
+        let probed: Vec<_> = groups.iter().flat_map(probe_group).collect();
+
+        let mut results = vec![None; positions.len()];
+        for (i, result) in probed {
+            results[i] = result;
         }
+        results
+    }
 
-        Some(alpha)
+    /// Find the WDL value of the specified position, taking its halfmove clock into account.
+    ///
+    /// [`Tablebase::probe_wdl`] assumes the halfmove clock is zero, so a position it reports as
+    /// won may be drawn by the 50 move rule when the clock is already high. This uses the DTZ to
+    /// check whether the counter can still be reset in time, so for wins and losses with a
+    /// nonzero halfmove clock, it needs the tables needed by [`Tablebase::probe_dtz`]. Other
+    /// values do not depend on the clock.
+    ///
+    /// This fails if a DTZ table is missing. [`Tablebase::probe_wdl_with_rule50`] reports the
+    /// value ignoring the clock instead.
+    pub fn probe_wdl_with_halfmove_clock(&self, position: &Board) -> Result<Wdl, ProbeError> {
+        self.probe_wdl_and_clock(position)?.1
     }
 
-    fn read_wdl(&self, position: &Board) -> Option<Wdl> {
-        // Tablebases do not include positions with castle rights
-        if position.castle_rights(Color::White).short.is_some()
-            || position.castle_rights(Color::White).long.is_some()
-            || position.castle_rights(Color::Black).short.is_some()
-            || position.castle_rights(Color::Black).long.is_some()
-        {
-            return None;
+    /// Find the WDL value of the specified position, taking its halfmove clock into account if the
+    /// needed DTZ tables are loaded.
+    ///
+    /// This is [`Tablebase::probe_wdl_with_halfmove_clock`] for tablebases which may only have
+    /// WDL tables. When a DTZ table is missing, wins and losses are reported as
+    /// [`Rule50Wdl::MaybeWin`] and [`Rule50Wdl::MaybeLoss`] instead of failing, since the value
+    /// is then only known ignoring the 50 move rule.
+    pub fn probe_wdl_with_rule50(&self, position: &Board) -> Result<Rule50Wdl, ProbeError> {
+        match self.probe_wdl_and_clock(position)? {
+            (_, Ok(wdl)) => Ok(Rule50Wdl::Exact(wdl)),
+            (Wdl::Win, Err(ProbeError::MissingTable(_))) => Ok(Rule50Wdl::MaybeWin),
+            (_, Err(ProbeError::MissingTable(_))) => Ok(Rule50Wdl::MaybeLoss),
+            (_, Err(e)) => Err(e),
         }
+    }
 
-        let mut material = Material::default();
-        for c in Color::ALL {
-            for p in Piece::ALL {
-                if p == Piece::King {
-                    continue;
-                }
-                material[(c, p)] = (position.pieces(p) & position.colors(c)).len() as u8;
-            }
+    /// The WDL value of the position ignoring its halfmove clock, and the result of taking the
+    /// clock into account, which only fails if the DTZ is needed and can't be probed.
+    fn probe_wdl_and_clock(
+        &self,
+        position: &Board,
+    ) -> Result<(Wdl, Result<Wdl, ProbeError>), ProbeError> {
+        let (wdl, _) = self.probe_wdl(position)?;
+        if position.halfmove_clock() == 0 || !matches!(wdl, Wdl::Win | Wdl::Loss) {
+            return Ok((wdl, Ok(wdl)));
         }
+        let clock_wdl = self
+            .probe_dtz(position)
+            .map(|(dtz, wdl)| root::with_halfmove_clock(wdl, dtz, position.halfmove_clock()));
+        Ok((wdl, clock_wdl))
+    }
 
-        if material == Material::default() {
-            // KvK
-            return Some(Wdl::Draw);
-        }
+    /// Find the WDL value of the specified position as if it had no castling rights, along with
+    /// how that relates to its true value.
+    ///
+    /// Positions from Chess960 games and analysis often keep castling rights into endgames where
+    /// castling hardly matters, and [`Tablebase::probe_wdl`] refuses them. Castling only gives
+    /// the side with the rights more moves, so the value without castling is a lower bound if
+    /// only the side to move has rights, and an upper bound if only the other side has. If both
+    /// sides have castling rights, the value is neither, and this fails with
+    /// [`ProbeError::CastlingRights`].
+    pub fn probe_wdl_ignoring_castling(&self, position: &Board) -> Result<CastlingWdl, ProbeError> {
+        let can_castle = |c| {
+            let rights = position.castle_rights(c);
+            rights.short.is_some() || rights.long.is_some()
+        };
+        let us = position.side_to_move();
+        let bound = match (can_castle(us), can_castle(!us)) {
+            (false, false) => CastlingWdl::Exact,
+            (true, false) => CastlingWdl::AtLeast,
+            (false, true) => CastlingWdl::AtMost,
+            (true, true) => return Err(ProbeError::CastlingRights),
+        };
 
-        let color_flip = !material.is_canonical()
-            || material.is_symmetric() && position.side_to_move() == Color::Black;
+        let mut builder = BoardBuilder::from_board(position);
+        builder.castle_rights = [CastleRights::EMPTY; Color::NUM];
+        let position = builder.build().map_err(|_| ProbeError::InvalidPosition)?;
+        let (wdl, _) = self.probe_wdl(&position)?;
+        Ok(bound(wdl))
+    }
 
-        let material = match color_flip {
-            true => material.flip(),
-            false => material,
-        };
+    /// Find the distance to zeroing of the specified position, along with its WDL value.
+    ///
+    /// This needs the tables needed by [`Tablebase::probe_wdl`] as well as the DTZ table of the
+    /// position. DTZ tables usually only store one side to move, so for the other side, each move
+    /// is searched and the DTZ tables of the resulting positions are probed instead.
+    pub fn probe_dtz(&self, position: &Board) -> Result<(Dtz, Wdl), ProbeError> {
+        let (wdl, _) = self.probe_wdl(position)?;
+        if wdl == Wdl::Draw {
+            return Ok((Dtz(0), wdl));
+        }
 
-        self.wdl
-            .get(&material)
-            .map(|table| table.read(position, color_flip))
+        // Tables store an arbitrary value for positions where the best move resets the 50 move
+        // counter, so those are found by searching the zeroing moves instead.
+        if self.best_move_zeroes(position, wdl)? {
+            return Ok((dtz_before_zeroing(wdl), wdl));
+        }
+
+        let read = match self.read_dtz(position, wdl) {
+            Err(ProbeError::MissingTable(material)) => {
+                let dtz = self.fallback.as_ref().and_then(|f| f.dtz(position));
+                return dtz
+                    .map(|dtz| (dtz, wdl))
+                    .ok_or(ProbeError::MissingTable(material));
+            }
+            read => read?,
+        };
+        match read {
+            DtzRead::Plies(plies) => {
+                let plies = plies as i32
+                    + match wdl {
+                        Wdl::CursedWin | Wdl::BlessedLoss => 100,
+                        _ => 0,
+                    };
+                Ok((Dtz(sign(wdl) * plies), wdl))
+            }
+            DtzRead::OtherSide => Ok((self.search_dtz(position, wdl)?, wdl)),
+        }
+    }
+
+    /// Probe the WDL value of the position and of its color-flipped mirror, which has the same
+    /// value, and check that they agree. See [`transforms::color_flip`].
+    ///
+    /// The two probes go through the whole pipeline with opposite color flips, and usually read
+    /// different subtables, so a disagreement points to a bug in how positions are mapped to
+    /// tables, or to a corrupt table. This doubles the cost of probing, so it is meant for
+    /// diagnostics. Returns [`ProbeError::ColorFlipMismatch`] if the values differ.
+    pub fn probe_wdl_cross_checked(&self, position: &Board) -> Result<(Wdl, bool), ProbeError> {
+        let flipped = transforms::color_flip(position).ok_or(ProbeError::CastlingRights)?;
+        let result = self.probe_wdl(position)?;
+        match self.probe_wdl(&flipped)? == result {
+            true => Ok(result),
+            false => Err(ProbeError::ColorFlipMismatch),
+        }
+    }
+
+    /// Probe the DTZ value of the position and of its color-flipped mirror, and check that they
+    /// agree. See [`Tablebase::probe_wdl_cross_checked`].
+    pub fn probe_dtz_cross_checked(&self, position: &Board) -> Result<(Dtz, Wdl), ProbeError> {
+        let flipped = transforms::color_flip(position).ok_or(ProbeError::CastlingRights)?;
+        let result = self.probe_dtz(position)?;
+        match self.probe_dtz(&flipped)? == result {
+            true => Ok(result),
+            false => Err(ProbeError::ColorFlipMismatch),
+        }
+    }
+
+    /// Evaluate every legal move of the specified position, sorted best-first.
+    ///
+    /// When the DTZ tables are loaded, the outcome of each move accounts for the halfmove clock
+    /// of the position, so a win that can no longer zero in time is reported as a cursed win.
+    ///
+    /// When the DTZ of a move can't be probed, the move is marked as
+    /// [`degraded`][RootMove::degraded] and a short search with the WDL tables looks for the
+    /// quickest way to reset the 50 move counter while keeping the outcome. If it finds one, the
+    /// DTZ is known and ranks the move as usual. Otherwise the DTZ is `None`, the halfmove clock is
+    /// ignored, and the move is ranked after moves with the same outcome and a known DTZ, so that
+    /// winning moves which zero are still preferred.
+    ///
+    /// An error is returned if the WDL value of any position after a move can't be probed.
+    pub fn probe_root(&self, position: &Board) -> Result<Vec<RootMove>, ProbeError> {
+        self.probe_root_with_tie_break(position, TieBreak::MoveOrder)
+    }
+
+    /// Evaluate every legal move of the specified position, sorted best-first, ordering moves
+    /// with the same outcome and DTZ with `tie_break`. See [`Tablebase::probe_root`].
+    pub fn probe_root_with_tie_break(
+        &self,
+        position: &Board,
+        tie_break: TieBreak,
+    ) -> Result<Vec<RootMove>, ProbeError> {
+        let mut root_moves = vec![];
+        for (mv, zeroes) in moves(position) {
+            let mut child = position.clone();
+            child.play_unchecked(mv);
+            let mut degraded = false;
+            let (wdl, dtz) = if child.status() == GameStatus::Won {
+                (Wdl::Win, Some(Dtz(1)))
+            } else {
+                let wdl = -self.probe_wdl(&child)?.0;
+                let dtz = match zeroes {
+                    true => Some(dtz_before_zeroing(wdl)),
+                    false => match self.probe_dtz(&child) {
+                        Ok((dtz, _)) => {
+                            let dtz = -dtz.0;
+                            Some(Dtz(dtz + dtz.signum()))
+                        }
+                        Err(_) => {
+                            degraded = true;
+                            let plies = self.search_plies_to_zeroing(&child, -wdl, DEGRADED_PLIES);
+                            plies.ok().flatten().map(|plies| dtz_after(wdl, plies + 1))
+                        }
+                    },
+                };
+                match dtz {
+                    Some(dtz) => (
+                        root::with_halfmove_clock(wdl, dtz, position.halfmove_clock()),
+                        Some(dtz),
+                    ),
+                    None => (wdl, None),
+                }
+            };
+            root_moves.push(RootMove {
+                mv,
+                wdl,
+                dtz,
+                degraded,
+                zeroing: zeroes,
+            });
+        }
+        root::sort(&mut root_moves, tie_break);
+        Ok(root_moves)
+    }
+
+    /// Find the best move of the specified position, choosing among equally good moves with
+    /// `tie_break`. Returns `None` if the position has no legal moves.
+    pub fn best_move(
+        &self,
+        position: &Board,
+        tie_break: TieBreak,
+    ) -> Result<Option<RootMove>, ProbeError> {
+        let moves = self.probe_root_with_tie_break(position, tie_break)?;
+        Ok(moves.into_iter().next())
+    }
+
+    /// The number of plies until the 50 move counter is reset in a position with the specified
+    /// WDL value, found by searching with WDL probes, for when its DTZ table isn't loaded. The
+    /// winning side keeps its outcome and zeroes as soon as possible, and the losing side delays
+    /// as long as possible. Returns `None` if this is more than `plies` plies.
+    fn search_plies_to_zeroing(
+        &self,
+        position: &Board,
+        wdl: Wdl,
+        plies: u32,
+    ) -> Result<Option<u32>, ProbeError> {
+        if wdl == Wdl::Draw || plies == 0 {
+            return Ok(None);
+        }
+        let winning = wdl > Wdl::Draw;
+        let mut best: Option<u32> = None;
+        for (mv, zeroes) in moves(position) {
+            let mut child = position.clone();
+            child.play_unchecked(mv);
+            let to_zeroing = if child.status() == GameStatus::Won {
+                Some(1)
+            } else if -self.probe_wdl(&child)?.0 != wdl {
+                continue;
+            } else if zeroes {
+                Some(1)
+            } else {
+                self.search_plies_to_zeroing(&child, -wdl, plies - 1)?
+                    .map(|p| p + 1)
+            };
+            best = match (winning, to_zeroing) {
+                (true, Some(p)) => Some(best.map_or(p, |b| b.min(p))),
+                (true, None) => best,
+                (false, Some(p)) => Some(best.map_or(p, |b| b.max(p))),
+                // The losing side may be able to delay beyond the bound.
+                (false, None) => return Ok(None),
+            };
+        }
+        Ok(best)
+    }
+
+    /// The legal moves of the specified position, with the WDL value after each move from the
+    /// point of view of the side to move.
+    ///
+    /// Moves are probed as the iterator is advanced, so this is cheaper than
+    /// [`Tablebase::probe_root`] when only some of the moves are needed. The WDL value is an error
+    /// if the position after the move can't be probed.
+    pub fn moves_with_wdl<'a>(
+        &'a self,
+        position: &'a Board,
+    ) -> impl Iterator<Item = (Move, Result<Wdl, ProbeError>)> + 'a {
+        moves(position).into_iter().map(move |(mv, _)| {
+            let mut child = position.clone();
+            child.play_unchecked(mv);
+            let wdl = match child.status() {
+                GameStatus::Won => Ok(Wdl::Win),
+                _ => self.probe_wdl(&child).map(|(wdl, _)| -wdl),
+            };
+            (mv, wdl)
+        })
+    }
+
+    /// Whether a capture or pawn move achieves `wdl`, in a position where it is better than a
+    /// draw or every legal move is a capture or pawn move.
+    fn best_move_zeroes(&self, position: &Board, wdl: Wdl) -> Result<bool, ProbeError> {
+        let mut zeroing = vec![];
+        let mut all_zeroing = true;
+        for (mv, zeroes) in moves(position) {
+            match zeroes {
+                true => zeroing.push(mv),
+                false => all_zeroing = false,
+            }
+        }
+
+        let mut best = None;
+        for mv in zeroing {
+            let mut child = position.clone();
+            child.play_unchecked(mv);
+            best = best.max(Some(-self.probe_wdl(&child)?.0));
+        }
+        Ok(best.is_some_and(|best| best >= wdl && (best > Wdl::Draw || all_zeroing)))
+    }
+
+    /// Find the DTZ of a position whose side to move isn't stored in its DTZ table by probing the
+    /// positions after each move.
+    fn search_dtz(&self, position: &Board, wdl: Wdl) -> Result<Dtz, ProbeError> {
+        let mut best: Option<i32> = None;
+        for (mv, zeroes) in moves(position) {
+            let mut child = position.clone();
+            child.play_unchecked(mv);
+            let dtz = if child.status() == GameStatus::Won {
+                1
+            } else if zeroes {
+                // The DTZ of a zeroing move is the DTZ of the position before it.
+                -dtz_before_zeroing(self.probe_wdl(&child)?.0).0
+            } else {
+                let dtz = -self.probe_dtz(&child)?.0 .0;
+                dtz + dtz.signum()
+            };
+            // A winning side zeroes as soon as possible, and a losing side as late as possible.
+            if dtz.signum() == sign(wdl) {
+                best = Some(best.map_or(dtz, |best| best.min(dtz)));
+            }
+        }
+        // With no legal moves, the side to move is mated.
+        Ok(Dtz(best.unwrap_or(-1)))
+    }
+
+    fn read_dtz(&self, position: &impl ProbePosition, wdl: Wdl) -> Result<DtzRead, ProbeError> {
+        let material = Material::of(position);
+        let color_flip = material.color_flip(position.side_to_move());
+        let material = match color_flip {
+            true => material.flip(),
+            false => material,
+        };
+        self.read_table(
+            &self.dtz,
+            Kind::Dtz,
+            material,
+            DtzTable::load,
+            true,
+            |table| table.read(self.block_cache.as_ref(), position, color_flip, wdl),
+        )
+    }
+
+    /// Read the table of `material` from `tables`, which hold tables of the specified kind,
+    /// loading it if it was added lazily. If `record` is set, the read is recorded if statistics
+    /// or a read hook are enabled.
+    fn read_table<T, R>(
+        &self,
+        tables: &HashMap<Material, Slot<T>>,
+        kind: Kind,
+        material: Material,
+        load: fn(Data, Material) -> Result<T, SyzygyError>,
+        record: bool,
+        read: impl FnOnce(&T) -> Option<R>,
+    ) -> Result<R, ProbeError> {
+        let recorded = record && (self.counters.is_some() || self.read_hook.is_some());
+        let start = recorded.then(Instant::now);
+
+        let result = match tables.get(&material) {
+            None => Err(ProbeError::MissingTable(material.to_string())),
+            Some(slot) => slot
+                .get(self, material, load)
+                .and_then(|t| read(&t.table))
+                .ok_or_else(|| ProbeError::CorruptTable(material.to_string())),
+        };
+
+        if let Some(start) = start {
+            let time = start.elapsed();
+            let dtz = kind == Kind::Dtz;
+            if let Some(counters) = &self.counters {
+                counters.record(material, dtz, time, result.as_ref().err());
+            }
+            if let Some(hook) = &self.read_hook {
+                hook(&TableRead {
+                    material: &material.to_string(),
+                    dtz,
+                    time,
+                    error: result.as_ref().err(),
+                });
+            }
+        }
+        result
+    }
+
+    fn probe_alpha_beta(
+        &self,
+        position: &Board,
+        mut alpha: Wdl,
+        beta: Wdl,
+    ) -> Result<Wdl, ProbeError> {
+        debug_assert!(position.en_passant().is_none());
+
+        // Read the WDL value of the position from the tablebase. This may be worse than the true
+        // WDL of the position; if a position has a capture producing a position with the same WDL
+        // as this position, then the tablebase can achieve better compression by storing a worse
+        // WDL for this position instead.
+        let v = self.read_wdl(position)?;
+        if v > alpha {
+            if v >= beta {
+                return Ok(v);
+            }
+            alpha = v;
+        }
+
+        // To deal with the above complication, we iterate over capture moves recursively to
+        // determine the capture-move WDL, and use that if it is greater than the stored WDL.
+        // This is low depth, as tablebase positions do not have very many pieces available for
+        // capture, and we further limit the extent of the search by doing alpha-beta pruning.
+        let their_pieces = position.colors(!position.side_to_move());
+        let mut captures = Captures::new();
+        position.generate_moves(|mut mvs| {
+            mvs.to &= their_pieces;
+            for mv in mvs {
+                captures.push(mv, false);
+            }
+            false
+        });
+
+        for (mv, _) in captures.iter() {
+            let mut new_pos = position.clone();
+            new_pos.play_unchecked(mv);
+            let v = -self.probe_alpha_beta(&new_pos, -beta, -alpha)?;
+            if v > alpha {
+                if v >= beta {
+                    return Ok(v);
+                }
+                alpha = v;
+            }
+        }
+
+        Ok(alpha)
+    }
+
+    fn read_wdl(&self, position: &impl ProbePosition) -> Result<Wdl, ProbeError> {
+        self.read_wdl_recorded(position, true)
+    }
+
+    /// Read the WDL value of a position, recording the read in the statistics and passing it to
+    /// the read hook if `record` is set.
+    fn read_wdl_recorded(
+        &self,
+        position: &impl ProbePosition,
+        record: bool,
+    ) -> Result<Wdl, ProbeError> {
+        let wdl = self.read_wdl_unchecked(position, record);
+
+        // Both views of the position must read the same value, but from different subtables or
+        // with different color flips, so this catches mistakes in either. Only the first read is
+        // recorded, since the probe only asked for one.
+        #[cfg(all(debug_assertions, feature = "check-color-flip"))]
+        debug_assert_eq!(
+            wdl,
+            self.read_wdl_unchecked(&ColorFlipped(position), false),
+            "the color flipped position read a different value"
+        );
+
+        wdl
+    }
+
+    fn read_wdl_unchecked(
+        &self,
+        position: &impl ProbePosition,
+        record: bool,
+    ) -> Result<Wdl, ProbeError> {
+        // Tablebases do not include positions with castle rights
+        if position.has_castle_rights() {
+            return Err(ProbeError::CastlingRights);
+        }
+
+        let pieces = (position.colors(Color::White) | position.colors(Color::Black)).len();
+        if pieces > self.max_probed_pieces() {
+            return Err(ProbeError::TooManyPieces);
+        }
+        if self.require_complete_coverage && pieces > self.min_pieces {
+            return Err(ProbeError::IncompleteCoverage);
+        }
+
+        let material = Material::of(position);
+        if material == Material::default() {
+            // KvK
+            return Ok(Wdl::Draw);
+        }
+
+        let color_flip = material.color_flip(position.side_to_move());
+
+        let material = match color_flip {
+            true => material.flip(),
+            false => material,
+        };
+
+        let read = |table: &WdlTable| table.read(self.block_cache.as_ref(), position, color_flip);
+        let wdl = self.read_table(&self.wdl, Kind::Wdl, material, WdlTable::load, record, read);
+        match (wdl, &self.fallback) {
+            (Err(ProbeError::MissingTable(material)), Some(fallback)) => {
+                let board = position.to_board().ok_or(ProbeError::InvalidPosition)?;
+                fallback
+                    .wdl(&board)
+                    .ok_or(ProbeError::MissingTable(material))
+            }
+            (wdl, _) => wdl,
+        }
+    }
+}
+
+impl<T> Slot<T> {
+    /// The table, if it has been loaded.
+    fn loaded(&self) -> Option<&LoadedTable<T>> {
+        match self {
+            Slot::Loaded(table) => Some(table),
+            #[cfg(feature = "fs")]
+            Slot::Lazy(_, table) => table.get()?.as_ref().ok(),
+        }
+    }
+
+    /// The file of the table and why it couldn't be loaded, if it was added lazily and failed to
+    /// load when it was first probed.
+    fn load_error(&self) -> Option<(&PathBuf, &SyzygyError)> {
+        match self {
+            Slot::Loaded(_) => None,
+            #[cfg(feature = "fs")]
+            Slot::Lazy(path, table) => Some((path, table.get()?.as_ref().err()?)),
+        }
+    }
+
+    /// The table, loading it with `load` if it hasn't been loaded yet. Returns `None` if loading
+    /// failed, now or on an earlier probe.
+    #[cfg_attr(not(feature = "fs"), allow(unused_variables))]
+    fn get(
+        &self,
+        tb: &Tablebase,
+        material: Material,
+        load: fn(Data, Material) -> Result<T, SyzygyError>,
+    ) -> Option<&LoadedTable<T>> {
+        match self {
+            Slot::Loaded(table) => Some(table),
+            #[cfg(feature = "fs")]
+            Slot::Lazy(path, table) => table
+                .get_or_init(|| {
+                    let start = Instant::now();
+                    let data = fs::map_file(&tb.map_options(), material, path);
+                    let result = data.and_then(|d| {
+                        load_table(&tb.budget, d, material, Some(path.clone()), start, load)
+                    });
+                    #[cfg(feature = "log")]
+                    if let Err(e) = &result {
+                        log::warn!("failed to load {}: {e}", path.display());
+                    }
+                    result
+                })
+                .as_ref()
+                .ok(),
+        }
+    }
+}
+
+/// The most captures a position within tablebase range can have. Each of at most `n` pieces can
+/// capture each of at most `MAX_PIECES - 1 - n` pieces other than the king, in up to four ways
+/// when promoting, plus at most two en passant captures, which is below this bound.
+const MAX_CAPTURES: usize = MAX_PIECES * MAX_PIECES;
+
+/// The captures of a position, with whether each is an en passant capture, stored inline so that
+/// probing doesn't allocate.
+struct Captures {
+    moves: [(Move, bool); MAX_CAPTURES],
+    len: usize,
+}
+
+impl Captures {
+    fn new() -> Self {
+        let none = Move {
+            from: Square::A1,
+            to: Square::A1,
+            promotion: None,
+        };
+        Captures {
+            moves: [(none, false); MAX_CAPTURES],
+            len: 0,
+        }
+    }
+
+    /// Add a capture. Captures are only generated after the position has been read from a table,
+    /// so it is within tablebase range and fits.
+    fn push(&mut self, mv: Move, ep: bool) {
+        self.moves[self.len] = (mv, ep);
+        self.len += 1;
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (Move, bool)> + '_ {
+        self.moves[..self.len].iter().copied()
+    }
+}
+
+/// The legal moves of a position, and whether each is a capture or pawn move.
+fn moves(position: &Board) -> Vec<(Move, bool)> {
+    let theirs = position.colors(!position.side_to_move());
+    let mut moves = vec![];
+    position.generate_moves(|mvs| {
+        for mv in mvs {
+            moves.push((mv, mvs.piece == Piece::Pawn || theirs.has(mv.to)));
+        }
+        false
+    });
+    moves
+}
+
+/// The DTZ of a position where the best move resets the 50 move counter.
+fn dtz_before_zeroing(wdl: Wdl) -> Dtz {
+    dtz_after(wdl, 1)
+}
+
+/// The DTZ of a position with the specified WDL value where the 50 move counter is reset after
+/// `plies` plies.
+fn dtz_after(wdl: Wdl, plies: u32) -> Dtz {
+    let plies = plies as i32;
+    Dtz(match wdl {
+        Wdl::Loss => -plies,
+        Wdl::BlessedLoss => -plies - 100,
+        Wdl::Draw => 0,
+        Wdl::CursedWin => plies + 100,
+        Wdl::Win => plies,
+    })
+}
+
+/// The sign of the DTZ of a position with the specified WDL value.
+fn sign(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Loss | Wdl::BlessedLoss => -1,
+        Wdl::Draw => 0,
+        Wdl::CursedWin | Wdl::Win => 1,
+    }
+}
+
+/// The number of tables with the specified number of pieces, including kings.
+fn table_count(pieces: usize) -> usize {
+    materials(pieces).len()
+}
+
+/// The canonical materials with the specified number of pieces, including kings.
+fn materials(pieces: usize) -> Vec<Material> {
+    fn visit(material: &mut Material, slot: usize, left: u8, found: &mut Vec<Material>) {
+        if slot == 10 {
+            if left == 0 && material.is_canonical() {
+                found.push(*material);
+            }
+            return;
+        }
+        for n in 0..=left {
+            material.0[slot / 5][slot % 5] = n;
+            visit(material, slot + 1, left - n, found);
+        }
+        material.0[slot / 5][slot % 5] = 0;
+    }
+    let mut found = vec![];
+    visit(&mut Material::default(), 0, pieces as u8 - 2, &mut found);
+    found
+}
+
+/// The canonical materials reachable from `material` by captures and promotions, including
+/// itself but not KvK.
+fn reachable_materials(material: Material) -> Vec<Material> {
+    let mut found = vec![material.canonical()];
+    found.retain(|&m| m != Material::default());
+    let mut i = 0;
+    while i < found.len() {
+        let m = found[i];
+        i += 1;
+        for c in Color::ALL {
+            for p in Piece::ALL {
+                if p == Piece::King || m[(c, p)] == 0 {
+                    continue;
+                }
+                let mut next = vec![];
+                let mut captured = m;
+                captured[(c, p)] -= 1;
+                next.push(captured);
+                if p == Piece::Pawn {
+                    for promotion in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+                        let mut promoted = captured;
+                        promoted[(c, promotion)] += 1;
+                        next.push(promoted);
+                    }
+                }
+                for n in next {
+                    let n = n.canonical();
+                    if n != Material::default() && !found.contains(&n) {
+                        found.push(n);
+                    }
+                }
+            }
+        }
+    }
+    found
+}
+
+/// Parses a table, recording how long it took to load starting from `start`, and accounts for its
+/// size in `budget`.
+fn load_table<T>(
+    budget: &ByteBudget,
+    data: Data,
+    material: Material,
+    path: Option<PathBuf>,
+    start: Instant,
+    load: fn(Data, Material) -> Result<T, SyzygyError>,
+) -> Result<LoadedTable<T>, SyzygyError> {
+    let size = data.as_ref().len();
+    if size < table::min_size(material) {
+        return Err(SyzygyError::FileTooSmall(path));
+    }
+    let dtz = Kind::of(data.as_ref()) == Kind::Dtz;
+    budget.reserve(size)?;
+    let table = load(data, material).inspect_err(|_| budget.release(size))?;
+    let load_time = start.elapsed();
+
+    #[cfg(feature = "log")]
+    if load_time > SLOW_LOAD {
+        log::warn!(
+            "loading {} took {:?}; it may be on slow storage, which also makes probing slow",
+            path.as_deref()
+                .map_or(material.to_string(), |p| p.display().to_string()),
+            load_time
+        );
+    }
+
+    Ok(LoadedTable {
+        table,
+        info: TableInfo {
+            material: material.to_string(),
+            path,
+            dtz,
+            size,
+            load_time,
+        },
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::is_color_flipped;
+
+    /// A KRvK file with the specified tables for white to move. Black to move is a constant table.
+    ///
+    /// The tables are decoded with 16 byte blocks and an index entry every 1024 values, and use
+    /// the symbols of the golden value test of the pairs decoder.
+    pub(crate) fn krvk(index_table: &[u8], size_table: &[u8], blocks: &[u8]) -> Vec<u8> {
+        let num_blocks = (blocks.len() / 16) as u8;
+        #[rustfmt::skip]
+        let symbols = [
+            0, 4, 10, 0, num_blocks, 0, 0, 0, 3, 2, 2, 0, 0, 0, 5, 0,
+            0x00, 0xF0, 0xFF, 0x04, 0x40, 0x00, 0x02, 0xF0, 0xFF, 0x04, 0xF0, 0xFF, 0x02, 0x30, 0x00,
+            0,
+        ];
+        krvk_file(&symbols, 2, index_table, size_table, blocks)
+    }
+
+    /// A KRvK file with the specified pairs header and tables for white to move, and a constant
+    /// `black_to_move` value.
+    fn krvk_file(
+        symbols: &[u8],
+        black_to_move: u8,
+        index_table: &[u8],
+        size_table: &[u8],
+        blocks: &[u8],
+    ) -> Vec<u8> {
+        // magic, flags, order, pieces (white king, white rook, black king), padding
+        let mut data = vec![0x71, 0xE8, 0x23, 0x5D, 1, 0, 0x66, 0x44, 0xEE, 0];
+        data.extend_from_slice(symbols);
+        data.extend_from_slice(&[0x80, black_to_move]);
+        data.extend_from_slice(index_table);
+        data.extend_from_slice(size_table);
+        data.resize(data.len().next_multiple_of(64), 0);
+        data.extend_from_slice(blocks);
+        data.resize(data.len().next_multiple_of(64), 0);
+        data
+    }
+
+    /// The pairs header of the white to move table of [`winning_krvk`]. The codes are a single
+    /// bit, for symbols 0 and 1, which are runs of 256 wins and 256 cursed wins. Symbols 2 and 3
+    /// are a single win and cursed win, and every symbol after them is the symbol two before it
+    /// paired with itself, doubling its run.
+    #[rustfmt::skip]
+    const WINNING_SYMBOLS: &[u8] = &[
+        // flags, block size, index bits, extra blocks, number of blocks
+        0, 4, 10, 0, 1, 0, 0, 0,
+        // max and min code length, offset for code length 1, number of symbols
+        1, 1, 0, 0, 18, 0,
+        // symbol patterns
+        0x10, 0x00, 0x01, 0x11, 0x10, 0x01,
+        0x04, 0xF0, 0xFF, 0x03, 0xF0, 0xFF,
+        0x02, 0x20, 0x00, 0x03, 0x30, 0x00, 0x04, 0x40, 0x00, 0x05, 0x50, 0x00,
+        0x06, 0x60, 0x00, 0x07, 0x70, 0x00, 0x08, 0x80, 0x00, 0x09, 0x90, 0x00,
+        0x0A, 0xA0, 0x00, 0x0B, 0xB0, 0x00, 0x0C, 0xC0, 0x00, 0x0D, 0xD0, 0x00,
+        0x0E, 0xE0, 0x00, 0x0F, 0xF0, 0x00,
+    ];
+
+    /// The single block of the white to move table of [`winning_krvk`], with one bit for each
+    /// run of 256 values, starting from the most significant bit. The runs alternate every 1024
+    /// values, so the positions in even index entries are wins and those in odd ones are cursed
+    /// wins. The last four bits are unused.
+    const WINNING_RUNS: [u8; 16] = [0x0F; 16];
+
+    /// A KRvK file in which white to move wins or has a cursed win, as set out by
+    /// [`WINNING_RUNS`], and black to move loses.
+    pub(crate) fn winning_krvk() -> Vec<u8> {
+        // Every index entry points at its own value in the single block.
+        let index_table: Vec<u8> = (0..31u16)
+            .flat_map(|i| {
+                let [lo, hi] = (1024 * i + 512).to_le_bytes();
+                [0, 0, 0, 0, lo, hi]
+            })
+            .collect();
+        let runs = 8 * WINNING_RUNS.len() as u16 - 4;
+        let size_table = (256 * runs - 1).to_le_bytes();
+        krvk_file(WINNING_SYMBOLS, 0, &index_table, &size_table, &WINNING_RUNS)
+    }
+
+    /// A tablebase with only [`winning_krvk`] loaded.
+    pub(crate) fn winning_tablebase() -> Tablebase {
+        let mut tb = Tablebase::new();
+        tb.load_bytes_owned("KRvK", winning_krvk().into()).unwrap();
+        tb
+    }
+
+    /// A DTZ table for [`winning_krvk`], which only stores white to move. Wins are 15 plies from
+    /// a zeroing move and cursed wins 41.
+    pub(crate) fn winning_krvk_dtz() -> Vec<u8> {
+        #[rustfmt::skip]
+        let mut data = vec![
+            // magic, flags, order, pieces (white king, white rook, black king), padding
+            0xD7, 0x66, 0x0C, 0xA5, 1, 0, 0x06, 0x04, 0x0E, 0,
+            // a constant table storing white to move, which is mapped and stores losses in plies
+            0x8A, 0,
+            // the maps for wins, losses, cursed wins, and blessed losses
+            1, 7, 1, 9, 1, 20, 1, 30,
+        ];
+        data.resize(64, 0);
+        data
+    }
+
+    /// A KRvK position with the white king, white rook and black king on the specified squares.
+    pub(crate) fn krvk_placement(
+        white_king: Square,
+        rook: Square,
+        black_king: Square,
+        side_to_move: Color,
+    ) -> Placement {
+        Placement {
+            pieces: vec![
+                (Piece::King, Color::White, white_king),
+                (Piece::Rook, Color::White, rook),
+                (Piece::King, Color::Black, black_king),
+            ],
+            side_to_move,
+        }
+    }
+
+    #[derive(Debug)]
+    pub(crate) struct Placement {
+        pub pieces: Vec<(Piece, Color, Square)>,
+        pub side_to_move: Color,
+    }
+
+    impl ProbePosition for Placement {
+        fn pieces(&self, piece: Piece) -> BitBoard {
+            self.pieces
+                .iter()
+                .filter(|&&(p, _, _)| p == piece)
+                .fold(BitBoard::EMPTY, |bb, &(_, _, sq)| bb | sq.bitboard())
+        }
+
+        fn colors(&self, color: Color) -> BitBoard {
+            self.pieces
+                .iter()
+                .filter(|&&(_, c, _)| c == color)
+                .fold(BitBoard::EMPTY, |bb, &(_, _, sq)| bb | sq.bitboard())
+        }
+
+        fn side_to_move(&self) -> Color {
+            self.side_to_move
+        }
+
+        fn en_passant(&self) -> Option<Square> {
+            None
+        }
+
+        fn has_castle_rights(&self) -> bool {
+            false
+        }
+    }
+
+    fn placement() -> impl Strategy<Value = Placement> {
+        let square = (0..64usize).prop_map(Square::index);
+        (square.clone(), square.clone(), square, any::<bool>()).prop_filter_map(
+            "pieces must be on distinct squares",
+            |(k, r, o, white)| {
+                (k != r && k != o && r != o).then_some(Placement {
+                    pieces: vec![
+                        (Piece::King, Color::White, k),
+                        (Piece::Rook, Color::White, r),
+                        (Piece::King, Color::Black, o),
+                    ],
+                    side_to_move: match white {
+                        true => Color::White,
+                        false => Color::Black,
+                    },
+                })
+            },
+        )
+    }
+
+    fn tables() -> impl Strategy<Value = (Vec<u8>, Vec<u8>, Vec<u8>)> {
+        (1..8usize).prop_flat_map(|num_blocks| {
+            (
+                prop::collection::vec(any::<u8>(), 6 * 31),
+                prop::collection::vec(any::<u8>(), 2 * num_blocks),
+                prop::collection::vec(any::<u8>(), 16 * num_blocks),
+            )
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn random_tables_do_not_panic(
+            (index_table, size_table, blocks) in tables(),
+            positions in prop::collection::vec(placement(), 1..16),
+        ) {
+            let mut tb = Tablebase::new();
+            let data = krvk(&index_table, &size_table, &blocks);
+            tb.load_bytes_owned("KRvK", data.into()).unwrap();
+            for pos in &positions {
+                let _ = tb.read_wdl(pos);
+            }
+        }
+
+        #[test]
+        fn color_flipped_positions_read_the_same_value(
+            (index_table, size_table, blocks) in tables(),
+            positions in prop::collection::vec(placement(), 1..16),
+        ) {
+            let mut tb = Tablebase::new();
+            let data = krvk(&index_table, &size_table, &blocks);
+            tb.load_bytes_owned("KRvK", data.into()).unwrap();
+            for pos in &positions {
+                prop_assert_eq!(
+                    tb.read_wdl_unchecked(pos, false),
+                    tb.read_wdl_unchecked(&ColorFlipped(pos), false)
+                );
+            }
+        }
+
+        #[test]
+        fn corrupt_files_do_not_panic(
+            (index_table, size_table, blocks) in tables(),
+            corruption in prop::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 1..4),
+            truncate in any::<prop::sample::Index>(),
+            positions in prop::collection::vec(placement(), 1..16),
+        ) {
+            let mut data = krvk(&index_table, &size_table, &blocks);
+            for (i, v) in corruption {
+                let i = i.index(data.len());
+                data[i] = v;
+            }
+            if truncate.index(4) == 0 {
+                data.truncate(truncate.index(data.len()));
+            }
+
+            let mut tb = Tablebase::new();
+            if tb.load_bytes_owned("KRvK", data.into()).is_ok() {
+                for pos in &positions {
+                    let _ = tb.read_wdl(pos);
+                }
+            }
+        }
+
+        #[test]
+        fn wrong_material_does_not_panic(
+            material in prop::sample::select(vec!["KQvK", "KRRvK", "KRvKR", "KPvK", "KRPvKP"]),
+            (index_table, size_table, blocks) in tables(),
+        ) {
+            let mut tb = Tablebase::new();
+            let data = krvk(&index_table, &size_table, &blocks);
+            prop_assert!(tb.load_bytes_owned(material, data.into()).is_err());
+        }
+    }
+
+    #[test]
+    fn winning_table_is_decoded() {
+        let tb = winning_tablebase();
+        let mut checked = Tablebase::new();
+        checked
+            .load_bytes_checked("KRvK", winning_krvk().into())
+            .unwrap();
+
+        let win = krvk_placement(Square::E1, Square::A7, Square::E8, Color::White);
+        let cursed_win = krvk_placement(Square::A1, Square::B7, Square::E8, Color::White);
+        let loss = krvk_placement(Square::A1, Square::B7, Square::E8, Color::Black);
+        assert_eq!(tb.read_wdl(&win), Ok(Wdl::Win));
+        assert_eq!(tb.read_wdl(&cursed_win), Ok(Wdl::CursedWin));
+        assert_eq!(tb.read_wdl(&loss), Ok(Wdl::Loss));
+        assert_eq!(tb.read_wdl(&ColorFlipped(&cursed_win)), Ok(Wdl::CursedWin));
+        assert_eq!(tb.read_wdl(&ColorFlipped(&loss)), Ok(Wdl::Loss));
+        assert_eq!(checked.read_wdl(&cursed_win), Ok(Wdl::CursedWin));
+    }
+
+    #[test]
+    fn symmetric_materials_flip_with_black_to_move() {
+        let mut position = Placement {
+            pieces: vec![
+                (Piece::King, Color::White, Square::E1),
+                (Piece::Queen, Color::White, Square::D1),
+                (Piece::King, Color::Black, Square::E8),
+                (Piece::Queen, Color::Black, Square::D8),
+            ],
+            side_to_move: Color::White,
+        };
+        assert!(!is_color_flipped(&position));
+        assert!(is_color_flipped(&ColorFlipped(&position)));
+
+        position.side_to_move = Color::Black;
+        assert!(is_color_flipped(&position));
+        assert!(!is_color_flipped(&ColorFlipped(&position)));
+
+        // Asymmetric materials are flipped based on which side is stronger.
+        position.pieces.pop();
+        assert!(!is_color_flipped(&position));
+        assert!(is_color_flipped(&ColorFlipped(&position)));
+        position.side_to_move = Color::White;
+        assert!(!is_color_flipped(&position));
+        assert!(is_color_flipped(&ColorFlipped(&position)));
+    }
+
+    #[test]
+    fn verifier_reports_corrupt_blocks() {
+        // A block of zeros decodes to 42 values, not 256.
+        let mut tb = Tablebase::new();
+        let data = krvk(&[0; 6 * 31], &[255, 0], &[0; 16]);
+        tb.load_bytes_owned("KRvK", data.into()).unwrap();
+
+        let (send, recv) = std::sync::mpsc::channel();
+        let verifier = Arc::new(tb).spawn_verifier(1000, move |info| {
+            let _ = send.send(info.material.clone());
+        });
+        let material = recv.recv_timeout(Duration::from_secs(10));
+        drop(verifier);
+        assert_eq!(material.as_deref(), Ok("KRvK"));
+    }
+
+    #[test]
+    fn checked_loads_reject_corrupt_blocks() {
+        let mut tb = Tablebase::new();
+        let data = krvk(&[0; 6 * 31], &[255, 0], &[0; 16]);
+        let result = tb.load_bytes_checked("KRvK", data.clone().into());
+        // The block is padded to 64 bytes at the end of the file.
+        assert!(matches!(
+            result,
+            Err(SyzygyError::Corrupted { material, offset })
+                if material == "KRvK" && offset == data.len() - 64
+        ));
+        assert_eq!(tb.table_bytes(), 0);
+
+        tb.load_bytes_checked("KRvK", winning_krvk().into())
+            .unwrap();
+        let position = krvk_placement(Square::E1, Square::A7, Square::E8, Color::White);
+        assert_eq!(tb.read_wdl(&position), Ok(Wdl::Win));
+        let position = krvk_placement(Square::A1, Square::B7, Square::E8, Color::White);
+        assert_eq!(tb.read_wdl(&position), Ok(Wdl::CursedWin));
+    }
+
+    #[test]
+    fn missing_tables_include_captures_and_promotions() {
+        let tb = winning_tablebase();
+
+        let missing = tb.missing_tables(3);
+        assert_eq!(missing.wdl, ["KBvK", "KNvK", "KPvK", "KQvK"]);
+        assert_eq!(missing.dtz.len(), 5);
+
+        let missing = tb.missing_tables_for("KvKRP").unwrap();
+        assert_eq!(
+            missing.wdl,
+            ["KBvK", "KNvK", "KPvK", "KQvK", "KQRvK", "KRBvK", "KRNvK", "KRPvK", "KRRvK"]
+        );
+        assert_eq!(missing.dtz, ["KRPvK"]);
+        assert!(!missing.is_empty());
+    }
+
+    #[test]
+    fn loaded_materials_are_listed() {
+        let mut tb = winning_tablebase();
+
+        let krvk: Material = "KRvK".parse().unwrap();
+        assert_eq!(tb.loaded_materials().collect::<Vec<_>>(), [krvk]);
+        assert!(tb.has_material(&krvk));
+        assert!(tb.has_material(&"KvKR".parse().unwrap()));
+        assert!(tb.has_material(&Material::default()));
+        assert!(!tb.has_material(&"KQvK".parse().unwrap()));
+
+        // Only materials with a loaded table are listed, even if they were loaded before.
+        tb.unload("KRvK").unwrap();
+        assert_eq!(tb.loaded_materials().count(), 0);
+        assert!(!tb.has_material(&krvk));
+        assert!(tb.has_material(&Material::default()));
+    }
+
+    #[test]
+    fn table_bytes_are_limited() {
+        let mut tb = Tablebase::new();
+        let data = winning_krvk();
+        tb.set_max_table_bytes(data.len() + 10);
+        tb.load_bytes_owned("KRvK", data.clone().into()).unwrap();
+        assert_eq!(tb.table_bytes(), data.len());
+        assert!(matches!(
+            tb.load_bytes_owned("KQvK", data.clone().into()),
+            Err(SyzygyError::TableBytesExceeded)
+        ));
+
+        // A table which fails to load doesn't count towards the limit.
+        tb.set_max_table_bytes(2 * data.len());
+        assert!(matches!(
+            tb.load_bytes_owned("KPvK", data.clone().into()),
+            Err(SyzygyError::MaterialMismatch)
+        ));
+        assert_eq!(tb.table_bytes(), data.len());
+
+        // Unloading a table frees its bytes for another.
+        tb.set_max_table_bytes(data.len());
+        assert!(tb.unload("KRvK").unwrap());
+        assert_eq!(tb.table_bytes(), 0);
+        tb.load_bytes_owned("KRvK", data.clone().into()).unwrap();
+        assert_eq!(tb.table_bytes(), data.len());
+        let position = krvk_placement(Square::E1, Square::A7, Square::E8, Color::White);
+        assert_eq!(tb.read_wdl(&position), Ok(Wdl::Win));
+        let position = krvk_placement(Square::A1, Square::B7, Square::E8, Color::Black);
+        assert_eq!(tb.read_wdl(&position), Ok(Wdl::Loss));
+    }
+
+    #[test]
+    fn tables_load_from_sources() {
+        let mut tb = Tablebase::new();
+        let data = winning_krvk();
+        let shared: Arc<[u8]> = data.clone().into();
+        tb.load_source("KRvK", shared.clone()).unwrap();
+        assert!(matches!(
+            tb.load_source("KQvK", data),
+            Err(SyzygyError::MaterialMismatch)
+        ));
+        assert_eq!(tb.table_info().count(), 1);
+        assert_eq!(tb.table_bytes(), shared.len());
+
+        // The table is read from the shared bytes.
+        let position = krvk_placement(Square::A1, Square::B7, Square::E8, Color::White);
+        assert_eq!(tb.read_wdl(&position), Ok(Wdl::CursedWin));
+        let position = krvk_placement(Square::E1, Square::A7, Square::E8, Color::White);
+        assert_eq!(tb.read_wdl(&position), Ok(Wdl::Win));
+    }
+
+    #[test]
+    #[cfg(feature = "checksum")]
+    fn tables_are_verified_against_checksums() {
+        use crate::ChecksumStatus;
+
+        let tb = winning_tablebase();
+        let data = winning_krvk();
+
+        let mut checksums = Checksums::new();
+        let results = tb.verify_checksums(&checksums);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].status, ChecksumStatus::Unlisted);
+
+        checksums.insert("KRvK.rtbw", checksum::md5(&data));
+        assert_eq!(
+            tb.verify_checksums(&checksums)[0].status,
+            ChecksumStatus::Match
+        );
+        checksums.insert("KRvK.rtbw", [0; 16]);
+        assert_eq!(
+            tb.verify_checksums(&checksums)[0].status,
+            ChecksumStatus::Mismatch
+        );
+    }
+
+    #[test]
+    fn endgames_combine_their_tables() {
+        let tb = winning_tablebase();
+        let size = winning_krvk().len();
+
+        let endgames = tb.endgame_info();
+        assert_eq!(endgames.len(), 1);
+        assert_eq!(endgames[0].material, "KRvK");
+        let wdl = endgames[0].wdl.as_ref().unwrap();
+        assert_eq!(
+            (wdl.material.as_str(), wdl.dtz, wdl.size),
+            ("KRvK", false, size)
+        );
+        assert!(endgames[0].dtz.is_none());
+        assert_eq!(endgames[0].size(), size);
+    }
+
+    #[test]
+    fn tables_can_be_unloaded() {
+        let mut tb = winning_tablebase();
+        let data = winning_krvk();
+        assert_eq!(tb.memory_usage()["KRvK"].owned, data.len());
+        let position = krvk_placement(Square::A1, Square::B7, Square::E8, Color::White);
+
+        tb.retain(|material| material != "KQvK");
+        assert_eq!(tb.read_wdl(&position), Ok(Wdl::CursedWin));
+        assert!(tb.unload("KvKR").unwrap());
+        assert!(!tb.unload("KRvK").unwrap());
+        assert_eq!(tb.table_info().count(), 0);
+        assert_eq!(tb.table_bytes(), 0);
+        assert_eq!(tb.max_pieces(), 2);
+        assert_eq!(tb.read_wdl(&position), Err(ProbeError::TooManyPieces));
+
+        tb.load_bytes_owned("KRvK", data.into()).unwrap();
+        tb.retain(|material| material != "KRvK");
+        assert_eq!(tb.read_wdl(&position), Err(ProbeError::TooManyPieces));
+
+        tb.load_bytes_owned("KRvK", winning_krvk().into()).unwrap();
+        tb.clear();
+        assert!(tb.memory_usage().is_empty());
+        assert_eq!(tb.read_wdl(&position), Err(ProbeError::TooManyPieces));
+    }
+
+    #[test]
+    fn table_counts() {
+        let counts: Vec<_> = (3..=7).map(table_count).collect();
+        assert_eq!(counts, [5, 30, 110, 365, 1001]);
+    }
+
+    #[test]
+    fn incomplete_coverage_is_not_probed() {
+        let mut tb = winning_tablebase();
+        assert_eq!(tb.max_pieces(), 3);
+        assert_eq!(tb.min_pieces(), 2);
+
+        let mut position = krvk_placement(Square::A1, Square::B7, Square::E8, Color::Black);
+        assert_eq!(tb.read_wdl(&position), Ok(Wdl::Loss));
+        tb.set_require_complete_coverage(true);
+        assert_eq!(tb.read_wdl(&position), Err(ProbeError::IncompleteCoverage));
+
+        // Bare kings are covered without any table.
+        position.pieces.remove(1);
+        assert_eq!(tb.read_wdl(&position), Ok(Wdl::Draw));
+        tb.set_require_complete_coverage(false);
+        assert_eq!(tb.read_wdl(&position), Ok(Wdl::Draw));
+    }
+
+    #[test]
+    fn probe_errors_name_the_missing_table() {
+        let tb = winning_tablebase();
+
+        let mut position = Placement {
+            pieces: vec![
+                (Piece::King, Color::White, Square::A1),
+                (Piece::Queen, Color::Black, Square::H2),
+                (Piece::King, Color::Black, Square::E8),
+            ],
+            side_to_move: Color::White,
+        };
+        assert_eq!(
+            tb.read_wdl(&position),
+            Err(ProbeError::MissingTable("KQvK".to_string()))
+        );
+
+        position
+            .pieces
+            .push((Piece::Rook, Color::White, Square::B1));
+        assert_eq!(tb.read_wdl(&position), Err(ProbeError::TooManyPieces));
+
+        // The loaded table still answers.
+        position = krvk_placement(Square::A1, Square::B7, Square::E8, Color::White);
+        assert_eq!(tb.read_wdl(&position), Ok(Wdl::CursedWin));
+    }
+
+    #[test]
+    fn fallback_answers_for_missing_tables() {
+        struct AlwaysLoss;
+
+        impl Fallback for AlwaysLoss {
+            fn wdl(&self, _: &Board) -> Option<Wdl> {
+                Some(Wdl::Loss)
+            }
+        }
+
+        let mut tb = winning_tablebase();
+        tb.set_fallback(Some(Box::new(AlwaysLoss)));
+
+        // Loaded tables are read rather than asking the fallback.
+        let loaded = krvk_placement(Square::A1, Square::B7, Square::E8, Color::White);
+        assert_eq!(tb.read_wdl(&loaded), Ok(Wdl::CursedWin));
+        let loaded = krvk_placement(Square::E1, Square::A7, Square::E8, Color::White);
+        assert_eq!(tb.read_wdl(&loaded), Ok(Wdl::Win));
+
+        let mut position = Placement {
+            pieces: vec![
+                (Piece::King, Color::White, Square::A1),
+                (Piece::Queen, Color::Black, Square::H2),
+                (Piece::King, Color::Black, Square::E8),
+            ],
+            side_to_move: Color::White,
+        };
+        assert_eq!(tb.read_wdl(&position), Ok(Wdl::Loss));
+
+        // Positions larger than the loaded tables go to the fallback as well.
+        position
+            .pieces
+            .push((Piece::Rook, Color::White, Square::B1));
+        assert_eq!(tb.read_wdl(&position), Ok(Wdl::Loss));
+
+        tb.set_fallback(None);
+        assert_eq!(tb.read_wdl(&position), Err(ProbeError::TooManyPieces));
+    }
+
+    #[test]
+    fn stats_count_reads_and_missing_tables() {
+        let mut tb = winning_tablebase();
+        tb.set_collect_stats(true);
+        let reads = Arc::new(std::sync::Mutex::new(vec![]));
+        tb.set_read_hook(Some(Box::new({
+            let reads = reads.clone();
+            move |read| {
+                let read = (read.material.to_string(), read.error.cloned());
+                reads.lock().unwrap().push(read);
+            }
+        })));
+
+        let mut position = krvk_placement(Square::A1, Square::B7, Square::E8, Color::White);
+        assert_eq!(tb.read_wdl(&position), Ok(Wdl::CursedWin));
+        position.side_to_move = Color::Black;
+        assert_eq!(tb.read_wdl(&position), Ok(Wdl::Loss));
+        position.pieces[1].0 = Piece::Queen;
+        let missing = ProbeError::MissingTable("KQvK".to_string());
+        assert_eq!(tb.read_wdl(&position), Err(missing.clone()));
+
+        let stats = tb.stats();
+        assert_eq!(stats.wdl["KRvK"].reads, 2);
+        assert_eq!(stats.wdl["KRvK"].missing, 0);
+        assert_eq!(stats.wdl["KRvK"].failed, 0);
+        assert_eq!(stats.wdl["KQvK"].missing, 1);
+        assert!(stats.dtz.is_empty());
+        assert_eq!(
+            *reads.lock().unwrap(),
+            [
+                ("KRvK".to_string(), None),
+                ("KRvK".to_string(), None),
+                ("KQvK".to_string(), Some(missing)),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn directories_are_searched_recursively() {
+        let dir = std::env::temp_dir().join(format!("cozy-syzygy-nested-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("3-4-5")).unwrap();
+        std::fs::create_dir_all(dir.join("6-wdl")).unwrap();
+        std::fs::write(dir.join("3-4-5/KRvK.rtbw"), winning_krvk()).unwrap();
+        std::fs::write(dir.join("3-4-5/KQvK.rtbw"), [0; 64]).unwrap();
+        std::fs::write(dir.join("6-wdl/README.txt"), "").unwrap();
+
+        let mut tb = Tablebase::new();
+        let summary = tb.add_directory_lenient(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(summary.loaded, 1);
+        assert_eq!(summary.failed.len(), 1);
+        assert_eq!(summary.failed[0].0.file_name().unwrap(), "KQvK.rtbw");
+        assert_eq!(tb.table_info().count(), 1);
+
+        let position = krvk_placement(Square::E1, Square::A7, Square::E8, Color::White);
+        assert_eq!(tb.read_wdl(&position), Ok(Wdl::Win));
+    }
+
+    #[test]
+    #[cfg(all(unix, feature = "mmap"))]
+    fn mapped_files_take_advice() {
+        let path = std::env::temp_dir().join(format!("cozy-syzygy-advice-{}", std::process::id()));
+        std::fs::create_dir_all(&path).unwrap();
+        let path = path.join("KRvK.rtbw");
+        std::fs::write(&path, winning_krvk()).unwrap();
+
+        let mut tb = Tablebase::new();
+        tb.set_mmap_options(MmapOptions {
+            advice: Some(Advice::Random),
+            lock: false,
+            populate: true,
+        });
+        tb.load_file(&path).unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+        assert_eq!(tb.memory_usage()["KRvK"].mapped, tb.table_bytes());
+        tb.advise_all(Advice::WillNeed).unwrap();
+
+        let position = krvk_placement(Square::E1, Square::A7, Square::E8, Color::White);
+        assert_eq!(tb.read_wdl(&position), Ok(Wdl::Win));
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn lazy_tables_load_on_first_probe() {
+        let dir = std::env::temp_dir().join(format!("cozy-syzygy-lazy-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("KRvK.rtbw"), winning_krvk()).unwrap();
+        std::fs::write(dir.join("KQvK.rtbw"), [0; 64]).unwrap();
+
+        let mut tb = Tablebase::new();
+        tb.add_directory_lazy(&dir).unwrap();
+        assert_eq!(tb.max_pieces(), 3);
+        assert_eq!(tb.table_info().count(), 0);
+
+        let mut position = krvk_placement(Square::A1, Square::B7, Square::E8, Color::White);
+        assert_eq!(tb.read_wdl(&position), Ok(Wdl::CursedWin));
+        assert_eq!(tb.table_info().count(), 1);
+        position.pieces[0].2 = Square::E1;
+        position.pieces[1].2 = Square::A7;
+        assert_eq!(tb.read_wdl(&position), Ok(Wdl::Win));
+
+        position.pieces[1].0 = Piece::Queen;
+        assert_eq!(
+            tb.read_wdl(&position),
+            Err(ProbeError::CorruptTable("KQvK".to_string()))
+        );
+        let quarantined = tb.stats().quarantined;
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(quarantined[0].0, dir.join("KQvK.rtbw"));
+
+        // The file is not loaded again, even once it has been fixed.
+        std::fs::copy(dir.join("KRvK.rtbw"), dir.join("KQvK.rtbw")).unwrap();
+        assert_eq!(
+            tb.read_wdl(&position),
+            Err(ProbeError::CorruptTable("KQvK".to_string()))
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn warmup_stops_at_the_deadline() {
+        let mut tb = winning_tablebase();
+        tb.load_bytes_owned("KRvK", winning_krvk_dtz().into())
+            .unwrap();
+        assert_eq!(tb.warmup(Duration::ZERO), 0);
+        assert_eq!(tb.warmup(Duration::from_secs(10)), 2);
+        assert_eq!(Tablebase::new().warmup(Duration::from_secs(10)), 0);
+    }
+
+    #[test]
+    fn tablebase_is_send_and_sync() {
+        fn check<T: Send + Sync>() {}
+        check::<Tablebase>();
+    }
+
+    #[test]
+    fn too_many_pieces_is_an_error() {
+        let mut tb = Tablebase::new();
+        let result = tb.load_bytes_owned("KQQQQQQQvK", Box::new([]));
+        assert!(matches!(result, Err(SyzygyError::TooManyPieces)));
+    }
+
+    #[test]
+    fn bad_headers_are_errors() {
+        let data = winning_krvk();
+
+        let mut tb = Tablebase::new();
+        let result = tb.load_bytes_owned("KQvK", data.clone().into());
+        assert!(matches!(result, Err(SyzygyError::MaterialMismatch)));
+
+        let result = tb.load_bytes_owned("KRvK", data[..20].into());
+        assert!(matches!(
+            result,
+            Err(SyzygyError::Corrupted { material, offset }) if material == "KRvK" && offset <= 20
+        ));
+    }
+
+    #[test]
+    fn rule50_values_depend_on_the_clock() {
+        let mut tb = winning_tablebase();
+        let board = |fen: String| fen.parse::<Board>().unwrap();
+        let win = |clock: u8| board(format!("4k3/R7/8/8/8/8/8/4K3 w - - {clock} 60"));
+        let cursed_win = |clock: u8| board(format!("4k3/1R6/8/8/8/8/8/K7 w - - {clock} 60"));
+        let loss = |clock: u8| board(format!("4k3/1R6/8/8/8/8/8/K7 b - - {clock} 60"));
+
+        // Without the DTZ table, wins and losses are only known when the clock is zero.
+        assert_eq!(
+            tb.probe_wdl_with_rule50(&win(0)),
+            Ok(Rule50Wdl::Exact(Wdl::Win))
+        );
+        assert_eq!(
+            tb.probe_wdl_with_rule50(&loss(0)),
+            Ok(Rule50Wdl::Exact(Wdl::Loss))
+        );
+        for clock in [1, 84, 85, 86, 99, 100] {
+            assert_eq!(
+                tb.probe_wdl_with_rule50(&win(clock)),
+                Ok(Rule50Wdl::MaybeWin)
+            );
+            assert_eq!(
+                tb.probe_wdl_with_rule50(&loss(clock)),
+                Ok(Rule50Wdl::MaybeLoss)
+            );
+            assert_eq!(
+                tb.probe_wdl_with_rule50(&cursed_win(clock)),
+                Ok(Rule50Wdl::Exact(Wdl::CursedWin))
+            );
+        }
+
+        // The win is 15 plies from zeroing, so it is drawn once the clock passes 85.
+        tb.load_bytes_owned("KRvK", winning_krvk_dtz().into())
+            .unwrap();
+        for (clock, wdl) in [(84, Wdl::Win), (85, Wdl::Win), (86, Wdl::CursedWin)] {
+            assert_eq!(
+                tb.probe_wdl_with_rule50(&win(clock)),
+                Ok(Rule50Wdl::Exact(wdl))
+            );
+            assert_eq!(tb.probe_wdl_with_halfmove_clock(&win(clock)), Ok(wdl));
+        }
+    }
+
+    #[test]
+    fn castling_rights_bound_the_value() {
+        let tb = winning_tablebase();
+        let probe = |fen: &str| tb.probe_wdl_ignoring_castling(&fen.parse().unwrap());
+
+        let position: Board = "4k3/8/8/8/8/8/8/R3K3 w Q - 0 1".parse().unwrap();
+        assert_eq!(tb.probe_wdl(&position), Err(ProbeError::CastlingRights));
+        assert_eq!(
+            tb.probe_wdl_ignoring_castling(&position),
+            Ok(CastlingWdl::AtLeast(Wdl::Win))
+        );
+        assert_eq!(
+            probe("4k3/8/8/8/8/8/8/4K2R b K - 0 1"),
+            Ok(CastlingWdl::AtMost(Wdl::Loss))
+        );
+        assert_eq!(
+            probe("r3k3/8/8/8/8/8/8/4K2R w Kq - 0 1"),
+            Err(ProbeError::CastlingRights)
+        );
+
+        // Without castling rights the value is exact.
+        assert_eq!(
+            probe("4k3/8/8/8/8/8/8/R3K3 w - - 0 1"),
+            Ok(CastlingWdl::Exact(Wdl::Win))
+        );
+        assert_eq!(
+            probe("4k3/8/8/8/8/8/8/4K2R b - - 0 1"),
+            Ok(CastlingWdl::Exact(Wdl::Loss))
+        );
+    }
+
+    #[test]
+    fn probe_results_name_the_capture() {
+        struct AlwaysDraw;
+
+        impl Fallback for AlwaysDraw {
+            fn wdl(&self, _: &Board) -> Option<Wdl> {
+                Some(Wdl::Draw)
+            }
+        }
+
+        let mut tb = winning_tablebase();
+        let probe = |tb: &Tablebase, fen: &str| tb.probe_wdl_result(&fen.parse().unwrap());
+
+        assert_eq!(
+            probe(&tb, "4k3/R7/8/8/8/8/8/4K3 w - - 0 1"),
+            Ok(ProbeResult {
+                wdl: Wdl::Win,
+                best_capture: None,
+            })
+        );
+        assert_eq!(
+            probe(&tb, "4k3/Q7/8/8/8/8/8/4K3 w - - 0 1"),
+            Err(ProbeError::MissingTable("KQvK".to_string()))
+        );
+        let knight = "4k3/8/8/8/3n4/8/8/K2R4 w - - 0 1";
+        assert_eq!(probe(&tb, knight), Err(ProbeError::TooManyPieces));
+
+        // Taking the knight wins, even though the fallback only knows a draw.
+        tb.set_fallback(Some(Box::new(AlwaysDraw)));
+        assert_eq!(
+            probe(&tb, knight),
+            Ok(ProbeResult {
+                wdl: Wdl::Win,
+                best_capture: Some(Move {
+                    from: Square::D1,
+                    to: Square::D4,
+                    promotion: None,
+                }),
+            })
+        );
+    }
+
+    #[test]
+    fn batches_match_single_probes() {
+        let tb = winning_tablebase();
+        // The materials are interleaved, so probing them grouped by material reorders them.
+        let positions: Vec<Board> = [
+            "4k3/1R6/8/8/8/8/8/K7 w - - 0 1",
+            "4k3/Q7/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/R7/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/3R4/8/8/8/8/8/K7 b - - 0 1",
+            "4k3/8/8/8/3n4/8/8/K2R4 w - - 0 1",
+            "4k3/1R6/8/8/8/8/8/K7 b - - 0 1",
+        ]
+        .iter()
+        .map(|fen| fen.parse().unwrap())
+        .collect();
+
+        let expected = [
+            Some((Wdl::CursedWin, false)),
+            None,
+            Some((Wdl::Win, false)),
+            Some((Wdl::Draw, false)),
+            Some((Wdl::Draw, false)),
+            None,
+            Some((Wdl::Loss, false)),
+        ];
+        assert_eq!(tb.probe_wdl_batch(&positions), expected);
+        for (position, expected) in positions.iter().zip(expected) {
+            assert_eq!(tb.probe_wdl(position).ok(), expected);
+        }
+    }
+
+    #[test]
+    fn dual_probes_match_single_probes() {
+        let tb = winning_tablebase();
+        for (fen, dual) in [
+            ("4k3/R7/8/8/8/8/8/4K3 w - - 0 1", (Wdl::Win, Wdl::Win)),
+            ("4k3/1R6/8/8/8/8/8/K7 w - - 0 1", (Wdl::CursedWin, Wdl::Win)),
+            ("4k3/1R6/8/8/8/8/8/K7 b - - 0 1", (Wdl::Loss, Wdl::Loss)),
+        ] {
+            let position: Board = fen.parse().unwrap();
+            let (wdl, _) = tb.probe_wdl(&position).unwrap();
+            assert_eq!((wdl, wdl.ignoring_50_move_rule()), dual);
+            assert_eq!(tb.probe_wdl_dual(&position), Ok(dual));
+        }
+
+        let position: Board = "4k3/Q7/8/8/8/8/8/4K3 w - - 0 1".parse().unwrap();
+        assert_eq!(
+            tb.probe_wdl_dual(&position),
+            Err(ProbeError::MissingTable("KQvK".to_string()))
+        );
+    }
+
+    #[test]
+    fn quiet_probes_are_not_recorded() {
+        let mut tb = winning_tablebase();
+        tb.set_collect_stats(true);
+        let reads = Arc::new(AtomicUsize::new(0));
+        tb.set_read_hook(Some(Box::new({
+            let reads = reads.clone();
+            move |_| {
+                reads.fetch_add(1, Ordering::Relaxed);
+            }
+        })));
+
+        let mut position = krvk_placement(Square::A1, Square::B7, Square::E8, Color::White);
+        assert_eq!(tb.probe_wdl_quiet(&position), Ok(Wdl::CursedWin));
+        position.pieces[1].0 = Piece::Queen;
+        let missing = ProbeError::MissingTable("KQvK".to_string());
+        assert_eq!(tb.probe_wdl_quiet(&position), Err(missing));
+        assert!(tb.stats().wdl.is_empty());
+        assert_eq!(reads.load(Ordering::Relaxed), 0);
+
+        // Other probes of the same position are recorded.
+        assert!(tb.probe_wdl_position(&position).is_err());
+        assert_eq!(tb.stats().wdl["KQvK"].missing, 1);
+        assert_eq!(reads.load(Ordering::Relaxed), 1);
     }
 }
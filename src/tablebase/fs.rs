@@ -0,0 +1,287 @@
+use std::collections::hash_map::Entry;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use super::{Slot, Tablebase};
+#[cfg(feature = "mmap")]
+use crate::table;
+use crate::table::Kind;
+#[cfg(feature = "checksum")]
+use crate::{ChecksumStatus, Checksums};
+use crate::{Data, LoadSummary, Material, SyzygyError, TablebaseBuilder, MAX_PIECES};
+
+impl Tablebase {
+    /// Configure how a tablebase is loaded. See [`TablebaseBuilder`].
+    pub fn builder() -> TablebaseBuilder {
+        TablebaseBuilder::new()
+    }
+
+    /// Load all of the Syzygy tablebase files in the specified directory, returning a summary of
+    /// what was loaded.
+    ///
+    /// Syzygy tablebase files have the extension `rtbw` for WDL data and `rtbz` for DTZ data.
+    /// Subdirectories are searched too, so a download split into directories such as `3-4-5/`,
+    /// `6-wdl/` and `6-dtz/` can be loaded from its parent directory. See
+    /// [`Tablebase::load_file`][Tablebase::load_file] for more information. Files with a material
+    /// that is already loaded are skipped, and files with non-standard names such as `KvKR.rtbw`
+    /// are loaded as the standard material; both are listed in the summary.
+    pub fn add_directory(&mut self, dir: impl AsRef<Path>) -> Result<LoadSummary, SyzygyError> {
+        let mut summary = LoadSummary::default();
+        for path in table_files(dir.as_ref())? {
+            self.load_listed_file(&path, false, &mut summary)?;
+        }
+        Ok(summary)
+    }
+
+    /// Load all of the Syzygy tablebase files in the specified directory, continuing past files
+    /// which fail to load.
+    ///
+    /// Unlike [`Tablebase::add_directory`], a bad file does not prevent the remaining files from
+    /// being loaded. The files which failed are listed in the returned summary; an error is only
+    /// returned if the directory itself could not be read.
+    pub fn add_directory_lenient(
+        &mut self,
+        dir: impl AsRef<Path>,
+    ) -> Result<LoadSummary, SyzygyError> {
+        let mut summary = LoadSummary::default();
+        for path in table_files(dir.as_ref())? {
+            if let Err(e) = self.load_listed_file(&path, false, &mut summary) {
+                summary.failed.push((path, e));
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Record the Syzygy tablebase files in the specified directory and its subdirectories
+    /// without loading them.
+    ///
+    /// Each file is memory-mapped and parsed the first time a position with its material is
+    /// probed, which makes startup much faster and uses less virtual memory when only a few of
+    /// the tables are probed. Files are only checked when they are loaded, so a bad file causes
+    /// probes of its material to fail with [`ProbeError::CorruptTable`][crate::ProbeError::CorruptTable] instead of an error
+    /// here. Such a file is only tried once, and is listed in
+    /// [`ProbeStats::quarantined`][crate::ProbeStats::quarantined]. Tables which have not been
+    /// loaded yet are not included in [`Tablebase::table_info`].
+    pub fn add_directory_lazy(&mut self, dir: impl AsRef<Path>) -> Result<(), SyzygyError> {
+        for path in table_files(dir.as_ref())? {
+            let material = material_of_file(&path)?;
+            if material.count() as usize > MAX_PIECES {
+                return Err(SyzygyError::TooManyPieces);
+            }
+
+            match kind_of_file(&path) {
+                Kind::Wdl => {
+                    if let Entry::Vacant(entry) = self.wdl.entry(material) {
+                        entry.insert(Slot::Lazy(path, OnceLock::new()));
+                        self.update_piece_counts();
+                    }
+                }
+                Kind::Dtz => {
+                    if let Entry::Vacant(entry) = self.dtz.entry(material) {
+                        entry.insert(Slot::Lazy(path, OnceLock::new()));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Load a Syzygy tablebase file from the file system.
+    ///
+    /// The non-extension part of the filename is used to determine the material of the tablebase
+    /// file, which is information not contained within the Syzygy tablebase file format. It must
+    /// be in the standard `K#vK#` format, where `#` is any number of piece characters. If this is
+    /// not correct for the file contents, loading usually fails with
+    /// [`SyzygyError::MaterialMismatch`], but may also succeed and give incorrect results.
+    ///
+    /// Files with the extension `rtbz` are loaded as DTZ tables, and any other file is loaded as a
+    /// WDL table. This memory-maps the file, or reads it into memory if the `mmap` feature is
+    /// disabled.
+    pub fn load_file(&mut self, file: impl AsRef<Path>) -> Result<(), SyzygyError> {
+        let path = file.as_ref();
+
+        let material = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or(SyzygyError::UnknownMaterial)?;
+
+        self.load_file_with_material(material, path)
+    }
+
+    /// Load a Syzygy tablebase file from the file system.
+    ///
+    /// The non-extension part of the filename is used to determine the material of the tablebase
+    /// file, which is information not contained within the Syzygy tablebase file format. It must
+    /// be in the standard `K#vK#` format, where `#` is any number of piece characters. If this is
+    /// not correct for the file contents, loading usually fails with
+    /// [`SyzygyError::MaterialMismatch`], but may also succeed and give incorrect results.
+    ///
+    /// Files with the extension `rtbz` are loaded as DTZ tables, and any other file is loaded as a
+    /// WDL table. This memory-maps the file, or reads it into memory if the `mmap` feature is
+    /// disabled.
+    pub fn load_file_with_material(
+        &mut self,
+        material: &str,
+        file: impl AsRef<Path>,
+    ) -> Result<(), SyzygyError> {
+        let path = file.as_ref();
+
+        self.load_path(material.parse()?, path, false)
+    }
+
+    /// Load a Syzygy tablebase file from the file system after checking it against its checksum
+    /// in `checksums`, which is looked up by the name of the file.
+    ///
+    /// The file is read into memory to be hashed, so it is not memory-mapped. Fails with
+    /// [`SyzygyError::ChecksumMismatch`] if the file does not match or is not listed. See
+    /// [`Tablebase::load_file`] for how the material and kind of the table are determined.
+    #[cfg(feature = "checksum")]
+    pub fn load_file_verified(
+        &mut self,
+        file: impl AsRef<Path>,
+        checksums: &Checksums,
+    ) -> Result<(), SyzygyError> {
+        let path = file.as_ref();
+        let material = material_of_file(path)?;
+        let name = path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .ok_or(SyzygyError::UnknownMaterial)?;
+        self.insert(material, kind_of_file(path), Some(path), false, || {
+            let data = std::fs::read(path)?;
+            match checksums.check(name, &data) {
+                ChecksumStatus::Match => Ok(Data::OwnedBytes(data.into())),
+                _ => Err(SyzygyError::ChecksumMismatch),
+            }
+        })
+    }
+
+    /// Load a file found in a directory, recording it in `summary`. Files with non-standard names
+    /// are loaded as the standard material, so that they are found when probing.
+    pub(crate) fn load_listed_file(
+        &mut self,
+        path: &Path,
+        read_into_memory: bool,
+        summary: &mut LoadSummary,
+    ) -> Result<(), SyzygyError> {
+        let material = material_of_file(path)?.canonical();
+        if path.file_stem().and_then(|s| s.to_str()) != Some(&material.to_string()) {
+            summary.non_canonical.push(path.to_owned());
+        }
+        let loaded = match kind_of_file(path) {
+            Kind::Wdl => self.wdl.contains_key(&material),
+            Kind::Dtz => self.dtz.contains_key(&material),
+        };
+        if loaded {
+            summary.duplicates.push(path.to_owned());
+            return Ok(());
+        }
+
+        self.load_path(material, path, read_into_memory)?;
+        summary.loaded += 1;
+        summary.loaded_by_pieces[material.count() as usize] += 1;
+        summary.bytes += std::fs::metadata(path)?.len();
+        Ok(())
+    }
+
+    /// Load a table file, either memory-mapping it or reading it into memory.
+    pub(crate) fn load_path(
+        &mut self,
+        material: Material,
+        path: &Path,
+        read_into_memory: bool,
+    ) -> Result<(), SyzygyError> {
+        let options = self.map_options();
+        self.insert(
+            material,
+            kind_of_file(path),
+            Some(path),
+            false,
+            || match read_into_memory {
+                true => Ok(Data::OwnedBytes(std::fs::read(path)?.into())),
+                false => map_file(&options, material, path),
+            },
+        )
+    }
+
+    /// The options files are memory-mapped with.
+    pub(super) fn map_options(&self) -> MapOptions {
+        #[cfg(all(unix, feature = "mmap"))]
+        return self.mmap_options;
+    }
+}
+
+/// How files are memory-mapped, which can only be configured on unix.
+#[cfg(all(unix, feature = "mmap"))]
+pub(super) type MapOptions = crate::MmapOptions;
+#[cfg(not(all(unix, feature = "mmap")))]
+pub(super) type MapOptions = ();
+
+/// The WDL and DTZ tablebase files in the specified directory and its subdirectories.
+pub(crate) fn table_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut dirs = vec![dir.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        for f in std::fs::read_dir(dir)? {
+            let f = f?;
+            // Symbolic links are not followed, so this can't loop.
+            let file_type = f.file_type()?;
+            if file_type.is_dir() {
+                dirs.push(f.path());
+                continue;
+            }
+            if !file_type.is_file() {
+                continue;
+            }
+            let path = f.path();
+            if matches!(
+                path.extension().and_then(|s| s.to_str()),
+                Some("rtbw" | "rtbz")
+            ) {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// The material of a table file, based on its name.
+pub(crate) fn material_of_file(path: &Path) -> Result<Material, SyzygyError> {
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or(SyzygyError::UnknownMaterial)?
+        .parse()
+}
+
+/// Whether a file holds a WDL or DTZ table, based on its extension.
+pub(crate) fn kind_of_file(path: &Path) -> Kind {
+    match path.extension().and_then(|s| s.to_str()) {
+        Some("rtbz") => Kind::Dtz,
+        _ => Kind::Wdl,
+    }
+}
+
+/// Memory-map a table file.
+#[cfg(feature = "mmap")]
+#[cfg_attr(not(unix), allow(unused_variables))]
+pub(super) fn map_file(
+    options: &MapOptions,
+    material: Material,
+    path: &Path,
+) -> Result<Data, SyzygyError> {
+    let file = std::fs::File::open(path)?;
+    // Check this before mapping, since empty files can't be memory-mapped.
+    if file.metadata()?.len() < table::min_size(material) as u64 {
+        return Err(SyzygyError::FileTooSmall(Some(path.to_owned())));
+    }
+    let map = unsafe { memmap::Mmap::map(&file)? };
+    #[cfg(unix)]
+    options.apply(&map)?;
+    Ok(Data::File(map))
+}
+
+/// Read a table file into memory, since memory mapping is disabled.
+#[cfg(not(feature = "mmap"))]
+pub(super) fn map_file(_: &MapOptions, _: Material, path: &Path) -> Result<Data, SyzygyError> {
+    Ok(Data::OwnedBytes(std::fs::read(path)?.into()))
+}
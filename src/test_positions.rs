@@ -0,0 +1,85 @@
+use crate::Wdl;
+
+/// A position with a known WDL value. See [`test_positions`].
+#[derive(Clone, Copy, Debug)]
+pub struct TestPosition {
+    pub fen: &'static str,
+    /// The material of the table containing the position, e.g. `KRvK`. Tables for subsets of
+    /// this material may also be needed to probe the position.
+    pub material: &'static str,
+    /// The WDL value for the side to move.
+    pub wdl: Wdl,
+    /// Whether the best move is a capture or en passant capture.
+    pub capture: bool,
+    pub category: TestCategory,
+}
+
+/// Why a [`TestPosition`] is included.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TestCategory {
+    Pawnless,
+    Pawnful,
+    /// A position which has caused incorrect results or panics in the past.
+    Regression,
+}
+
+/// Positions with known WDL values, for smoke testing tablebase files and probing code.
+///
+/// The results are those of [`Tablebase::probe_wdl`][crate::Tablebase::probe_wdl].
+pub fn test_positions() -> &'static [TestPosition] {
+    TEST_POSITIONS
+}
+
+const fn pos(
+    fen: &'static str,
+    material: &'static str,
+    wdl: Wdl,
+    capture: bool,
+    category: TestCategory,
+) -> TestPosition {
+    TestPosition {
+        fen,
+        material,
+        wdl,
+        capture,
+        category,
+    }
+}
+
+use TestCategory::*;
+use Wdl::*;
+
+#[rustfmt::skip]
+const TEST_POSITIONS: &[TestPosition] = &[
+    pos("4k3/8/8/1R6/4K3/8/8/8 w - - 0 1", "KRvK", Win, false, Pawnless),
+    pos("4k3/8/8/1R6/4K3/8/8/8 b - - 0 1", "KRvK", Loss, false, Pawnless),
+    pos("7k/5KR1/8/8/8/8/8/8 b - - 0 1", "KRvK", Draw, false, Pawnless),
+    pos("7k/5KR1/8/8/8/8/8/r7 w - - 0 1", "KRvKR", Draw, false, Pawnless),
+    pos("7k/5KR1/8/8/8/8/8/r7 b - - 0 1", "KRvKR", Win, false, Pawnless),
+    pos("7k/5KR1/8/8/8/8/8/6r1 w - - 0 1", "KRvKR", Win, true, Pawnless),
+    pos("7k/5KR1/8/8/8/2R5/8/r7 w - - 0 1", "KRRvKR", Win, false, Pawnless),
+    pos("7k/2Q2K2/8/8/8/3r4/8/r7 w - - 0 1", "KRRvKQ", Win, false, Pawnless),
+    pos("7k/2Q2K2/8/8/8/3r4/8/r7 b - - 0 1", "KRRvKQ", Win, false, Pawnless),
+    pos("7k/2Q2K2/4n3/4r3/8/8/8/8 w - - 0 1", "KRNvKQ", Win, true, Pawnless),
+    pos("7k/2Q2K2/4n3/4r3/8/8/8/8 b - - 0 1", "KRNvKQ", Win, true, Pawnless),
+    pos("8/6B1/8/8/B7/8/K2k4/2n5 w - - 0 1", "KBBvKN", CursedWin, false, Pawnless),
+    pos("8/6B1/8/8/B7/1K6/3kn3/8 b - - 0 1", "KBBvKN", BlessedLoss, false, Pawnless),
+
+    pos("4k3/8/8/3K4/7p/8/8/8 w - - 0 1", "KPvK", Draw, false, Pawnful),
+    pos("8/8/8/4K3/1P5p/8/8/4k3 b - - 0 1", "KPvKP", Win, false, Pawnful),
+    pos("8/8/8/4K3/1P5p/8/8/4k3 w - - 0 1", "KPvKP", Win, false, Pawnful),
+    pos("8/8/3K4/6R1/7k/7p/8/8 b - - 0 1", "KRvKP", Win, true, Pawnful),
+    pos("8/6B1/8/8/B7/8/K1pk4/8 b - - 0 1", "KBBvKP", BlessedLoss, false, Pawnful),
+    // Stalemate if no en passant
+    pos("K7/1r6/1k6/1Pp5/8/8/8/8 w - c6 0 1", "KRPvKP", Loss, true, Pawnful),
+    pos("K7/1r6/1k6/1Pp5/8/8/8/8 w - - 0 1", "KRPvKP", Draw, false, Pawnful),
+    // En passant is the best move but not the only move
+    pos("5K2/8/5k2/8/pP6/B7/8/8 b - b3 0 1", "KBPvKP", Draw, true, Pawnful),
+    pos("5K2/8/5k2/8/pP6/B7/8/8 b - - 0 1", "KBPvKP", Loss, false, Pawnful),
+
+    pos("8/8/5p2/5k2/8/4K3/6Qp/8 w - - 0 78", "KPPvKQ", Win, true, Regression),
+    pos("6k1/KPr1P3/8/8/8/8/8/8 b - - 0 69", "KPPvKR", Draw, false, Regression),
+    pos("8/2k5/4p3/5p2/3K4/8/7p/8 b - - 0 68", "KPPPvK", Win, false, Regression),
+    pos("8/8/3k4/4p3/8/8/6p1/1K4B1 w - - 0 57", "KPPvKB", Draw, false, Regression),
+    pos("RR6/8/8/8/3kn3/8/6K1/8 w - - 16 9", "KRRvKN", Win, false, Regression),
+];
@@ -0,0 +1,183 @@
+//! The symmetries of the board used by the prober, for tools which need to agree with it, such as
+//! bitbase builders.
+//!
+//! Mirroring the files preserves the rules of chess for positions without castling rights.
+//! Mirroring the ranks or along the diagonal additionally needs the position to have no pawns.
+
+use cozy_chess::{Board, BoardBuilder, Color, Piece, Square};
+
+use crate::constants::FLIP_DIAGONAL;
+use crate::ProbePosition;
+
+/// A symmetry of the board: mirroring the files, mirroring the ranks, and mirroring along the
+/// A1-H8 diagonal, applied in that order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Transform {
+    pub flip_file: bool,
+    pub flip_rank: bool,
+    pub flip_diagonal: bool,
+}
+
+impl Transform {
+    /// The eight symmetries of the board, starting with the identity.
+    pub const ALL: [Transform; 8] = {
+        let mut all = [Transform {
+            flip_file: false,
+            flip_rank: false,
+            flip_diagonal: false,
+        }; 8];
+        let mut i = 0;
+        while i < 8 {
+            all[i] = Transform {
+                flip_file: i & 1 != 0,
+                flip_rank: i & 2 != 0,
+                flip_diagonal: i & 4 != 0,
+            };
+            i += 1;
+        }
+        all
+    };
+
+    pub fn square(self, sq: Square) -> Square {
+        let sq = if self.flip_file { sq.flip_file() } else { sq };
+        let sq = if self.flip_rank { sq.flip_rank() } else { sq };
+        match self.flip_diagonal {
+            true => FLIP_DIAGONAL[sq as usize],
+            false => sq,
+        }
+    }
+
+    /// Whether this symmetry can be applied to positions with pawns, which is only the case if it
+    /// keeps every square on its rank.
+    pub fn allows_pawns(self) -> bool {
+        !self.flip_rank && !self.flip_diagonal
+    }
+
+    /// Apply this symmetry to a position. Returns `None` if the position has castling rights, or
+    /// has pawns and [`Transform::allows_pawns`] is false.
+    pub fn apply(self, board: &Board) -> Option<Board> {
+        if !self.allows_pawns() && !board.pieces(Piece::Pawn).is_empty() {
+            return None;
+        }
+        map(board, |sq| self.square(sq), false)
+    }
+}
+
+/// Mirror the files of a position. Returns `None` if the position has castling rights.
+pub fn flip_file(board: &Board) -> Option<Board> {
+    Transform {
+        flip_file: true,
+        ..Transform::default()
+    }
+    .apply(board)
+}
+
+/// Mirror the ranks of a position. Returns `None` if the position has castling rights or pawns.
+pub fn flip_rank(board: &Board) -> Option<Board> {
+    Transform {
+        flip_rank: true,
+        ..Transform::default()
+    }
+    .apply(board)
+}
+
+/// Mirror a position along the A1-H8 diagonal. Returns `None` if the position has castling rights
+/// or pawns.
+pub fn flip_diagonal(board: &Board) -> Option<Board> {
+    Transform {
+        flip_diagonal: true,
+        ..Transform::default()
+    }
+    .apply(board)
+}
+
+/// Swap the colors of a position and mirror its ranks, so that the other side is to move. This is
+/// how positions are viewed when [`is_color_flipped`][crate::is_color_flipped] is true. Returns
+/// `None` if the position has castling rights.
+pub fn color_flip(board: &Board) -> Option<Board> {
+    map(board, Square::flip_rank, true)
+}
+
+fn map(board: &Board, square: impl Fn(Square) -> Square, swap_colors: bool) -> Option<Board> {
+    if board.has_castle_rights() {
+        return None;
+    }
+    let color = |c: Color| match swap_colors {
+        true => !c,
+        false => c,
+    };
+
+    let mut builder = BoardBuilder::empty();
+    for c in Color::ALL {
+        for p in Piece::ALL {
+            for sq in board.colored_pieces(c, p) {
+                *builder.square_mut(square(sq)) = Some((p, color(c)));
+            }
+        }
+    }
+    builder.side_to_move = color(board.side_to_move());
+    builder.en_passant = ProbePosition::en_passant(board).map(&square);
+    builder.halfmove_clock = board.halfmove_clock();
+    builder.fullmove_number = board.fullmove_number();
+    builder.build().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::generate::random_positions;
+    use crate::tablebase::tests::winning_tablebase;
+    use crate::Wdl;
+
+    #[test]
+    fn symmetric_positions_have_the_same_value() {
+        let tb = winning_tablebase();
+        let mut seen = Vec::new();
+        for board in random_positions("KRvK", 6).unwrap().take(200) {
+            let wdl = tb.probe_wdl(&board).unwrap();
+            seen.push(wdl.0);
+            for t in Transform::ALL {
+                let image = t.apply(&board).unwrap();
+                assert_eq!(tb.probe_wdl(&image).unwrap(), wdl, "{t:?} {board}");
+            }
+            let flipped = color_flip(&board).unwrap();
+            assert_eq!(flipped.side_to_move(), !board.side_to_move());
+            assert_eq!(tb.probe_wdl(&flipped).unwrap(), wdl, "{board}");
+        }
+        // The fixture gives a mix of values, so a symmetry which moved a position to another index
+        // would be noticed.
+        for wdl in [Wdl::Win, Wdl::CursedWin, Wdl::Loss] {
+            assert!(seen.contains(&wdl), "{wdl:?}");
+        }
+    }
+
+    #[test]
+    fn pawns_only_allow_file_mirroring() {
+        let board: Board = "4k3/8/8/8/8/8/1P6/4K3 w - - 0 1".parse().unwrap();
+        for t in Transform::ALL {
+            assert_eq!(t.apply(&board).is_some(), t.allows_pawns(), "{t:?}");
+        }
+        assert!(flip_file(&board).is_some());
+        assert!(flip_rank(&board).is_none());
+        assert!(flip_diagonal(&board).is_none());
+        assert!(color_flip(&board).is_some());
+
+        let castling: Board = "4k3/8/8/8/8/8/8/4K2R w K - 0 1".parse().unwrap();
+        assert!(Transform::ALL.iter().all(|t| t.apply(&castling).is_none()));
+        assert!(color_flip(&castling).is_none());
+    }
+
+    #[test]
+    fn symmetries_are_distinct() {
+        let images: HashSet<_> = Transform::ALL
+            .iter()
+            .map(|t| t.square(Square::B1))
+            .collect();
+        assert_eq!(images.len(), 8);
+        assert_eq!(Transform::ALL[0], Transform::default());
+        assert!(images.contains(&Square::A2));
+        assert!(images.contains(&Square::H7));
+    }
+}
@@ -0,0 +1,79 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::{TableInfo, Tablebase};
+
+/// A background thread checking that the loaded tables can be decoded, started by
+/// [`Tablebase::spawn_verifier`].
+///
+/// The thread is stopped when this is dropped.
+pub struct Verifier {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Verifier {
+    pub(crate) fn spawn(
+        tb: Arc<Tablebase>,
+        blocks_per_second: u32,
+        mut on_corrupt: impl FnMut(&TableInfo) + Send + 'static,
+    ) -> Verifier {
+        assert!(blocks_per_second > 0, "blocks_per_second must be positive");
+        let interval = Duration::from_secs(1) / blocks_per_second;
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = std::thread::spawn({
+            let stop = stop.clone();
+            move || {
+                // Waits for the next block to be due, returning false if the verifier is stopped.
+                let mut next = Instant::now();
+                let mut wait = || {
+                    next += interval;
+                    while !stop.load(Ordering::Relaxed) {
+                        match next.checked_duration_since(Instant::now()) {
+                            Some(left) => std::thread::park_timeout(left),
+                            None => return true,
+                        }
+                    }
+                    false
+                };
+
+                loop {
+                    for (table, info) in tb.tables() {
+                        for block in 0..table.blocks() {
+                            if !wait() {
+                                return;
+                            }
+                            if !table.check_block(block) {
+                                on_corrupt(info);
+                                break;
+                            }
+                        }
+                    }
+                    // Don't spin when there is nothing to check.
+                    if !wait() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        Verifier {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for Verifier {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            // A panic in the callback has already been reported by the thread.
+            let _ = thread.join();
+        }
+    }
+}